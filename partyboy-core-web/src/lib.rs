@@ -2,6 +2,7 @@ use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use wasm_bindgen::prelude::*;
 
 pub use partyboy_core::input::Input;
+pub use partyboy_core::ppu::pixel_format::PixelFormat;
 pub use partyboy_core::ppu::rgb::Rgb;
 pub use partyboy_core::GameBoy;
 
@@ -28,6 +29,14 @@ pub fn batch_ticks(gb: &mut GameBoy, ticks: u64) -> u64 {
     0
 }
 
+/// The current frame in `format`, ready to be drawn without converting it in js
+#[wasm_bindgen]
+pub fn render_frame(gb: &GameBoy, format: PixelFormat) -> Vec<u8> {
+    let mut frame = vec![0; 160 * 144 * format.bytes_per_pixel()];
+    gb.render_frame_buffer(format, &mut frame);
+    frame
+}
+
 #[wasm_bindgen]
 pub fn handle_ticks(gb: &mut GameBoy) -> Vec<f32> {
     let mut samples = Vec::new();
@@ -19,7 +19,7 @@ mod square_channel;
 mod sweep;
 
 const SAMPLE_BUFFER_LEN: usize = 512;
-const TICKS_PER_SAMPLE: u32 = 87;
+pub(crate) const TICKS_PER_SAMPLE: u32 = 87;
 
 pub type Sample = f32;
 
@@ -2,12 +2,18 @@ use crate::{
     GameBoy,
     bus::{Bus, CgbCompatibility},
     cartridge::Cartridge,
-    ppu::{ObjectPriorityMode, cgb_palette, rgb::Rgb},
+    patch,
+    ppu::{
+        ObjectPriorityMode,
+        cgb_palette::{self, BootPaletteCombo},
+        rgb::{ColorCorrection, Rgb},
+    },
+    sgb::Sgb,
 };
 use thiserror::Error;
 
 #[cfg(feature = "web")]
-use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::{JsError, prelude::wasm_bindgen};
 
 pub type SerialWriteHandler = Box<dyn FnMut(u8)>;
 
@@ -20,6 +26,12 @@ pub enum GameBoyBuilderError {
     UnableToParseBios,
     #[error("Internal error: unable to parse bios skip snapshot")]
     UnableToLoadBiosSkipSnapshot,
+    #[error("Unable to apply the patch to the rom")]
+    UnableToApplyPatch,
+    #[error("A custom palette must have exactly 12 colors")]
+    InvalidCustomPalette,
+    #[error("The rom is invalid or its cartridge type isn't supported")]
+    InvalidRom,
 }
 
 #[cfg_attr(feature = "web", wasm_bindgen)]
@@ -27,7 +39,12 @@ pub struct GameBoyBuilder {
     rom: Option<Vec<u8>>,
     ram: Option<Vec<u8>>,
     bios: Option<Vec<u8>>,
+    patch: Option<Vec<u8>>,
     serial_write_handler: Option<SerialWriteHandler>,
+    sgb: bool,
+    color_correction: ColorCorrection,
+    palette_combo: Option<BootPaletteCombo>,
+    custom_palette: Option<Vec<u32>>,
 }
 
 impl Default for GameBoyBuilder {
@@ -43,6 +60,11 @@ impl GameBoyBuilder {
             ram: None,
             serial_write_handler: None,
             bios: None,
+            patch: None,
+            sgb: false,
+            color_correction: ColorCorrection::None,
+            palette_combo: None,
+            custom_palette: None,
         }
     }
 
@@ -65,6 +87,13 @@ impl GameBoyBuilder {
         builder
     }
 
+    /// An IPS, UPS or BPS patch to apply to the rom
+    pub fn patch(self, patch: Vec<u8>) -> Self {
+        let mut builder = self;
+        builder.patch = Some(patch);
+        builder
+    }
+
     /// Must be a gameboy color bios?
     //  TODO: what happens if DMG bios is used
     pub fn bios(self, bios: Vec<u8>) -> Self {
@@ -73,12 +102,117 @@ impl GameBoyBuilder {
         builder
     }
 
-    fn create_gameboy_from_snapshot(self) -> Result<GameBoy, GameBoyBuilderError> {
+    /// Run carts that support it in Super Game Boy mode. Carts that don't declare SGB
+    /// support, or that are made for the CGB, ignore this. So do carts that also run on
+    /// the CGB when a bios is given, as the CGB bios starts them in CGB mode
+    pub fn sgb(self, sgb: bool) -> Self {
+        let mut builder = self;
+        builder.sgb = sgb;
+        builder
+    }
+
+    /// How CGB colors are shown, can be changed later with
+    /// [`GameBoy::set_color_correction`]
+    pub fn color_correction(self, color_correction: ColorCorrection) -> Self {
+        let mut builder = self;
+        builder.color_correction = color_correction;
+        builder
+    }
+
+    /// Picks the palette DMG games get in biosless mode like the CGB bios combos do,
+    /// instead of choosing it by the title hash
+    pub fn palette_combo(self, combo: BootPaletteCombo) -> Self {
+        let mut builder = self;
+        builder.palette_combo = Some(combo);
+        builder
+    }
+
+    /// 12 RGB32 colors DMG games get in biosless mode, 4 each for the background and both
+    /// object palettes. Takes precedence over [`GameBoyBuilder::palette_combo`]
+    pub fn custom_palette(self, palette: Vec<u32>) -> Self {
+        let mut builder = self;
+        builder.custom_palette = Some(palette);
+        builder
+    }
+
+    /// The palettes DMG games are colorized with, in RGB32
+    fn dmg_palettes(
+        custom_palette: Option<&[u32]>,
+        palette_combo: Option<BootPaletteCombo>,
+        cartridge: &Cartridge,
+    ) -> Result<[u32; 12], GameBoyBuilderError> {
+        if let Some(palette) = custom_palette {
+            return palette
+                .try_into()
+                .map_err(|_| GameBoyBuilderError::InvalidCustomPalette);
+        }
+
+        Ok(match palette_combo {
+            Some(combo) => cgb_palette::get_combo_palettes(combo),
+            None => cgb_palette::get_color_palettes(cartridge),
+        })
+    }
+
+    fn enable_sgb(gb: &mut GameBoy) {
+        let Some(cartridge) = &gb.bus.cartridge else {
+            return;
+        };
+
+        let compatibility = CgbCompatibility::from(cartridge.read_rom(0x143));
+        if !Sgb::is_supported(cartridge) || matches!(compatibility, CgbCompatibility::CgbOnly) {
+            return;
+        }
+
+        // games that also run on a CGB look at A after the boot rom to tell which console
+        // they are on, so they have to be shown the one a SGB leaves behind. The CGB bios
+        // sets that up itself and the game would be on a CGB with a SGB attached
+        let cgb_and_dmg = matches!(compatibility, CgbCompatibility::CgbAndDmg);
+        if cgb_and_dmg && gb.bus.bios_enabled {
+            log::warn!("Not running in SGB mode, the bios starts this game in CGB mode");
+            return;
+        }
+
+        log::info!("Running in SGB mode");
+        gb.bus.sgb = Some(Sgb::new());
+
+        if cgb_and_dmg {
+            gb.bus.write_u8(0xFF4C, 0x04);
+            gb.cpu.set_sgb_boot_registers();
+        }
+    }
+
+    fn apply_patch(self) -> Result<Self, GameBoyBuilderError> {
+        let mut builder = self;
+        if let (Some(rom), Some(patch)) = (builder.rom.as_mut(), builder.patch.take()) {
+            *rom = patch::apply(rom, &patch).map_err(|e| {
+                log::error!("{}", e);
+                GameBoyBuilderError::UnableToApplyPatch
+            })?;
+            log::info!("Applied patch to the rom");
+        }
+        Ok(builder)
+    }
+
+    fn create_cartridge(
+        rom: Option<Vec<u8>>,
+        ram: Option<Vec<u8>>,
+    ) -> Result<Option<Cartridge>, GameBoyBuilderError> {
+        rom.map(|rom| Cartridge::new(rom, ram))
+            .transpose()
+            .map_err(|e| {
+                log::error!("{}", e);
+                GameBoyBuilderError::InvalidRom
+            })
+    }
+
+    fn create_gameboy_from_snapshot(
+        self,
+        cartridge: Option<Cartridge>,
+    ) -> Result<GameBoy, GameBoyBuilderError> {
         log::info!("SKIPPING BIOS VIA SNAPSHOT");
         let bios_skip_snapshot = include_bytes!("../../bin/bios_skip_snapshot.bin");
         let mut gb: GameBoy = rmp_serde::from_slice(bios_skip_snapshot)
             .map_err(|_| GameBoyBuilderError::UnableToLoadBiosSkipSnapshot)?;
-        let cartridge = self.rom.map(|rom| Cartridge::new(rom, self.ram));
 
         gb.bus.ppu.gpu_vram[0].iter_mut().for_each(|x| *x = 0);
         gb.bus.ppu.gpu_vram[1].iter_mut().for_each(|x| *x = 0);
@@ -104,14 +238,12 @@ impl GameBoyBuilder {
             CgbCompatibility::None | CgbCompatibility::CgbAndDmg
         ) {
             if let Some(cartridge) = &cartridge {
-                // unwrap: get_color_palettes(..) returns an array of 12 too
-                let palettes: [Rgb; 12] = cgb_palette::get_color_palettes(cartridge)
-                    .into_iter()
-                    .map(Rgb::from_rgb32)
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap();
-
+                let palettes = Self::dmg_palettes(
+                    self.custom_palette.as_deref(),
+                    self.palette_combo,
+                    cartridge,
+                )?
+                .map(Rgb::from_rgb32);
                 gb.bus.ppu.override_color_palettes(&palettes);
             }
         }
@@ -132,38 +264,82 @@ impl GameBoyBuilder {
         Ok(gb)
     }
 
-    #[cfg(not(feature = "web"))]
-    pub fn build(self) -> Result<GameBoy, GameBoyBuilderError> {
-        match self.bios {
+    fn try_build(self) -> Result<GameBoy, GameBoyBuilderError> {
+        let mut builder = self.apply_patch()?;
+        let cartridge = Self::create_cartridge(builder.rom.take(), builder.ram.take())?;
+        let sgb = builder.sgb;
+        let color_correction = builder.color_correction;
+        let mut gb = match builder.bios {
             Some(bios) => {
-                let serial_write_handler = self
+                let serial_write_handler = builder
                     .serial_write_handler
                     .unwrap_or_else(|| Box::new(Bus::get_handle_blargg_output()));
                 let bios: [u8; 2304] = bios
                     .try_into()
                     .map_err(|_| GameBoyBuilderError::UnableToParseBios)?;
 
-                Ok(GameBoy::new(self.rom, self.ram, bios, serial_write_handler))
+                GameBoy::new(cartridge, bios, serial_write_handler)
             }
-            None => self.create_gameboy_from_snapshot(),
+            None => builder.create_gameboy_from_snapshot(cartridge)?,
+        };
+
+        if sgb {
+            Self::enable_sgb(&mut gb);
         }
+        gb.set_color_correction(color_correction);
+
+        Ok(gb)
+    }
+
+    #[cfg(not(feature = "web"))]
+    pub fn build(self) -> Result<GameBoy, GameBoyBuilderError> {
+        self.try_build()
     }
 
+    /// Errors are thrown as JS exceptions
     #[cfg(feature = "web")]
-    pub fn build(self) -> GameBoy {
-        match self.bios {
-            Some(bios) => {
-                let serial_write_handler = self
-                    .serial_write_handler
-                    .unwrap_or_else(|| Box::new(Bus::get_handle_blargg_output()));
-                let bios: [u8; 2304] = bios
-                    .try_into()
-                    .map_err(|_| GameBoyBuilderError::UnableToParseBios)
-                    .unwrap();
+    pub fn build(self) -> Result<GameBoy, JsError> {
+        Ok(self.try_build()?)
+    }
+}
 
-                GameBoy::new(self.rom, self.ram, bios, serial_write_handler)
-            }
-            None => self.create_gameboy_from_snapshot().unwrap(),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgb_rom(cgb_flag: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x143] = cgb_flag;
+        rom[0x146] = 0x03;
+        rom[0x14B] = 0x33;
+        rom
+    }
+
+    #[test]
+    fn sgb_mode_with_bios() {
+        let build = |cgb_flag| {
+            GameBoyBuilder::new()
+                .rom(sgb_rom(cgb_flag))
+                .bios(vec![0; 0x900])
+                .sgb(true)
+                .try_build()
+                .unwrap()
+        };
+
+        assert!(build(0x00).is_sgb());
+        // the bios would show the game a CGB
+        assert!(!build(0x80).is_sgb());
+    }
+
+    #[test]
+    fn sgb_mode_without_bios() {
+        let gb = GameBoyBuilder::new()
+            .rom(sgb_rom(0x80))
+            .sgb(true)
+            .try_build()
+            .unwrap();
+
+        assert!(gb.is_sgb());
+        assert_eq!(gb.cpu.trace_registers().af, 0x0100);
     }
 }
@@ -10,8 +10,14 @@ use crate::{
     common::{BoxedSlice, D2Array},
     cpu::speed_controller::CpuSpeedController,
     dma::oam::OamDma,
+    infrared::InfraredPort,
+    ppu::frame_blend::FrameBlender,
+    serial::Serial,
+    sgb::Sgb,
 };
 
+#[cfg(feature = "debug_info")]
+use crate::debug::breakpoint::{Breakpoints, WatchAccess};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -78,6 +84,35 @@ pub(crate) struct Bus {
     pub input: Input,
     pub cpu_speed_controller: CpuSpeedController,
     pub apu: Apu,
+
+    #[cfg(feature = "debug_info")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub breakpoints: Breakpoints,
+
+    /// Set when running a cart in Super Game Boy mode
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sgb: Option<Sgb>,
+
+    #[cfg_attr(feature = "serde", serde(default = "Serial::new"))]
+    pub serial: Serial,
+
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub infrared: InfraredPort,
+
+    /// Set whenever the game writes to the cartridge ram or flash, so frontends know when
+    /// to write a save
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cartridge_ram_dirty: bool,
+
+    /// Set when blended frames were requested, kept up to date every frame
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub frame_blender: Option<FrameBlender>,
+
+    /// Makes `LY` always read as this value, see [`GameBoy::enable_gameboy_doctor_mode`]
+    ///
+    /// [`GameBoy::enable_gameboy_doctor_mode`]: crate::GameBoy::enable_gameboy_doctor_mode
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub fixed_ly: Option<u8>,
 }
 
 impl Bus {
@@ -109,6 +144,18 @@ impl Bus {
             input: Input::new(),
             cpu_speed_controller: CpuSpeedController::new(CgbCompatibility::CgbOnly),
             apu: Apu::new(),
+
+            #[cfg(feature = "debug_info")]
+            breakpoints: Breakpoints::default(),
+
+            sgb: None,
+
+            serial: Serial::new(),
+
+            infrared: InfraredPort::default(),
+            cartridge_ram_dirty: false,
+            frame_blender: None,
+            fixed_ly: None,
         }
     }
 
@@ -134,6 +181,16 @@ impl Bus {
     }
 
     pub fn read_u8(&self, addr: u16) -> u8 {
+        let val = self.peek_u8(addr);
+
+        #[cfg(feature = "debug_info")]
+        self.breakpoints.on_access(addr, val, WatchAccess::Read);
+
+        val
+    }
+
+    /// Reads from the bus without any debugging side effects (i.e. watchpoints)
+    pub fn peek_u8(&self, addr: u16) -> u8 {
         match addr {
             0x0000..=0x00FF if self.bios_enabled => self.bios[addr as usize],
             0x0200..=0x08FF if self.bios_enabled => self.bios[addr as usize],
@@ -169,7 +226,13 @@ impl Bus {
             }
 
             // 0xFF00 and above
-            0xFF00 => self.input.read_joyp(),
+            0xFF00 => match &self.sgb {
+                Some(sgb) => sgb.read_joyp(&self.input),
+                None => self.input.read_joyp(),
+            },
+            0xFF01..=0xFF02 => self
+                .serial
+                .read(addr, self.console_compatibility_mode.is_cgb_mode()),
             0xFF04..=0xFF07 => self.timer.read(addr),
             0xFF0F => 0b1110_0000 | (self.interrupts.flags & 0b0001_1111),
             0xFFFF => self.interrupts.enable,
@@ -177,12 +240,17 @@ impl Bus {
             0xFF46 => self.oam_dma.read_u8(),
             0xFF51..=0xFF55 => self.ppu.hdma.read_u8(addr),
 
+            0xFF44 => self.fixed_ly.unwrap_or_else(|| self.ppu.read_u8(addr)),
             0xFF40..=0xFF4B => self.ppu.read_u8(addr),
             0xFF4D => self.cpu_speed_controller.read_key1(),
             0xFF4F => self.ppu.read_u8(addr),
             0xFF68..=0xFF6B => self.ppu.read_u8(addr),
 
             0xFF70 => self.working_ram_bank as u8,
+            0xFF56 => match self.console_compatibility_mode {
+                CgbCompatibility::None => 0xFF,
+                _ => self.infrared.read(),
+            },
 
             0xFF10..=0xFF14 => self.apu.read_u8(addr),
             0xFF16..=0xFF19 => self.apu.read_u8(addr),
@@ -197,16 +265,21 @@ impl Bus {
     }
 
     pub fn write_u8(&mut self, addr: u16, val: u8) {
+        #[cfg(feature = "debug_info")]
+        self.breakpoints.on_access(addr, val, WatchAccess::Write);
+
         match addr {
             0x0000..=0x7FFF => {
                 if let Some(cartridge) = &mut self.cartridge {
-                    cartridge.write_rom(addr, val)
+                    cartridge.write_rom(addr, val);
+                    self.cartridge_ram_dirty |= cartridge.take_dirty();
                 }
             }
             0x8000..=0x9FFF => self.ppu.write_vram(addr - 0x8000, val),
             0xA000..=0xBFFF => {
                 if let Some(cartridge) = &mut self.cartridge {
-                    cartridge.write_ram(addr - 0xA000, val)
+                    self.cartridge_ram_dirty |= cartridge.is_ram_enabled();
+                    cartridge.write_ram(addr - 0xA000, val);
                 }
             }
 
@@ -239,8 +312,20 @@ impl Bus {
             0xFF30..=0xFF3F => self.apu.write_u8(addr, val),
 
             // 0xFF00 and above
-            0xFF00 => self.input.set_column_line(val),
-            0xFF01 => (self.serial_write_handler)(val),
+            0xFF00 => {
+                self.input.set_column_line(val);
+                if let Some(sgb) = &mut self.sgb {
+                    sgb.write_joyp(val);
+                }
+            }
+            0xFF01 => {
+                (self.serial_write_handler)(val);
+                self.serial
+                    .write(addr, val, self.console_compatibility_mode.is_cgb_mode());
+            }
+            0xFF02 => self
+                .serial
+                .write(addr, val, self.console_compatibility_mode.is_cgb_mode()),
             0xFF03..=0xFF07 => self.timer.write(addr, val),
             0xFF0F => self.interrupts.flags = val,
             0xFF50 => {
@@ -277,6 +362,10 @@ impl Bus {
             }
             0xFF4F => self.ppu.write_u8(addr, val, &mut self.interrupts),
             0xFF68..=0xFF6C => self.ppu.write_u8(addr, val, &mut self.interrupts),
+            0xFF56 => match self.console_compatibility_mode {
+                CgbCompatibility::None => {}
+                _ => self.infrared.write(val),
+            },
 
             0xFF70 => {
                 let mut bank = (val & 0b0000_0111) as usize;
@@ -304,7 +393,49 @@ impl Bus {
     }
 
     pub fn tick_ppu(&mut self) {
+        let ly = self.ppu.ly;
         self.ppu.tick(&mut self.interrupts);
+
+        if ly == 143 && self.ppu.ly == 144 {
+            if let Some(sgb) = &mut self.sgb {
+                sgb.on_frame(&*self.ppu.shade_buffer, &mut *self.ppu.frame_buffer);
+            }
+            if let Some(frame_blender) = &mut self.frame_blender {
+                frame_blender.on_frame(&*self.ppu.frame_buffer);
+            }
+        }
+
+        #[cfg(feature = "debug_info")]
+        self.breakpoints.on_ppu_tick(&self.ppu);
+    }
+
+    /// The bank currently mapped at `addr`. Only ROM, VRAM and WRAM are banked,
+    /// everything else is reported as bank 0
+    pub fn mapped_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x00FF if self.bios_enabled => 0,
+            0x0200..=0x08FF if self.bios_enabled => 0,
+
+            0x0000..=0x7FFF => self
+                .cartridge
+                .as_ref()
+                .map(|cart| cart.rom_bank(addr))
+                .unwrap_or(0),
+            0x8000..=0x9FFF => (self.ppu.gpu_vram_bank & 1) as usize,
+            0xD000..=0xDFFF | 0xF000..=0xFDFF => self.working_ram_bank,
+
+            _ => 0,
+        }
+    }
+
+    /// Like `peek_u8`, but the switchable ROM region reads from `bank` instead of
+    /// whatever bank the MBC currently has mapped
+    #[cfg(feature = "debug_info")]
+    pub fn peek_u8_in_bank(&self, bank: usize, addr: u16) -> u8 {
+        match (addr, &self.cartridge) {
+            (0x4000..=0x7FFF, Some(cart)) => cart.peek_rom_bank(bank, addr),
+            _ => self.peek_u8(addr),
+        }
     }
 
     pub fn hdma_copy_word(&mut self) -> bool {
@@ -0,0 +1,504 @@
+//! The Game Boy Camera (Pocket Camera) cartridge, with its M64282FP image sensor.
+//!
+//! See also: https://gbdev.io/pandocs/Gameboy_Camera.html
+//!
+//! A capture is processed all at once when it finishes, the resulting 128x112 image is
+//! written as 2bpp tiles to RAM bank 0, starting at 0xA100. Gain and exposure are
+//! approximated as plain multipliers, which is close enough for games to work.
+
+use std::sync::mpsc::Receiver;
+
+#[cfg(feature = "serde")]
+use {
+    super::serialize::{ram_bank_deserialize, ram_bank_serialize},
+    serde::{Deserialize, Serialize},
+    serde_big_array::BigArray,
+};
+
+use super::{CartridgeInterface, init_rom_and_ram};
+
+pub const IMAGE_WIDTH: usize = 128;
+pub const IMAGE_HEIGHT: usize = 112;
+
+const IMAGE_OFFSET: usize = 0x100;
+const REGISTER_COUNT: usize = 0x36;
+
+const REG_CONTROL: usize = 0x00;
+const REG_GAIN: usize = 0x01;
+const REG_EXPOSURE_HIGH: usize = 0x02;
+const REG_EXPOSURE_LOW: usize = 0x03;
+const REG_EDGE: usize = 0x04;
+const REG_MATRIX: usize = 0x06;
+
+/// Where the pictures come from. Pixels are 8 bit luminance, 0 being black
+pub trait CameraSource: Send {
+    /// Returns the next `IMAGE_WIDTH` x `IMAGE_HEIGHT` image, row by row
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/// A gradient with a checkerboard on top, used when no source was set
+#[derive(Default)]
+pub struct TestPattern;
+
+impl CameraSource for TestPattern {
+    fn capture(&mut self) -> Vec<u8> {
+        (0..IMAGE_HEIGHT)
+            .flat_map(|y| (0..IMAGE_WIDTH).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let gradient = (x * 255 / (IMAGE_WIDTH - 1)) as u8;
+                match (x / 16 + y / 16) % 2 {
+                    0 => gradient,
+                    _ => gradient / 2,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The same picture for every capture
+#[derive(Clone)]
+pub struct StillImage {
+    pixels: Vec<u8>,
+}
+
+impl StillImage {
+    /// Scales an 8 bit luminance image of any size to fit the sensor
+    pub fn from_luma(width: usize, height: usize, pixels: &[u8]) -> Self {
+        Self {
+            pixels: scale_to_sensor(width, height, pixels),
+        }
+    }
+}
+
+impl CameraSource for StillImage {
+    fn capture(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/// Frames sent by another program or thread. Each capture uses the latest frame received,
+/// frames have to be `IMAGE_WIDTH` x `IMAGE_HEIGHT` luminance images
+pub struct FrameFeed {
+    frames: Receiver<Vec<u8>>,
+    latest: Vec<u8>,
+}
+
+impl FrameFeed {
+    pub fn new(frames: Receiver<Vec<u8>>) -> Self {
+        Self {
+            frames,
+            latest: vec![0; IMAGE_WIDTH * IMAGE_HEIGHT],
+        }
+    }
+}
+
+impl CameraSource for FrameFeed {
+    fn capture(&mut self) -> Vec<u8> {
+        if let Some(frame) = self
+            .frames
+            .try_iter()
+            .filter(|frame| frame.len() == IMAGE_WIDTH * IMAGE_HEIGHT)
+            .last()
+        {
+            self.latest = frame;
+        }
+
+        self.latest.clone()
+    }
+}
+
+/// Nearest neighbour scaling, cropping the source to keep its aspect ratio
+fn scale_to_sensor(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    if width == 0 || height == 0 || pixels.len() < width * height {
+        return vec![0; IMAGE_WIDTH * IMAGE_HEIGHT];
+    }
+
+    let scale = f32::min(
+        width as f32 / IMAGE_WIDTH as f32,
+        height as f32 / IMAGE_HEIGHT as f32,
+    );
+    let x_offset = (width as f32 - IMAGE_WIDTH as f32 * scale) / 2.0;
+    let y_offset = (height as f32 - IMAGE_HEIGHT as f32 * scale) / 2.0;
+
+    (0..IMAGE_HEIGHT)
+        .flat_map(|y| (0..IMAGE_WIDTH).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let src_x = ((x_offset + x as f32 * scale) as usize).min(width - 1);
+            let src_y = ((y_offset + y as f32 * scale) as usize).min(height - 1);
+            pixels[src_y * width + src_x]
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PocketCamera {
+    is_ram_enabled: bool,
+    registers_mapped: bool,
+
+    current_rom_bank: usize,
+    current_ram_bank: usize,
+
+    #[cfg_attr(feature = "serde", serde(with = "BigArray"))]
+    registers: [u8; REGISTER_COUNT],
+    /// T-cycles until the running capture finishes
+    capture_cycles_left: u32,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<Box<dyn CameraSource>>,
+
+    #[serde(skip)]
+    rom_banks: Vec<[u8; 0x4000]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "ram_bank_serialize",
+            deserialize_with = "ram_bank_deserialize"
+        )
+    )]
+    ram_banks: Vec<[u8; 0x2000]>,
+}
+
+impl PocketCamera {
+    pub fn new(
+        rom: Vec<u8>,
+        ram: Option<Vec<u8>>,
+        num_rom_banks: usize,
+        num_ram_banks: usize,
+    ) -> Self {
+        let (rom_banks, ram_banks) = init_rom_and_ram(rom, ram, num_rom_banks, num_ram_banks);
+
+        Self {
+            is_ram_enabled: false,
+            registers_mapped: false,
+
+            current_rom_bank: 1,
+            current_ram_bank: 0,
+
+            registers: [0; REGISTER_COUNT],
+            capture_cycles_left: 0,
+
+            source: None,
+
+            rom_banks,
+            ram_banks,
+        }
+    }
+
+    pub fn set_source(&mut self, source: Option<Box<dyn CameraSource>>) {
+        self.source = source;
+    }
+
+    pub fn take_source(&mut self) -> Option<Box<dyn CameraSource>> {
+        self.source.take()
+    }
+
+    fn exposure(&self) -> u32 {
+        u16::from_be_bytes([
+            self.registers[REG_EXPOSURE_HIGH],
+            self.registers[REG_EXPOSURE_LOW],
+        ]) as u32
+    }
+
+    /// The capture takes `32446 + (N ? 0 : 512) + 16 * exposure` M-cycles
+    fn capture_cycles(&self) -> u32 {
+        let n = self.registers[REG_CONTROL] & 0b1000_0000 != 0;
+        let m_cycles = 32446 + if n { 0 } else { 512 } + 16 * self.exposure();
+        m_cycles * 4
+    }
+
+    pub fn tick(&mut self) {
+        if self.capture_cycles_left == 0 {
+            return;
+        }
+
+        self.capture_cycles_left -= 1;
+        if self.capture_cycles_left == 0 {
+            self.finish_capture();
+        }
+    }
+
+    /// The sensor value at a pixel after gain and exposure. Pixels outside the image
+    /// repeat the edge, which matters for edge enhancement
+    fn sensor_value(image: &[u8], x: isize, y: isize, multiplier: f32) -> f32 {
+        let x = x.clamp(0, IMAGE_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, IMAGE_HEIGHT as isize - 1) as usize;
+        image[y * IMAGE_WIDTH + x] as f32 * multiplier
+    }
+
+    fn process(&self, image: &[u8]) -> Vec<u8> {
+        let gain = 1.0 + (self.registers[REG_GAIN] & 0x1F) as f32 / 16.0;
+        let exposure = self.exposure() as f32 / 0x1000 as f32;
+        let multiplier = gain * exposure;
+
+        let edge_ratio = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0]
+            [(self.registers[REG_EDGE] >> 4) as usize & 7];
+        let enhance_edges = self.registers[REG_GAIN] & 0xE0 == 0xE0;
+        let invert = self.registers[REG_EDGE] & 0b0000_1000 != 0;
+
+        let mut tiles = vec![0; IMAGE_WIDTH * IMAGE_HEIGHT / 4];
+        for y in 0..IMAGE_HEIGHT {
+            for x in 0..IMAGE_WIDTH {
+                let (sx, sy) = (x as isize, y as isize);
+                let mut value = Self::sensor_value(image, sx, sy, multiplier);
+
+                if enhance_edges {
+                    let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                        .into_iter()
+                        .map(|(dx, dy)| Self::sensor_value(image, sx + dx, sy + dy, multiplier))
+                        .sum::<f32>();
+                    value += (value * 4.0 - neighbours) * edge_ratio;
+                }
+
+                let mut value = value.clamp(0.0, 255.0) as u8;
+                if invert {
+                    value = 255 - value;
+                }
+
+                // each pixel of a 4x4 block has its own 3 thresholds
+                let matrix = REG_MATRIX + ((y % 4) * 4 + x % 4) * 3;
+                let thresholds = &self.registers[matrix..matrix + 3];
+                let shade = match value {
+                    v if v < thresholds[0] => 3,
+                    v if v < thresholds[1] => 2,
+                    v if v < thresholds[2] => 1,
+                    _ => 0,
+                };
+
+                let tile = (y / 8) * (IMAGE_WIDTH / 8) + x / 8;
+                let offset = tile * 16 + (y % 8) * 2;
+                let bit = 7 - (x % 8);
+                tiles[offset] |= (shade & 1) << bit;
+                tiles[offset + 1] |= ((shade >> 1) & 1) << bit;
+            }
+        }
+
+        tiles
+    }
+
+    fn finish_capture(&mut self) {
+        let image = match &mut self.source {
+            Some(source) => source.capture(),
+            None => TestPattern.capture(),
+        };
+
+        if image.len() == IMAGE_WIDTH * IMAGE_HEIGHT {
+            let tiles = self.process(&image);
+            if let Some(bank) = self.ram_banks.first_mut() {
+                bank[IMAGE_OFFSET..IMAGE_OFFSET + tiles.len()].copy_from_slice(&tiles);
+            }
+        } else {
+            log::warn!("Camera source returned an image of the wrong size, ignoring it");
+        }
+
+        self.registers[REG_CONTROL] &= !1;
+    }
+}
+
+impl CartridgeInterface for PocketCamera {
+    fn read_rom(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => self.rom_banks[0][addr as usize],
+            0x4000..=0x7FFF => self.rom_banks[self.current_rom_bank][(addr - 0x4000) as usize],
+            _ => panic!(),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.is_ram_enabled = value & 0x0F == 0x0A,
+
+            0x2000..=0x3FFF => {
+                self.current_rom_bank = (value & 0x3F) as usize % self.rom_banks.len();
+            }
+
+            0x4000..=0x5FFF => {
+                self.registers_mapped = value & 0x10 != 0;
+                if !self.registers_mapped {
+                    self.current_ram_bank = (value & 0x0F) as usize % self.ram_banks.len().max(1);
+                }
+            }
+
+            0x6000..=0x7FFF => {}
+
+            _ => panic!(),
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if self.registers_mapped {
+            // only the control register can be read back
+            return match addr & 0x7F {
+                0 => self.registers[REG_CONTROL],
+                _ => 0x00,
+            };
+        }
+
+        if !self.is_ram_enabled {
+            return 0xFF;
+        }
+
+        // the image can't be read while the sensor is writing it
+        if self.capture_cycles_left > 0 {
+            return 0x00;
+        }
+
+        self.ram_banks
+            .get(self.current_ram_bank)
+            .map(|bank| bank[addr as usize])
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if self.registers_mapped {
+            let register = (addr & 0x7F) as usize;
+            if register == REG_CONTROL {
+                self.registers[REG_CONTROL] = value & 0b1000_0111;
+                self.capture_cycles_left = match value & 1 {
+                    0 => 0,
+                    _ => self.capture_cycles(),
+                };
+            } else if register < REGISTER_COUNT {
+                self.registers[register] = value;
+            }
+            return;
+        }
+
+        if !self.is_ram_enabled {
+            return;
+        }
+
+        if let Some(bank) = self.ram_banks.get_mut(self.current_ram_bank) {
+            bank[addr as usize] = value;
+        }
+    }
+
+    fn has_ram(&self) -> bool {
+        !self.ram_banks.is_empty()
+    }
+
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled && !self.registers_mapped
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
+        &self.ram_banks
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
+    fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
+        self.rom_banks = rom;
+    }
+
+    fn take_rom(self) -> Vec<[u8; 0x4000]> {
+        self.rom_banks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> PocketCamera {
+        PocketCamera::new(vec![0; 0x4000 * 64], None, 64, 16)
+    }
+
+    fn write_register(camera: &mut PocketCamera, register: u16, value: u8) {
+        camera.write_rom(0x4000, 0x10);
+        camera.write_ram(register, value);
+    }
+
+    struct Flat(u8);
+
+    impl CameraSource for Flat {
+        fn capture(&mut self) -> Vec<u8> {
+            vec![self.0; IMAGE_WIDTH * IMAGE_HEIGHT]
+        }
+    }
+
+    fn capture(camera: &mut PocketCamera) {
+        write_register(camera, 0, 0x81);
+        assert_eq!(camera.read_ram(0) & 1, 1);
+        while camera.capture_cycles_left > 0 {
+            camera.tick();
+        }
+        assert_eq!(camera.read_ram(0) & 1, 0);
+        camera.write_rom(0x4000, 0);
+    }
+
+    #[test]
+    fn capture_takes_exposure_time() {
+        let mut camera = camera();
+        write_register(&mut camera, 2, 0x01);
+        write_register(&mut camera, 3, 0x00);
+        write_register(&mut camera, 0, 0x81);
+
+        assert_eq!(camera.capture_cycles_left, (32446 + 16 * 0x100) * 4);
+    }
+
+    #[test]
+    fn dithers_with_matrix_thresholds() {
+        let mut camera = camera();
+        camera.set_source(Some(Box::new(Flat(0x80))));
+
+        // exposure of 0x1000 with no gain leaves the sensor values as they are
+        write_register(&mut camera, 2, 0x10);
+        write_register(&mut camera, 3, 0x00);
+        for i in 0..16 {
+            let thresholds = if i == 0 {
+                [0x90, 0xA0, 0xB0]
+            } else {
+                [0x10, 0x20, 0x30]
+            };
+            for (j, threshold) in thresholds.into_iter().enumerate() {
+                write_register(&mut camera, (REG_MATRIX + i * 3 + j) as u16, threshold);
+            }
+        }
+
+        capture(&mut camera);
+        camera.write_rom(0x0000, 0x0A);
+
+        // the matrix repeats every 4 pixels. the first pixel of each block is below its
+        // lowest threshold (black), the others are above all of theirs (white)
+        assert_eq!(camera.read_ram(0x100), 0b1000_1000);
+        assert_eq!(camera.read_ram(0x101), 0b1000_1000);
+        assert_eq!(camera.read_ram(0x102), 0);
+    }
+
+    #[test]
+    fn ram_reads_open_bus_while_disabled() {
+        let mut camera = camera();
+        camera.write_rom(0x0000, 0x0A);
+        camera.write_ram(0x10, 0x42);
+        assert_eq!(camera.read_ram(0x10), 0x42);
+
+        camera.write_rom(0x0000, 0x00);
+        assert_eq!(camera.read_ram(0x10), 0xFF);
+    }
+
+    #[test]
+    fn scales_still_images() {
+        let pixels = (0..256 * 224).map(|i| (i % 256) as u8).collect::<Vec<_>>();
+        let mut image = StillImage::from_luma(256, 224, &pixels);
+        let captured = image.capture();
+
+        assert_eq!(captured.len(), IMAGE_WIDTH * IMAGE_HEIGHT);
+        assert_eq!(captured[0], 0);
+        assert_eq!(captured[1], 2);
+    }
+}
@@ -40,6 +40,10 @@ pub struct Mbc1 {
         )
     )]
     ram_banks: Vec<[u8; 0x2000]>,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_battery: bool,
 }
 
 impl Mbc1 {
@@ -48,6 +52,7 @@ impl Mbc1 {
         ram: Option<Vec<u8>>,
         num_rom_banks: usize,
         num_ram_banks: usize,
+        has_battery: bool,
     ) -> Self {
         let rom_bank_mask_lo = match num_rom_banks - 1 {
             0..=1 => 0b0000_0001,
@@ -82,6 +87,7 @@ impl Mbc1 {
 
             rom_banks,
             ram_banks,
+            has_battery,
         }
     }
 
@@ -192,10 +198,34 @@ impl CartridgeInterface for Mbc1 {
         !self.ram_banks.is_empty()
     }
 
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_has_battery(&mut self, has_battery: bool) {
+        self.has_battery = has_battery;
+    }
+
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
         &self.ram_banks
     }
 
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => self.get_mapped_0_bank(),
+            _ => self.current_rom_bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
         self.rom_banks = rom;
     }
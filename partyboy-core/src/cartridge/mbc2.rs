@@ -24,6 +24,10 @@ pub struct Mbc2 {
         )
     )]
     ram_banks: Vec<[u8; 0x2000]>,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_battery: bool,
 }
 
 impl Mbc2 {
@@ -32,6 +36,7 @@ impl Mbc2 {
         ram: Option<Vec<u8>>,
         num_rom_banks: usize,
         num_ram_banks: usize,
+        has_battery: bool,
     ) -> Self {
         let rom_bank_mask = match num_rom_banks - 1 {
             0..=1 => 0b0000_0001,
@@ -40,13 +45,10 @@ impl Mbc2 {
             _ => 0b0000_1111,
         };
 
-        let (rom_banks, mut ram_banks) = init_rom_and_ram(rom, ram, num_rom_banks, num_ram_banks);
-
         // MBC2 always has at least 1 bank:
         // https://gbdev.io/pandocs/The_Cartridge_Header.html#0149--ram-size
-        if num_ram_banks == 0 {
-            ram_banks.push([0; 0x2000]);
-        }
+        let num_ram_banks = num_ram_banks.max(1);
+        let (rom_banks, ram_banks) = init_rom_and_ram(rom, ram, num_rom_banks, num_ram_banks);
 
         Self {
             is_ram_enabled: false,
@@ -54,6 +56,7 @@ impl Mbc2 {
             rom_banks,
             ram_banks,
             rom_bank_mask,
+            has_battery,
         }
     }
 }
@@ -119,10 +122,34 @@ impl CartridgeInterface for Mbc2 {
         !self.ram_banks.is_empty()
     }
 
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_has_battery(&mut self, has_battery: bool) {
+        self.has_battery = has_battery;
+    }
+
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
         &self.ram_banks
     }
 
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
         self.rom_banks = rom;
     }
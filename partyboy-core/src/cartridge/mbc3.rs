@@ -8,6 +8,53 @@ use super::{CartridgeInterface, init_rom_and_ram};
 
 // TODO: RTC impl is broken?
 
+/// The clock state other emulators (VBA, BGB, SameBoy...) append to MBC3 saves: the running
+/// and the latched registers as 32 bit values, then a 32 or 64 bit unix timestamp of when
+/// the save was written
+pub struct RtcFooter {
+    regs: [u8; 5],
+    latched_regs: [u8; 5],
+    timestamp: u64,
+}
+
+impl RtcFooter {
+    /// With a 32 and a 64 bit timestamp
+    pub const SIZES: [usize; 2] = [44, 48];
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if !Self::SIZES.contains(&bytes.len()) {
+            return None;
+        }
+
+        let word = |i: usize| bytes[i * 4];
+        let mut timestamp = [0; 8];
+        timestamp[..bytes.len() - 40].copy_from_slice(&bytes[40..]);
+
+        Some(Self {
+            regs: std::array::from_fn(word),
+            latched_regs: std::array::from_fn(|i| word(5 + i)),
+            timestamp: u64::from_le_bytes(timestamp),
+        })
+    }
+
+    /// The 48 byte version, with a 64 bit timestamp
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.regs
+            .iter()
+            .chain(&self.latched_regs)
+            .flat_map(|&reg| u32::from(reg).to_le_bytes())
+            .chain(self.timestamp.to_le_bytes())
+            .collect()
+    }
+
+    /// How far the clock had counted when the save was written
+    fn seconds(&self) -> u64 {
+        let [seconds, minutes, hours, days_low, days_high] = self.regs.map(u64::from);
+        let days = days_low | ((days_high & 1) << 8);
+        ((days * 24 + hours) * 60 + minutes) * 60 + seconds
+    }
+}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Mbc3 {
     is_ram_rtc_enabled: bool,
@@ -31,6 +78,18 @@ pub struct Mbc3 {
     rtc_banked: bool,
 
     prev_latch_val: u8,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_battery: bool,
+
+    /// Seconds added to the wall clock, so the clock carries on from where the save left it
+    #[cfg_attr(feature = "serde", serde(default))]
+    rtc_offset: i64,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_rtc: bool,
 }
 
 impl Mbc3 {
@@ -39,6 +98,9 @@ impl Mbc3 {
         ram: Option<Vec<u8>>,
         num_rom_banks: usize,
         num_ram_banks: usize,
+        has_battery: bool,
+        has_rtc: bool,
+        rtc: Option<RtcFooter>,
     ) -> Self {
         let rom_bank_mask = match num_rom_banks - 1 {
             0..=1 => 0b0000_0001,
@@ -52,6 +114,13 @@ impl Mbc3 {
         };
 
         let (rom_banks, ram_banks) = init_rom_and_ram(rom, ram, num_rom_banks, num_ram_banks);
+        let (rtc_regs, rtc_offset) = match rtc {
+            Some(rtc) => (
+                rtc.latched_regs,
+                rtc.seconds() as i64 - rtc.timestamp as i64,
+            ),
+            None => ([0; 5], 0),
+        };
 
         Self {
             is_ram_rtc_enabled: false,
@@ -59,14 +128,49 @@ impl Mbc3 {
             current_ram_bank: 0,
             rom_bank_mask,
 
-            rtc_regs: [0; 5],
+            rtc_regs,
             rtc_banked: false,
 
             rom_banks,
             ram_banks,
             prev_latch_val: 204, // random val
+            has_battery,
+            rtc_offset,
+            has_rtc,
         }
     }
+
+    pub fn set_has_rtc(&mut self, has_rtc: bool) {
+        self.has_rtc = has_rtc;
+    }
+
+    /// The clock as it goes at the end of save files, for carts that have one
+    pub fn rtc_footer(&self) -> Option<RtcFooter> {
+        if !self.has_rtc {
+            return None;
+        }
+
+        let timestamp = now_secs();
+        let seconds = timestamp.saturating_add_signed(self.rtc_offset);
+        Some(RtcFooter {
+            regs: clock_regs(seconds, self.rtc_regs[4]),
+            latched_regs: self.rtc_regs,
+            timestamp,
+        })
+    }
+}
+
+/// The clock registers after `seconds`. The flags in the high day register besides the
+/// top bit of the day are kept from `day_high`
+fn clock_regs(seconds: u64, day_high: u8) -> [u8; 5] {
+    let days = seconds / 60 / 60 / 24;
+    [
+        (seconds % 60) as u8,
+        ((seconds / 60) % 60) as u8,
+        (((seconds / 60) / 60) % 24) as u8,
+        days as u8,
+        (day_high & !1) | ((days >> 8) & 1) as u8,
+    ]
 }
 
 impl CartridgeInterface for Mbc3 {
@@ -110,11 +214,8 @@ impl CartridgeInterface for Mbc3 {
 
             0x6000..=0x7FFF => {
                 if self.prev_latch_val == 0x00 && value == 0x01 {
-                    let now = now_secs();
-
-                    self.rtc_regs[0] = (now % 60) as u8;
-                    self.rtc_regs[1] = ((now / 60) % 60) as u8;
-                    self.rtc_regs[2] = (((now / 60) / 60) % 24) as u8;
+                    let now = now_secs().saturating_add_signed(self.rtc_offset);
+                    self.rtc_regs = clock_regs(now, self.rtc_regs[4]);
                 }
 
                 self.prev_latch_val = value;
@@ -150,10 +251,34 @@ impl CartridgeInterface for Mbc3 {
         !self.ram_banks.is_empty()
     }
 
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_rtc_enabled
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_has_battery(&mut self, has_battery: bool) {
+        self.has_battery = has_battery;
+    }
+
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
         &self.ram_banks
     }
 
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
         self.rom_banks = rom;
     }
@@ -186,3 +311,58 @@ extern "C" {
 fn now_secs() -> u64 {
     performance_now() as u64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_carries_on_from_rtc_footer() {
+        // 1:10:00 on day 0, saved an hour ago
+        let mut footer = vec![0; 48];
+        footer[4] = 10;
+        footer[8] = 1;
+        footer[40..].copy_from_slice(&(now_secs() - 60 * 60).to_le_bytes());
+
+        let rtc = RtcFooter::parse(&footer).unwrap();
+        let mut cart = Mbc3::new(vec![0; 0x8000], None, 2, 0, true, true, Some(rtc));
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x6000, 0x01);
+
+        assert!(cart.rtc_regs[0] <= 1);
+        assert_eq!(cart.rtc_regs[1..], [10, 2, 0, 0]);
+    }
+
+    #[test]
+    fn rtc_footer_round_trips() {
+        // day 300, 5:30:00, saved a day ago
+        let timestamp = now_secs() - 24 * 60 * 60;
+        let rtc = RtcFooter {
+            regs: [0, 30, 5, 300u16 as u8, 1],
+            latched_regs: [1, 2, 3, 4, 0],
+            timestamp,
+        };
+        let cart = Mbc3::new(vec![0; 0x8000], None, 2, 0, true, true, Some(rtc));
+
+        let footer = cart.rtc_footer().unwrap().to_bytes();
+        assert_eq!(footer.len(), 48);
+        assert_eq!(
+            footer[20..40],
+            [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let rtc = RtcFooter::parse(&footer).unwrap();
+        let mut cart = Mbc3::new(vec![0; 0x8000], None, 2, 0, true, true, Some(rtc));
+        assert_eq!(cart.rtc_regs, [1, 2, 3, 4, 0]);
+        cart.write_rom(0x6000, 0x00);
+        cart.write_rom(0x6000, 0x01);
+        assert!(cart.rtc_regs[0] <= 1);
+        assert_eq!(cart.rtc_regs[1..], [30, 5, 301u16 as u8, 1]);
+    }
+
+    #[test]
+    fn no_footer_without_rtc() {
+        let cart = Mbc3::new(vec![0; 0x8000], None, 2, 1, true, false, None);
+        assert!(cart.rtc_footer().is_none());
+    }
+}
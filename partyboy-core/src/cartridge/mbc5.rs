@@ -25,6 +25,17 @@ pub struct Mbc5 {
         )
     )]
     ram_banks: Vec<[u8; 0x2000]>,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_battery: bool,
+
+    /// Rumble carts use bit 3 of the ram bank number for the motor, so they can only
+    /// address 8 ram banks
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_rumble: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rumble: bool,
 }
 
 impl Mbc5 {
@@ -33,6 +44,8 @@ impl Mbc5 {
         ram: Option<Vec<u8>>,
         num_rom_banks: usize,
         num_ram_banks: usize,
+        has_battery: bool,
+        has_rumble: bool,
     ) -> Self {
         let rom_bank_mask = match num_rom_banks - 1 {
             0..=1 => 0b0000_0001,
@@ -56,8 +69,17 @@ impl Mbc5 {
 
             rom_banks,
             ram_banks,
+            has_battery,
+
+            has_rumble,
+            rumble: false,
         }
     }
+
+    /// Whether the rumble motor is on
+    pub fn rumble(&self) -> bool {
+        self.rumble
+    }
 }
 
 impl CartridgeInterface for Mbc5 {
@@ -92,7 +114,10 @@ impl CartridgeInterface for Mbc5 {
             }
 
             0x4000..=0x5FFF => {
-                if value <= 0x0F {
+                if self.has_rumble {
+                    self.rumble = value & 0b1000 != 0;
+                    self.current_ram_bank = (value & 0b0111) as usize;
+                } else if value <= 0x0F {
                     self.current_ram_bank = value as usize;
                 }
             }
@@ -123,10 +148,34 @@ impl CartridgeInterface for Mbc5 {
         !self.ram_banks.is_empty()
     }
 
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_has_battery(&mut self, has_battery: bool) {
+        self.has_battery = has_battery;
+    }
+
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
         &self.ram_banks
     }
 
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            _ => self.current_rom_bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
         self.rom_banks = rom;
     }
@@ -0,0 +1,396 @@
+#[cfg(feature = "serde")]
+use {
+    super::serialize::{ram_bank_deserialize, ram_bank_serialize},
+    serde::{Deserialize, Serialize},
+};
+
+use super::{CartridgeInterface, init_rom_and_ram};
+
+/// 1MB of flash, in 8KB banks
+const FLASH_BANKS: usize = 128;
+const FLASH_BANK_SIZE: usize = 0x2000;
+/// 32KB of ram, in 4KB banks
+const RAM_HALF_BANKS: usize = 8;
+const RAM_BANKS: usize = RAM_HALF_BANKS / 2;
+
+/// Macronix MX29F008 ids, returned while in id mode
+const FLASH_MANUFACTURER_ID: u8 = 0xC2;
+const FLASH_DEVICE_ID: u8 = 0x81;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum FlashState {
+    Ready,
+    /// Got `AA` at 0x5555
+    Unlock1,
+    /// Got `55` at 0x2AAA, waiting for a command
+    Unlock2,
+    Id,
+    Program,
+    /// Got `80`, erasing needs a second unlock sequence
+    EraseUnlock0,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+/// MBC6, only used by Net de Get. The rom and ram are split into two independently
+/// switchable halves each, and either rom half can map the 1MB flash chip instead.
+///
+/// Flash commands complete instantly. The flash is stored after the ram, so it ends up
+/// in save files and snapshots along with it.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mbc6 {
+    is_ram_enabled: bool,
+    is_flash_enabled: bool,
+    is_flash_write_enabled: bool,
+
+    /// 8KB bank numbers mapped at 0x4000 and 0x6000
+    rom_bank: [usize; 2],
+    /// Whether 0x4000 and 0x6000 map flash instead of rom
+    flash_selected: [bool; 2],
+    /// 4KB bank numbers mapped at 0xA000 and 0xB000
+    ram_bank: [usize; 2],
+
+    flash_state: FlashState,
+
+    #[serde(skip)]
+    rom_banks: Vec<[u8; 0x4000]>,
+
+    /// The 32KB of ram followed by the flash
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "ram_bank_serialize",
+            deserialize_with = "ram_bank_deserialize"
+        )
+    )]
+    ram_banks: Vec<[u8; 0x2000]>,
+
+    /// Whether the flash was programmed or erased since [`CartridgeInterface::take_dirty`]
+    #[serde(skip)]
+    flash_dirty: bool,
+}
+
+impl Mbc6 {
+    /// `ram` may contain just the ram, or the ram followed by the flash
+    pub fn new(rom: Vec<u8>, ram: Option<Vec<u8>>, num_rom_banks: usize) -> Self {
+        let (rom_banks, mut ram_banks) = init_rom_and_ram(rom, None, num_rom_banks, 0);
+
+        let ram = ram.unwrap_or_default();
+        ram_banks.extend(ram.chunks_exact(0x2000).map(|chunk| {
+            let mut arr = [0; 0x2000];
+            arr.copy_from_slice(chunk);
+            arr
+        }));
+        ram_banks.resize(RAM_BANKS, [0; 0x2000]);
+        // erased flash reads as 0xFF
+        ram_banks.resize(RAM_BANKS + FLASH_BANKS, [0xFF; 0x2000]);
+        ram_banks.truncate(RAM_BANKS + FLASH_BANKS);
+
+        Self {
+            is_ram_enabled: false,
+            is_flash_enabled: false,
+            is_flash_write_enabled: false,
+
+            rom_bank: [0, 0],
+            flash_selected: [false, false],
+            ram_bank: [0, 0],
+
+            flash_state: FlashState::Ready,
+
+            rom_banks,
+            ram_banks,
+            flash_dirty: false,
+        }
+    }
+
+    fn flash_byte(&self, flash_addr: usize) -> u8 {
+        let flash_addr = flash_addr % (FLASH_BANKS * FLASH_BANK_SIZE);
+        self.ram_banks[RAM_BANKS + flash_addr / 0x2000][flash_addr % 0x2000]
+    }
+
+    fn flash_byte_mut(&mut self, flash_addr: usize) -> &mut u8 {
+        let flash_addr = flash_addr % (FLASH_BANKS * FLASH_BANK_SIZE);
+        &mut self.ram_banks[RAM_BANKS + flash_addr / 0x2000][flash_addr % 0x2000]
+    }
+
+    fn write_flash(&mut self, flash_addr: usize, value: u8) {
+        if !self.is_flash_write_enabled {
+            return;
+        }
+
+        // the unlock sequences only look at the low 15 address bits
+        let command_addr = flash_addr & 0x7FFF;
+
+        self.flash_state = match (self.flash_state, command_addr, value) {
+            // programming can only clear bits, erasing sets them again
+            (FlashState::Program, _, _) => {
+                *self.flash_byte_mut(flash_addr) &= value;
+                self.flash_dirty = true;
+                FlashState::Ready
+            }
+
+            (_, _, 0xF0) => FlashState::Ready,
+
+            (FlashState::Ready | FlashState::Id, 0x5555, 0xAA) => FlashState::Unlock1,
+            (FlashState::Unlock1, 0x2AAA, 0x55) => FlashState::Unlock2,
+            (FlashState::Unlock2, 0x5555, 0x90) => FlashState::Id,
+            (FlashState::Unlock2, 0x5555, 0xA0) => FlashState::Program,
+            (FlashState::Unlock2, 0x5555, 0x80) => FlashState::EraseUnlock0,
+
+            (FlashState::EraseUnlock0, 0x5555, 0xAA) => FlashState::EraseUnlock1,
+            (FlashState::EraseUnlock1, 0x2AAA, 0x55) => FlashState::EraseUnlock2,
+            (FlashState::EraseUnlock2, 0x5555, 0x10) => {
+                for bank in &mut self.ram_banks[RAM_BANKS..] {
+                    bank.fill(0xFF);
+                }
+                self.flash_dirty = true;
+                FlashState::Ready
+            }
+            (FlashState::EraseUnlock2, _, 0x30) => {
+                let bank = (flash_addr / FLASH_BANK_SIZE) % FLASH_BANKS;
+                self.ram_banks[RAM_BANKS + bank].fill(0xFF);
+                self.flash_dirty = true;
+                FlashState::Ready
+            }
+
+            (FlashState::Id, _, _) => FlashState::Id,
+            _ => FlashState::Ready,
+        };
+    }
+
+    fn ram_offset(&self, addr: u16) -> (usize, usize) {
+        let half = (addr as usize >> 12) & 1;
+        let bank = self.ram_bank[half] % RAM_HALF_BANKS;
+        let offset = (bank % 2) * 0x1000 + (addr as usize & 0x0FFF);
+        (bank / 2, offset)
+    }
+}
+
+impl CartridgeInterface for Mbc6 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        let half = match addr {
+            0x0000..=0x3FFF => return self.rom_banks[0][addr as usize],
+            0x4000..=0x5FFF => 0,
+            0x6000..=0x7FFF => 1,
+            _ => panic!(),
+        };
+
+        let bank = self.rom_bank[half];
+        let offset = addr as usize & 0x1FFF;
+
+        if self.flash_selected[half] {
+            if !self.is_flash_enabled {
+                return 0xFF;
+            }
+
+            if self.flash_state == FlashState::Id {
+                return match offset {
+                    0 => FLASH_MANUFACTURER_ID,
+                    1 => FLASH_DEVICE_ID,
+                    _ => 0xFF,
+                };
+            }
+
+            return self.flash_byte(bank * FLASH_BANK_SIZE + offset);
+        }
+
+        let bank = bank % (self.rom_banks.len() * 2);
+        self.rom_banks[bank / 2][(bank % 2) * 0x2000 + offset]
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x03FF => self.is_ram_enabled = value & 0x0F == 0x0A,
+            0x0400..=0x07FF => self.ram_bank[0] = (value & 0x07) as usize,
+            0x0800..=0x0BFF => self.ram_bank[1] = (value & 0x07) as usize,
+            0x0C00..=0x0FFF => self.is_flash_enabled = value & 1 != 0,
+            0x1000 => self.is_flash_write_enabled = value & 1 != 0,
+            0x1001..=0x1FFF => {}
+
+            0x2000..=0x27FF => self.rom_bank[0] = (value & 0x7F) as usize,
+            0x2800..=0x2FFF => self.flash_selected[0] = value == 0x08,
+            0x3000..=0x37FF => self.rom_bank[1] = (value & 0x7F) as usize,
+            0x3800..=0x3FFF => self.flash_selected[1] = value == 0x08,
+
+            0x4000..=0x7FFF => {
+                let half = ((addr - 0x4000) / 0x2000) as usize;
+                if self.flash_selected[half] && self.is_flash_enabled {
+                    let flash_addr =
+                        self.rom_bank[half] * FLASH_BANK_SIZE + (addr as usize & 0x1FFF);
+                    self.write_flash(flash_addr, value);
+                }
+            }
+
+            _ => panic!(),
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.is_ram_enabled {
+            return 0xFF;
+        }
+
+        let (bank, offset) = self.ram_offset(addr);
+        self.ram_banks[bank][offset]
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.is_ram_enabled {
+            return;
+        }
+
+        let (bank, offset) = self.ram_offset(addr);
+        self.ram_banks[bank][offset] = value;
+    }
+
+    fn has_ram(&self) -> bool {
+        true
+    }
+
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.flash_dirty)
+    }
+
+    fn has_battery(&self) -> bool {
+        // the header doesn't say, but the flash keeps its contents either way
+        true
+    }
+
+    fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
+        &self.ram_banks
+    }
+
+    /// Banks are reported in 16KB units, like every other cart
+    fn rom_bank(&self, addr: u16) -> usize {
+        match addr {
+            0x0000..=0x3FFF => 0,
+            0x4000..=0x5FFF => self.rom_bank[0] / 2,
+            _ => self.rom_bank[1] / 2,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
+    fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
+        self.rom_banks = rom;
+    }
+
+    fn take_rom(self) -> Vec<[u8; 0x4000]> {
+        self.rom_banks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every 8KB bank starts with its own number
+    fn cart() -> Mbc6 {
+        let rom = (0..8u8)
+            .flat_map(|bank| std::iter::once(bank).chain(std::iter::repeat_n(0, 0x1FFF)))
+            .collect();
+        Mbc6::new(rom, None, 4)
+    }
+
+    fn flash_command(cart: &mut Mbc6, command: u8) {
+        // 0x5555 is in flash bank 2, 0x2AAA in flash bank 1
+        cart.write_rom(0x2000, 2);
+        cart.write_rom(0x3000, 1);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x5555, command);
+    }
+
+    #[test]
+    fn switches_rom_halves_independently() {
+        let mut cart = cart();
+        cart.write_rom(0x2000, 3);
+        cart.write_rom(0x3000, 6);
+
+        assert_eq!(cart.read_rom(0x4000), 3);
+        assert_eq!(cart.read_rom(0x6000), 6);
+    }
+
+    #[test]
+    fn switches_ram_halves_independently() {
+        let mut cart = cart();
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x0400, 3);
+        cart.write_rom(0x0800, 3);
+
+        cart.write_ram(0x0000, 0x42);
+        assert_eq!(cart.read_ram(0x1000), 0x42);
+
+        cart.write_rom(0x0800, 4);
+        assert_eq!(cart.read_ram(0x1000), 0x00);
+    }
+
+    #[test]
+    fn programs_and_erases_flash() {
+        let mut cart = cart();
+        cart.write_rom(0x0C00, 1);
+        cart.write_rom(0x1000, 1);
+        cart.write_rom(0x2800, 0x08);
+        cart.write_rom(0x3800, 0x08);
+
+        flash_command(&mut cart, 0x90);
+        assert_eq!(cart.read_rom(0x4000), FLASH_MANUFACTURER_ID);
+        assert_eq!(cart.read_rom(0x4001), FLASH_DEVICE_ID);
+        cart.write_rom(0x4000, 0xF0);
+
+        flash_command(&mut cart, 0xA0);
+        cart.write_rom(0x2000, 5);
+        cart.write_rom(0x4010, 0x5A);
+        assert_eq!(cart.read_rom(0x4010), 0x5A);
+
+        flash_command(&mut cart, 0x80);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x2000, 5);
+        cart.write_rom(0x4000, 0x30);
+        assert_eq!(cart.read_rom(0x4010), 0xFF);
+    }
+
+    #[test]
+    fn flash_writes_mark_save_dirty() {
+        let mut cart = cart();
+        cart.write_rom(0x0C00, 1);
+        cart.write_rom(0x1000, 1);
+        cart.write_rom(0x2800, 0x08);
+        cart.write_rom(0x3800, 0x08);
+        assert!(!cart.take_dirty());
+
+        flash_command(&mut cart, 0xA0);
+        assert!(!cart.take_dirty());
+        cart.write_rom(0x2000, 5);
+        cart.write_rom(0x4010, 0x5A);
+        assert!(cart.take_dirty());
+        assert!(!cart.take_dirty());
+
+        flash_command(&mut cart, 0x80);
+        cart.write_rom(0x5555, 0xAA);
+        cart.write_rom(0x6AAA, 0x55);
+        cart.write_rom(0x5555, 0x10);
+        assert!(cart.take_dirty());
+    }
+
+    #[test]
+    fn flash_needs_write_enable() {
+        let mut cart = cart();
+        cart.write_rom(0x0C00, 1);
+        cart.write_rom(0x2800, 0x08);
+        cart.write_rom(0x3800, 0x08);
+
+        flash_command(&mut cart, 0xA0);
+        cart.write_rom(0x4000, 0x00);
+        assert_eq!(cart.read_rom(0x4000), 0xFF);
+    }
+}
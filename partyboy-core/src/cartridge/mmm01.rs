@@ -0,0 +1,306 @@
+#[cfg(feature = "serde")]
+use {
+    super::serialize::{ram_bank_deserialize, ram_bank_serialize},
+    serde::{Deserialize, Serialize},
+};
+
+use super::{CartridgeInterface, init_rom_and_ram};
+
+/// MMM01, used by multicarts. It starts out mapping the last 32KB of the rom, which holds
+/// the menu. Once the menu picks a game it locks the outer bank registers and the cart
+/// behaves like an MBC1 limited to the chosen game.
+///
+/// Bits set in the rom/ram bank masks are frozen after locking, which is how the menu
+/// limits the game to its own banks.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Mmm01 {
+    is_ram_enabled: bool,
+    locked: bool,
+
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    rom_bank_mask: u8,
+
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    ram_bank_mask: u8,
+
+    mbc1_mode: bool,
+    mbc1_mode_locked: bool,
+    multiplex: bool,
+
+    #[serde(skip)]
+    rom_banks: Vec<[u8; 0x4000]>,
+
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            serialize_with = "ram_bank_serialize",
+            deserialize_with = "ram_bank_deserialize"
+        )
+    )]
+    ram_banks: Vec<[u8; 0x2000]>,
+
+    /// Missing from old snapshots, see [`super::Cartridge::load_rom`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    has_battery: bool,
+}
+
+impl Mmm01 {
+    pub fn new(
+        rom: Vec<u8>,
+        ram: Option<Vec<u8>>,
+        num_rom_banks: usize,
+        num_ram_banks: usize,
+        has_battery: bool,
+    ) -> Self {
+        let (rom_banks, ram_banks) = init_rom_and_ram(rom, ram, num_rom_banks, num_ram_banks);
+
+        Self {
+            is_ram_enabled: false,
+            locked: false,
+
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+
+            mbc1_mode: false,
+            mbc1_mode_locked: false,
+            multiplex: false,
+
+            rom_banks,
+            ram_banks,
+            has_battery,
+        }
+    }
+
+    /// Banks mapped at 0x0000-0x3FFF and 0x4000-0x7FFF
+    fn mapped_rom_banks(&self) -> (usize, usize) {
+        let len = self.rom_banks.len();
+        if !self.locked {
+            return (len - 2, len - 1);
+        }
+
+        let frozen_low = self.rom_bank_low & (self.rom_bank_mask << 1);
+        let (mid_0, mid) = match self.multiplex {
+            true => (
+                if self.mbc1_mode { 0 } else { self.ram_bank_low },
+                self.ram_bank_low,
+            ),
+            false => (self.rom_bank_mid, self.rom_bank_mid),
+        };
+        let high = (self.rom_bank_high as usize) << 7;
+
+        let bank_0 = frozen_low as usize | ((mid_0 as usize) << 5) | high;
+        let mut bank = self.rom_bank_low as usize | ((mid as usize) << 5) | high;
+        // like the MBC1, the switchable region can't map the same bank as the fixed one
+        if bank == bank_0 {
+            bank += 1;
+        }
+
+        (bank_0 % len, bank % len)
+    }
+
+    /// Bits of `rom_bank_low` that writes can't change. The masks are only stored until
+    /// the mapping is locked, the menu can still switch banks freely before that
+    fn frozen_rom_bits(&self) -> u8 {
+        match self.locked {
+            true => self.rom_bank_mask << 1,
+            false => 0,
+        }
+    }
+
+    /// Bits of `ram_bank_low` that writes can't change, see [`Self::frozen_rom_bits`]
+    fn frozen_ram_bits(&self) -> u8 {
+        match self.locked {
+            true => self.ram_bank_mask,
+            false => 0,
+        }
+    }
+
+    fn mapped_ram_bank(&self) -> usize {
+        let bank = match self.multiplex {
+            true => self.rom_bank_mid | (self.ram_bank_high << 2),
+            false => self.ram_bank_low | (self.ram_bank_high << 2),
+        };
+
+        bank as usize % self.ram_banks.len().max(1)
+    }
+}
+
+impl CartridgeInterface for Mmm01 {
+    fn read_rom(&self, addr: u16) -> u8 {
+        let (bank_0, bank) = self.mapped_rom_banks();
+        match addr {
+            0x0000..=0x3FFF => self.rom_banks[bank_0][addr as usize],
+            0x4000..=0x7FFF => self.rom_banks[bank][(addr - 0x4000) as usize],
+            _ => panic!(),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.is_ram_enabled = value & 0x0F == 0x0A;
+                if !self.locked {
+                    self.ram_bank_mask = (value >> 4) & 0b11;
+                    self.locked = value & 0x40 != 0;
+                }
+            }
+
+            0x2000..=0x3FFF => {
+                if !self.locked {
+                    self.rom_bank_mid = (value >> 5) & 0b11;
+                }
+                let frozen = self.frozen_rom_bits();
+                self.rom_bank_low = (self.rom_bank_low & frozen) | (value & !frozen & 0x1F);
+            }
+
+            0x4000..=0x5FFF => {
+                let frozen = self.frozen_ram_bits();
+                self.ram_bank_low = (self.ram_bank_low & frozen) | (value & !frozen & 0b11);
+                if !self.locked {
+                    self.ram_bank_high = (value >> 2) & 0b11;
+                    self.rom_bank_high = (value >> 4) & 0b11;
+                    self.mbc1_mode_locked = value & 0x40 != 0;
+                }
+            }
+
+            0x6000..=0x7FFF => {
+                if !self.mbc1_mode_locked {
+                    self.mbc1_mode = value & 1 != 0;
+                }
+                if !self.locked {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & 0x40 != 0;
+                }
+            }
+
+            _ => panic!(),
+        }
+    }
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        if !self.is_ram_enabled || self.ram_banks.is_empty() {
+            return 0xFF;
+        }
+
+        self.ram_banks[self.mapped_ram_bank()][addr as usize]
+    }
+
+    fn write_ram(&mut self, addr: u16, value: u8) {
+        if !self.is_ram_enabled || self.ram_banks.is_empty() {
+            return;
+        }
+
+        let bank = self.mapped_ram_bank();
+        self.ram_banks[bank][addr as usize] = value;
+    }
+
+    fn has_ram(&self) -> bool {
+        !self.ram_banks.is_empty()
+    }
+
+    fn is_ram_enabled(&self) -> bool {
+        self.is_ram_enabled && !self.ram_banks.is_empty()
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_has_battery(&mut self, has_battery: bool) {
+        self.has_battery = has_battery;
+    }
+
+    fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
+        &self.ram_banks
+    }
+
+    fn rom_bank(&self, addr: u16) -> usize {
+        let (bank_0, bank) = self.mapped_rom_banks();
+        match addr {
+            0x0000..=0x3FFF => bank_0,
+            _ => bank,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.rom_banks
+    }
+
+    fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
+        self.rom_banks = rom;
+    }
+
+    fn take_rom(self) -> Vec<[u8; 0x4000]> {
+        self.rom_banks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every bank starts with its own number
+    fn cart() -> Mmm01 {
+        let rom = (0..16u8)
+            .flat_map(|bank| std::iter::once(bank).chain(std::iter::repeat_n(0, 0x3FFF)))
+            .collect();
+        Mmm01::new(rom, None, 16, 1, false)
+    }
+
+    #[test]
+    fn starts_in_menu_mode() {
+        let mut cart = cart();
+        assert_eq!(cart.read_rom(0x0000), 14);
+        assert_eq!(cart.read_rom(0x4000), 15);
+
+        // bank switches don't take effect until the mapping is locked
+        cart.write_rom(0x2000, 3);
+        assert_eq!(cart.read_rom(0x4000), 15);
+    }
+
+    #[test]
+    fn locks_game_into_its_banks() {
+        let mut cart = cart();
+
+        // the game lives in banks 4-7: base bank 4, with bank bits 2+ frozen
+        cart.write_rom(0x2000, 0x04);
+        cart.write_rom(0x6000, 0b0011_1000);
+        cart.write_rom(0x0000, 0x40);
+
+        assert_eq!(cart.read_rom(0x0000), 4);
+        assert_eq!(cart.read_rom(0x4000), 5);
+
+        cart.write_rom(0x2000, 0x03);
+        assert_eq!(cart.read_rom(0x4000), 7);
+
+        // the outer bank can't be changed anymore
+        cart.write_rom(0x2000, 0x01);
+        cart.write_rom(0x6000, 0);
+        cart.write_rom(0x0000, 0x00);
+        assert_eq!(cart.read_rom(0x0000), 4);
+        assert_eq!(cart.read_rom(0x4000), 5);
+    }
+
+    #[test]
+    fn masks_apply_once_locked() {
+        let mut cart = cart();
+
+        // the mask is set before the bank, which has to be stored as a whole
+        cart.write_rom(0x6000, 0b0011_1000);
+        cart.write_rom(0x2000, 0x06);
+        cart.write_rom(0x0000, 0x40);
+
+        assert_eq!(cart.read_rom(0x0000), 4);
+        assert_eq!(cart.read_rom(0x4000), 6);
+    }
+}
@@ -2,13 +2,26 @@ use std::iter;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-
-use crate::cartridge::{mbc1::Mbc1, mbc2::Mbc2, mbc3::Mbc3, mbc5::Mbc5, rom::Rom};
-
+use thiserror::Error;
+
+use crate::cartridge::{
+    camera::PocketCamera,
+    mbc1::Mbc1,
+    mbc2::Mbc2,
+    mbc3::{Mbc3, RtcFooter},
+    mbc5::Mbc5,
+    mbc6::Mbc6,
+    mmm01::Mmm01,
+    rom::Rom,
+};
+
+pub mod camera;
 pub mod mbc1;
 pub mod mbc2;
 pub mod mbc3;
 pub mod mbc5;
+pub mod mbc6;
+pub mod mmm01;
 pub mod rom;
 
 #[cfg(feature = "serde")]
@@ -23,8 +36,24 @@ trait CartridgeInterface {
 
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]>;
 
-    #[allow(unused)] // This will be needed for writing save files
+    /// The rom bank currently mapped at `addr`
+    fn rom_bank(&self, addr: u16) -> usize;
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]>;
+
     fn has_ram(&self) -> bool;
+    /// Whether writes to the ram area currently change the ram
+    fn is_ram_enabled(&self) -> bool;
+    /// Whether saved data was changed by writes to the rom area since the last call, like
+    /// the MBC6 flash being programmed
+    fn take_dirty(&mut self) -> bool {
+        false
+    }
+    /// Whether the ram is battery backed and should be kept between sessions
+    fn has_battery(&self) -> bool;
+    /// For carts that read it from the header, rather than always having a battery or not
+    fn set_has_battery(&mut self, _has_battery: bool) {}
 
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>);
     fn take_rom(self) -> Vec<[u8; 0x4000]>;
@@ -44,6 +73,20 @@ trait CartridgeInterface {
     // }
 }
 
+#[derive(Error, Debug)]
+pub enum CartridgeError {
+    #[error("The rom is {0} bytes, too short to have a header")]
+    TooShort(usize),
+    #[error("Cartridge has invalid ROM size code? Code: {0:#04X}")]
+    InvalidRomSize(u8),
+    #[error("Cartridge has invalid RAM size code? Code: {0:#04X}")]
+    InvalidRamSize(u8),
+    #[error("The header says the rom is {expected} bytes, but it is {actual} bytes")]
+    RomSizeMismatch { expected: usize, actual: usize },
+    #[error("Unable to handle cartridge type: {0:#04X}")]
+    UnsupportedType(u8),
+}
+
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Cartridge {
     Rom(Rom),
@@ -51,10 +94,17 @@ pub enum Cartridge {
     Mbc2(Mbc2),
     Mbc3(Mbc3),
     Mbc5(Mbc5),
+    PocketCamera(PocketCamera),
+    Mmm01(Mmm01),
+    Mbc6(Mbc6),
 }
 
 impl Cartridge {
-    pub fn new(rom: Vec<u8>, ram: Option<Vec<u8>>) -> Self {
+    pub fn new(rom: Vec<u8>, ram: Option<Vec<u8>>) -> Result<Self, CartridgeError> {
+        if rom.len() < 0x150 {
+            return Err(CartridgeError::TooShort(rom.len()));
+        }
+
         // parse cart header
         // CGB flag
         log::info!("CGB compat mode: {:#04X}", rom[0x143]);
@@ -62,9 +112,10 @@ impl Cartridge {
             log::warn!("This rom is only supported for game boy color");
         }
 
-        let cartridge_type_code = rom[0x147];
-        let rom_size_code = rom[0x148];
-        let ram_size_code = rom[0x149];
+        let header = Self::header_offset(&rom);
+        let cartridge_type_code = rom[header + 0x147];
+        let rom_size_code = rom[header + 0x148];
+        let ram_size_code = rom[header + 0x149];
 
         // This includes rom bank 0
         let num_rom_banks: usize = match rom_size_code {
@@ -86,10 +137,7 @@ impl Cartridge {
             0x53 => 80, // 1.2MB
             0x54 => 96, // 1.5MB
 
-            _ => panic!(
-                "Cartridge has invalid ROM size code? Code: {:#04X}",
-                rom_size_code
-            ),
+            _ => return Err(CartridgeError::InvalidRomSize(rom_size_code)),
         };
 
         log::debug!("rom size code: {}, banks: {}", rom_size_code, num_rom_banks);
@@ -101,41 +149,130 @@ impl Cartridge {
             0x04 => 16,
             0x05 => 8,
 
-            _ => panic!(
-                "Cartridge has invalid RAM size code? Code: {:#04X}",
-                ram_size_code
-            ),
+            _ => return Err(CartridgeError::InvalidRamSize(ram_size_code)),
         };
 
         log::debug!("ram size code: {}, banks: {}", ram_size_code, num_ram_banks);
 
-        match cartridge_type_code {
+        // carts without an MBC always have exactly 32KB
+        let expected_len = match cartridge_type_code {
+            0x00 => 0x8000,
+            _ => num_rom_banks * 0x4000,
+        };
+        if rom.len() != expected_len {
+            return Err(CartridgeError::RomSizeMismatch {
+                expected: expected_len,
+                actual: rom.len(),
+            });
+        }
+
+        let has_battery = Self::has_battery_for_type(cartridge_type_code);
+        log::debug!("has battery: {}", has_battery);
+
+        let cartridge = match cartridge_type_code {
             0x00 => Self::Rom(Rom::new(rom)),
 
             0x01..=0x03 => {
                 log::info!("MBC1 cart detected!");
-                Self::Mbc1(Mbc1::new(rom, ram, num_rom_banks, num_ram_banks))
+                Self::Mbc1(Mbc1::new(
+                    rom,
+                    ram,
+                    num_rom_banks,
+                    num_ram_banks,
+                    has_battery,
+                ))
             }
 
             0x05 | 0x06 => {
                 log::info!("MBC2 cart detected!");
-                Self::Mbc2(Mbc2::new(rom, ram, num_rom_banks, num_ram_banks))
+                Self::Mbc2(Mbc2::new(
+                    rom,
+                    ram,
+                    num_rom_banks,
+                    num_ram_banks,
+                    has_battery,
+                ))
+            }
+
+            0x0B..=0x0D => {
+                log::info!("MMM01 cart detected!");
+                Self::Mmm01(Mmm01::new(
+                    rom,
+                    ram,
+                    num_rom_banks,
+                    num_ram_banks,
+                    has_battery,
+                ))
             }
 
             0x0F..=0x13 => {
                 log::info!("MBC3 cart detected!");
-                Self::Mbc3(Mbc3::new(rom, ram, num_rom_banks, num_ram_banks))
+                let (ram, rtc) = split_rtc_footer(ram, num_ram_banks * 0x2000);
+                Self::Mbc3(Mbc3::new(
+                    rom,
+                    ram,
+                    num_rom_banks,
+                    num_ram_banks,
+                    has_battery,
+                    Self::has_rtc_for_type(cartridge_type_code),
+                    rtc,
+                ))
             }
 
             0x19..=0x1E => {
                 log::info!("MBC5 cart detected!");
-                Self::Mbc5(Mbc5::new(rom, ram, num_rom_banks, num_ram_banks))
+                Self::Mbc5(Mbc5::new(
+                    rom,
+                    ram,
+                    num_rom_banks,
+                    num_ram_banks,
+                    has_battery,
+                    (0x1C..=0x1E).contains(&cartridge_type_code),
+                ))
+            }
+
+            0x20 => {
+                log::info!("MBC6 cart detected!");
+                Self::Mbc6(Mbc6::new(rom, ram, num_rom_banks))
             }
 
-            _ => unimplemented!(
-                "Unable to handle cartridge type: {:#04X}",
-                cartridge_type_code
-            ),
+            0xFC => {
+                log::info!("Pocket Camera cart detected!");
+                Self::PocketCamera(PocketCamera::new(rom, ram, num_rom_banks, num_ram_banks))
+            }
+
+            _ => return Err(CartridgeError::UnsupportedType(cartridge_type_code)),
+        };
+
+        Ok(cartridge)
+    }
+
+    /// From the cartridge type codes in the header:
+    /// https://gbdev.io/pandocs/The_Cartridge_Header.html#0147--cartridge-type
+    ///
+    /// The MBC6 (0x20) isn't listed with a battery, but its flash keeps the saves either way
+    fn has_battery_for_type(cartridge_type_code: u8) -> bool {
+        matches!(
+            cartridge_type_code,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x20 | 0x22 | 0xFC..
+        )
+    }
+
+    /// MBC3 carts with a clock, which goes at the end of the save
+    fn has_rtc_for_type(cartridge_type_code: u8) -> bool {
+        matches!(cartridge_type_code, 0x0F | 0x10)
+    }
+
+    /// MMM01 multicarts boot into a menu in the last 32KB of the rom, which is also where
+    /// their real header is. Everything else has its header in the first bank
+    fn header_offset(rom: &[u8]) -> usize {
+        let Some(menu) = rom.len().checked_sub(0x8000) else {
+            return 0;
+        };
+
+        match rom[menu + 0x147] {
+            0x0B..=0x0D if menu > 0 => menu,
+            _ => 0,
         }
     }
 
@@ -146,6 +283,9 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => cart.read_rom(addr),
             Cartridge::Mbc3(cart) => cart.read_rom(addr),
             Cartridge::Mbc5(cart) => cart.read_rom(addr),
+            Cartridge::PocketCamera(cart) => cart.read_rom(addr),
+            Cartridge::Mmm01(cart) => cart.read_rom(addr),
+            Cartridge::Mbc6(cart) => cart.read_rom(addr),
         }
     }
 
@@ -156,6 +296,9 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => cart.write_rom(addr, val),
             Cartridge::Mbc3(cart) => cart.write_rom(addr, val),
             Cartridge::Mbc5(cart) => cart.write_rom(addr, val),
+            Cartridge::PocketCamera(cart) => cart.write_rom(addr, val),
+            Cartridge::Mmm01(cart) => cart.write_rom(addr, val),
+            Cartridge::Mbc6(cart) => cart.write_rom(addr, val),
         }
     }
 
@@ -166,6 +309,9 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => cart.read_ram(addr),
             Cartridge::Mbc3(cart) => cart.read_ram(addr),
             Cartridge::Mbc5(cart) => cart.read_ram(addr),
+            Cartridge::PocketCamera(cart) => cart.read_ram(addr),
+            Cartridge::Mmm01(cart) => cart.read_ram(addr),
+            Cartridge::Mbc6(cart) => cart.read_ram(addr),
         }
     }
 
@@ -176,9 +322,58 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => cart.write_ram(addr, val),
             Cartridge::Mbc3(cart) => cart.write_ram(addr, val),
             Cartridge::Mbc5(cart) => cart.write_ram(addr, val),
+            Cartridge::PocketCamera(cart) => cart.write_ram(addr, val),
+            Cartridge::Mmm01(cart) => cart.write_ram(addr, val),
+            Cartridge::Mbc6(cart) => cart.write_ram(addr, val),
         }
     }
 
+    pub fn rom_bank(&self, addr: u16) -> usize {
+        match self {
+            Cartridge::Rom(cart) => cart.rom_bank(addr),
+            Cartridge::Mbc1(cart) => cart.rom_bank(addr),
+            Cartridge::Mbc2(cart) => cart.rom_bank(addr),
+            Cartridge::Mbc3(cart) => cart.rom_bank(addr),
+            Cartridge::Mbc5(cart) => cart.rom_bank(addr),
+            Cartridge::PocketCamera(cart) => cart.rom_bank(addr),
+            Cartridge::Mmm01(cart) => cart.rom_bank(addr),
+            Cartridge::Mbc6(cart) => cart.rom_bank(addr),
+        }
+    }
+
+    /// Advances carts that have their own hardware, i.e. the camera sensor
+    pub fn tick(&mut self) {
+        if let Cartridge::PocketCamera(cart) = self {
+            cart.tick();
+        }
+    }
+
+    /// Whether the rumble motor of an MBC5 rumble cart is on
+    pub fn rumble(&self) -> bool {
+        matches!(self, Cartridge::Mbc5(cart) if cart.rumble())
+    }
+
+    /// Reads `addr` as if `bank` was mapped into it, regardless of the current MBC state.
+    /// Banks that don't exist read as 0xFF
+    #[cfg(feature = "debug_info")]
+    pub fn peek_rom_bank(&self, bank: usize, addr: u16) -> u8 {
+        let banks = match self {
+            Cartridge::Rom(cart) => cart.rom_banks(),
+            Cartridge::Mbc1(cart) => cart.rom_banks(),
+            Cartridge::Mbc2(cart) => cart.rom_banks(),
+            Cartridge::Mbc3(cart) => cart.rom_banks(),
+            Cartridge::Mbc5(cart) => cart.rom_banks(),
+            Cartridge::PocketCamera(cart) => cart.rom_banks(),
+            Cartridge::Mmm01(cart) => cart.rom_banks(),
+            Cartridge::Mbc6(cart) => cart.rom_banks(),
+        };
+
+        banks
+            .get(bank)
+            .map(|bank| bank[(addr & 0x3FFF) as usize])
+            .unwrap_or(0xFF)
+    }
+
     fn get_ram_iter<T: CartridgeInterface>(cart: &T) -> Box<dyn Iterator<Item = u8> + '_> {
         Box::new(
             cart.ram_banks()
@@ -196,21 +391,112 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => Self::get_ram_iter(cart),
             Cartridge::Mbc3(cart) => Self::get_ram_iter(cart),
             Cartridge::Mbc5(cart) => Self::get_ram_iter(cart),
+            Cartridge::PocketCamera(cart) => Self::get_ram_iter(cart),
+            Cartridge::Mmm01(cart) => Self::get_ram_iter(cart),
+            Cartridge::Mbc6(cart) => Self::get_ram_iter(cart),
         }
     }
 
+    /// The ram followed by the clock of carts that have one, as it goes into save files.
+    /// `None` for carts with neither
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        let rtc = match self {
+            Cartridge::Mbc3(cart) => cart.rtc_footer(),
+            _ => None,
+        };
+        if !self.has_ram() && rtc.is_none() {
+            return None;
+        }
+
+        let mut data = self.iter_ram().collect::<Vec<_>>();
+        if let Some(rtc) = rtc {
+            data.extend(rtc.to_bytes());
+        }
+        Some(data)
+    }
+
     pub fn has_ram(&self) -> bool {
-        !matches!(self, Self::Rom(_))
+        match self {
+            Cartridge::Rom(cart) => cart.has_ram(),
+            Cartridge::Mbc1(cart) => cart.has_ram(),
+            Cartridge::Mbc2(cart) => cart.has_ram(),
+            Cartridge::Mbc3(cart) => cart.has_ram(),
+            Cartridge::Mbc5(cart) => cart.has_ram(),
+            Cartridge::PocketCamera(cart) => cart.has_ram(),
+            Cartridge::Mmm01(cart) => cart.has_ram(),
+            Cartridge::Mbc6(cart) => cart.has_ram(),
+        }
+    }
+
+    pub fn is_ram_enabled(&self) -> bool {
+        match self {
+            Cartridge::Rom(cart) => cart.is_ram_enabled(),
+            Cartridge::Mbc1(cart) => cart.is_ram_enabled(),
+            Cartridge::Mbc2(cart) => cart.is_ram_enabled(),
+            Cartridge::Mbc3(cart) => cart.is_ram_enabled(),
+            Cartridge::Mbc5(cart) => cart.is_ram_enabled(),
+            Cartridge::PocketCamera(cart) => cart.is_ram_enabled(),
+            Cartridge::Mmm01(cart) => cart.is_ram_enabled(),
+            Cartridge::Mbc6(cart) => cart.is_ram_enabled(),
+        }
+    }
+
+    pub fn take_dirty(&mut self) -> bool {
+        match self {
+            Cartridge::Rom(cart) => cart.take_dirty(),
+            Cartridge::Mbc1(cart) => cart.take_dirty(),
+            Cartridge::Mbc2(cart) => cart.take_dirty(),
+            Cartridge::Mbc3(cart) => cart.take_dirty(),
+            Cartridge::Mbc5(cart) => cart.take_dirty(),
+            Cartridge::PocketCamera(cart) => cart.take_dirty(),
+            Cartridge::Mmm01(cart) => cart.take_dirty(),
+            Cartridge::Mbc6(cart) => cart.take_dirty(),
+        }
     }
 
-    /// Init the rom, used for applying snapshots
+    pub fn has_battery(&self) -> bool {
+        match self {
+            Cartridge::Rom(cart) => cart.has_battery(),
+            Cartridge::Mbc1(cart) => cart.has_battery(),
+            Cartridge::Mbc2(cart) => cart.has_battery(),
+            Cartridge::Mbc3(cart) => cart.has_battery(),
+            Cartridge::Mbc5(cart) => cart.has_battery(),
+            Cartridge::PocketCamera(cart) => cart.has_battery(),
+            Cartridge::Mmm01(cart) => cart.has_battery(),
+            Cartridge::Mbc6(cart) => cart.has_battery(),
+        }
+    }
+
+    /// Init the rom, used for applying snapshots. Snapshots from before carts knew whether
+    /// they have a battery or a clock don't say, so that is taken from the header again
     pub(crate) fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
+        let header = Self::header_offset(rom.as_flattened());
+        let cartridge_type_code = rom.as_flattened().get(header + 0x147).copied();
+        let has_battery = cartridge_type_code.is_some_and(Self::has_battery_for_type);
+        if let Cartridge::Mbc3(cart) = self {
+            cart.set_has_rtc(cartridge_type_code.is_some_and(Self::has_rtc_for_type));
+        }
+
         match self {
             Cartridge::Rom(cart) => cart.load_rom(rom),
             Cartridge::Mbc1(cart) => cart.load_rom(rom),
             Cartridge::Mbc2(cart) => cart.load_rom(rom),
             Cartridge::Mbc3(cart) => cart.load_rom(rom),
             Cartridge::Mbc5(cart) => cart.load_rom(rom),
+            Cartridge::PocketCamera(cart) => cart.load_rom(rom),
+            Cartridge::Mmm01(cart) => cart.load_rom(rom),
+            Cartridge::Mbc6(cart) => cart.load_rom(rom),
+        }
+
+        match self {
+            Cartridge::Rom(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mbc1(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mbc2(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mbc3(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mbc5(cart) => cart.set_has_battery(has_battery),
+            Cartridge::PocketCamera(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mmm01(cart) => cart.set_has_battery(has_battery),
+            Cartridge::Mbc6(cart) => cart.set_has_battery(has_battery),
         }
     }
 
@@ -221,6 +507,9 @@ impl Cartridge {
             Cartridge::Mbc2(cart) => cart.take_rom(),
             Cartridge::Mbc3(cart) => cart.take_rom(),
             Cartridge::Mbc5(cart) => cart.take_rom(),
+            Cartridge::PocketCamera(cart) => cart.take_rom(),
+            Cartridge::Mmm01(cart) => cart.take_rom(),
+            Cartridge::Mbc6(cart) => cart.take_rom(),
         }
     }
 }
@@ -243,7 +532,7 @@ fn init_rom_and_ram(
 
     let ram_banks: Vec<[u8; 0x2000]> = match ram {
         Some(ram) => {
-            assert_eq!(ram.len(), num_ram_banks * 0x2000);
+            let ram = fit_save_ram(ram, num_ram_banks * 0x2000);
             ram.chunks_exact(0x2000)
                 .map(|chunk| {
                     let mut arr = [0; 0x2000];
@@ -260,3 +549,171 @@ fn init_rom_and_ram(
 
     (rom_banks, ram_banks)
 }
+
+/// Splits the RTC footer other emulators append to MBC3 saves off the ram, see
+/// [`RtcFooter`]
+fn split_rtc_footer(ram: Option<Vec<u8>>, ram_len: usize) -> (Option<Vec<u8>>, Option<RtcFooter>) {
+    let Some(mut ram) = ram else {
+        return (None, None);
+    };
+
+    let rtc = ram.get(ram_len..).and_then(RtcFooter::parse);
+    if rtc.is_some() {
+        log::info!("Loaded the RTC from the save file");
+        ram.truncate(ram_len);
+    }
+
+    (Some(ram), rtc)
+}
+
+/// Makes a save file fit the cartridge ram, so saves from other emulators or from a
+/// different dump of the game can still be loaded
+fn fit_save_ram(mut ram: Vec<u8>, expected_len: usize) -> Vec<u8> {
+    if ram.len() == expected_len {
+        return ram;
+    }
+
+    log::warn!(
+        "Save file is {} bytes but the cartridge has {} bytes of ram, {}",
+        ram.len(),
+        expected_len,
+        match ram.len() > expected_len {
+            true => "truncating it",
+            false => "padding it with zeros",
+        }
+    );
+    ram.resize(expected_len, 0);
+    ram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_from_cartridge_type() {
+        let cart = |cartridge_type_code| {
+            let mut rom = vec![0; 0x8000];
+            rom[0x147] = cartridge_type_code;
+            rom[0x149] = 0x02;
+            Cartridge::new(rom, None).unwrap()
+        };
+
+        let mbc5_ram = cart(0x1A);
+        assert!(mbc5_ram.has_ram());
+        assert!(!mbc5_ram.has_battery());
+        assert!(cart(0x1B).has_battery());
+        assert!(!cart(0x00).has_ram());
+
+        // old snapshots don't say, so it comes from the header of the loaded rom
+        let mut snapshot = cart(0x1B);
+        if let Cartridge::Mbc5(mbc5) = &mut snapshot {
+            mbc5.set_has_battery(false);
+        }
+        snapshot.load_rom(cart(0x1B).take_rom());
+        assert!(snapshot.has_battery());
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let cart = |len: usize, header: [u8; 3]| {
+            let mut rom = vec![0; len];
+            if len > 0x149 {
+                rom[0x147..0x14A].copy_from_slice(&header);
+            }
+            Cartridge::new(rom, None).map(|_| ())
+        };
+
+        assert!(cart(0x8000, [0x00, 0x00, 0x00]).is_ok());
+        assert!(matches!(
+            cart(0x100, [0x00; 3]),
+            Err(CartridgeError::TooShort(0x100))
+        ));
+        assert!(matches!(
+            cart(0x8000, [0x01, 0x42, 0x00]),
+            Err(CartridgeError::InvalidRomSize(0x42))
+        ));
+        assert!(matches!(
+            cart(0x8000, [0x01, 0x00, 0x42]),
+            Err(CartridgeError::InvalidRamSize(0x42))
+        ));
+        assert!(matches!(
+            cart(0x8000, [0x01, 0x01, 0x00]),
+            Err(CartridgeError::RomSizeMismatch { .. })
+        ));
+        assert!(matches!(
+            cart(0x8000, [0xFE, 0x00, 0x00]),
+            Err(CartridgeError::UnsupportedType(0xFE))
+        ));
+    }
+
+    #[test]
+    fn rumble_motor() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x1E;
+        rom[0x149] = 0x03;
+        let mut cart = Cartridge::new(rom, None).unwrap();
+
+        cart.write_rom(0x0000, 0x0A);
+        cart.write_rom(0x4000, 0x0B);
+        assert!(cart.rumble());
+        cart.write_ram(0x0000, 0x42);
+
+        // the motor bit doesn't select a different bank
+        cart.write_rom(0x4000, 0x03);
+        assert!(!cart.rumble());
+        assert_eq!(cart.read_ram(0x0000), 0x42);
+    }
+
+    #[test]
+    fn splits_rtc_footer() {
+        let mut save = vec![0x42; 0x8000];
+        save.extend([0x01; 48]);
+
+        let (ram, rtc) = split_rtc_footer(Some(save), 0x8000);
+        assert_eq!(ram, Some(vec![0x42; 0x8000]));
+        assert!(rtc.is_some());
+
+        let (ram, rtc) = split_rtc_footer(Some(vec![0x42; 0x8000 + 40]), 0x8000);
+        assert_eq!(ram.map(|ram| ram.len()), Some(0x8000 + 40));
+        assert!(rtc.is_none());
+    }
+
+    #[test]
+    fn saves_rtc_after_ram() {
+        let cart = |cartridge_type_code, ram_size_code| {
+            let mut rom = vec![0; 0x8000];
+            rom[0x147] = cartridge_type_code;
+            rom[0x149] = ram_size_code;
+            Cartridge::new(rom, None).unwrap()
+        };
+
+        let save = cart(0x10, 0x02).save_data().unwrap();
+        assert_eq!(save.len(), 0x2000 + 48);
+        assert_eq!(save[..0x2000], [0; 0x2000]);
+
+        // the clock is saved even without ram, and reloaded from such a save
+        assert_eq!(
+            cart(0x0F, 0x00).save_data().map(|save| save.len()),
+            Some(48)
+        );
+        let (ram, rtc) = split_rtc_footer(cart(0x0F, 0x00).save_data(), 0);
+        assert_eq!(ram, Some(vec![]));
+        assert!(rtc.is_some());
+
+        assert_eq!(
+            cart(0x13, 0x02).save_data().map(|save| save.len()),
+            Some(0x2000)
+        );
+        assert_eq!(cart(0x11, 0x00).save_data(), None);
+    }
+
+    #[test]
+    fn fits_mismatched_save() {
+        assert_eq!(fit_save_ram(vec![0x42; 0x4000], 0x2000), vec![0x42; 0x2000]);
+
+        let ram = fit_save_ram(vec![0x42; 0x2000], 0x4000);
+        assert_eq!(ram[..0x2000], [0x42; 0x2000]);
+        assert_eq!(ram[0x2000..], [0; 0x2000]);
+    }
+}
@@ -50,10 +50,27 @@ impl CartridgeInterface for Rom {
         false
     }
 
+    fn is_ram_enabled(&self) -> bool {
+        false
+    }
+
+    fn has_battery(&self) -> bool {
+        false
+    }
+
     fn ram_banks(&self) -> &Vec<[u8; 0x2000]> {
         unimplemented!("ROM has no RAM.")
     }
 
+    fn rom_bank(&self, addr: u16) -> usize {
+        (addr / 0x4000) as usize
+    }
+
+    #[cfg(feature = "debug_info")]
+    fn rom_banks(&self) -> &Vec<[u8; 0x4000]> {
+        &self.data
+    }
+
     fn load_rom(&mut self, rom: Vec<[u8; 0x4000]>) {
         self.data = rom;
     }
@@ -3,6 +3,8 @@ use crate::cpu::Cpu;
 use crate::cpu::register::Flag;
 
 pub(super) trait CbOp {
+    const NAME: &'static str;
+
     fn apply(cpu: &mut Cpu, v: u8) -> u8;
 }
 
@@ -16,6 +18,8 @@ pub(super) struct Swap;
 pub(super) struct Srl;
 
 impl CbOp for Rlc {
+    const NAME: &'static str = "RLC";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.rlc(v)
@@ -23,6 +27,8 @@ impl CbOp for Rlc {
 }
 
 impl CbOp for Rrc {
+    const NAME: &'static str = "RRC";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.rrc(v)
@@ -30,6 +36,8 @@ impl CbOp for Rrc {
 }
 
 impl CbOp for Rl {
+    const NAME: &'static str = "RL";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.rl(v)
@@ -37,6 +45,8 @@ impl CbOp for Rl {
 }
 
 impl CbOp for Rr {
+    const NAME: &'static str = "RR";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.rr(v)
@@ -44,6 +54,8 @@ impl CbOp for Rr {
 }
 
 impl CbOp for Sla {
+    const NAME: &'static str = "SLA";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.sla(v)
@@ -51,6 +63,8 @@ impl CbOp for Sla {
 }
 
 impl CbOp for Sra {
+    const NAME: &'static str = "SRA";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.sra(v)
@@ -58,6 +72,8 @@ impl CbOp for Sra {
 }
 
 impl CbOp for Swap {
+    const NAME: &'static str = "SWAP";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.swap(v)
@@ -65,6 +81,8 @@ impl CbOp for Swap {
 }
 
 impl CbOp for Srl {
+    const NAME: &'static str = "SRL";
+
     #[inline(always)]
     fn apply(cpu: &mut Cpu, v: u8) -> u8 {
         cpu.srl(v)
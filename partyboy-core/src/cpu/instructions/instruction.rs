@@ -46,4 +46,92 @@ impl Debug for InstructionOpcode {
 
 pub struct Instruction {
     pub steps: &'static [InstructionStep],
+    pub info: InstructionInfo,
+}
+
+/// How an operand of an instruction is encoded and displayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// A register or register pair, e.g. `A` or `HL`
+    Reg(&'static str),
+    /// A branch condition, e.g. `NZ`
+    Cond(&'static str),
+    /// Memory pointed to by a register pair, e.g. `(HL+)`
+    RegMem(&'static str),
+    /// `(FF00+C)`
+    HighMemC,
+    /// Immediate byte following the opcode
+    Imm8,
+    /// Signed immediate byte following the opcode
+    SignedImm8,
+    /// Immediate word following the opcode
+    Imm16,
+    /// Immediate word used as a jump/call target
+    Addr16,
+    /// Memory pointed to by an immediate word, e.g. `($C000)`
+    Mem16,
+    /// Memory pointed to by `0xFF00` + an immediate byte
+    HighMem8,
+    /// Signed immediate byte relative to the next instruction
+    Rel8,
+    /// `SP` + a signed immediate byte
+    SpRel8,
+    /// Bit index of a `BIT`/`RES`/`SET` instruction
+    Bit(u8),
+    /// Fixed `RST` vector
+    Vector(u16),
+    /// The opcode byte itself, for opcodes that don't decode to anything
+    Byte(u8),
+}
+
+impl Operand {
+    /// Number of bytes this operand occupies after the opcode
+    pub fn encoded_len(&self) -> u16 {
+        match self {
+            Operand::Imm8
+            | Operand::SignedImm8
+            | Operand::HighMem8
+            | Operand::Rel8
+            | Operand::SpRel8 => 1,
+            Operand::Imm16 | Operand::Addr16 | Operand::Mem16 => 2,
+            Operand::None
+            | Operand::Reg(_)
+            | Operand::Cond(_)
+            | Operand::RegMem(_)
+            | Operand::HighMemC
+            | Operand::Bit(_)
+            | Operand::Vector(_)
+            | Operand::Byte(_) => 0,
+        }
+    }
+}
+
+/// Static description of an instruction, used by the disassembler.
+#[derive(Clone, Copy, Debug)]
+pub struct InstructionInfo {
+    pub mnemonic: &'static str,
+    pub operands: [Operand; 2],
+    /// T-cycles taken, or the T-cycles when a conditional branch is not taken
+    pub cycles: u8,
+    /// T-cycles taken when a conditional branch is taken
+    pub branch_cycles: Option<u8>,
+}
+
+impl InstructionInfo {
+    pub fn new<const N: usize>(mnemonic: &'static str, ops: [Operand; N], cycles: u8) -> Self {
+        let mut operands = [Operand::None; 2];
+        operands[..N].copy_from_slice(&ops);
+        Self {
+            mnemonic,
+            operands,
+            cycles,
+            branch_cycles: None,
+        }
+    }
+
+    pub fn branch(mut self, cycles: u8) -> Self {
+        self.branch_cycles = Some(cycles);
+        self
+    }
 }
@@ -1,5 +1,5 @@
 use super::alu::{Rl, Rlc, Rr, Rrc, Sla, Sra, Srl, Swap};
-use super::instruction::{Instruction, InstructionOpcode};
+use super::instruction::{Instruction, InstructionInfo, InstructionOpcode};
 use super::opcodes::*;
 use super::regs::cond::{Carry, NZ, NotCarry, Z};
 use super::regs::{A, AF, B, BC, C, D, DE, E, H, HL, L, SP};
@@ -574,4 +574,12 @@ impl InstructionCache {
             InstructionOpcode::InterruptServiceRoutine => &mut self.interrupt_service_routine,
         }
     }
+
+    pub(crate) fn info(&self, opcode: InstructionOpcode) -> &InstructionInfo {
+        match opcode {
+            InstructionOpcode::Unprefixed(opcode) => &self.instructions[opcode as usize].info,
+            InstructionOpcode::Prefixed(opcode) => &self.cb_instructions[opcode as usize].info,
+            InstructionOpcode::InterruptServiceRoutine => &self.interrupt_service_routine.info,
+        }
+    }
 }
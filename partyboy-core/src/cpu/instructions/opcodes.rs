@@ -1,5 +1,7 @@
 use super::alu::CbOp;
-use super::instruction::{Instruction, InstructionState, InstructionStep};
+use super::instruction::{
+    Instruction, InstructionInfo, InstructionState, InstructionStep, Operand,
+};
 use super::regs::cond::Cond;
 use super::regs::{Reg8, Reg16};
 
@@ -33,12 +35,14 @@ const BLANK_EXEC_NEXT: InstructionStep =
 pub fn nop() -> Instruction {
     Instruction {
         steps: &[InstructionStep::Instant(|_, _| InstructionState::Finished)],
+        info: InstructionInfo::new("NOP", [], 4),
     }
 }
 
 pub fn cb_prefix_stub() -> Instruction {
     Instruction {
         steps: &[InstructionStep::Instant(|_, _| unimplemented!("CB prefix"))],
+        info: InstructionInfo::new("PREFIX", [], 4),
     }
 }
 
@@ -47,6 +51,7 @@ pub fn unused_opcode<const OP: u16>() -> Instruction {
         steps: &[InstructionStep::Instant(|_, _| {
             panic!("Executed unused opcode {OP:#06X}")
         })],
+        info: InstructionInfo::new("DB", [Operand::Byte(OP as u8)], 0),
     }
 }
 
@@ -56,6 +61,7 @@ pub fn di() -> Instruction {
             bus.interrupts.disable_master();
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("DI", [], 4),
     }
 }
 
@@ -68,6 +74,7 @@ pub fn ei() -> Instruction {
             }
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("EI", [], 4),
     }
 }
 
@@ -85,6 +92,7 @@ pub fn halt() -> Instruction {
             }
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("HALT", [], 4),
     }
 }
 
@@ -105,7 +113,11 @@ pub fn stop() -> Instruction {
         });
         arr
     };
-    Instruction { steps: &STEPS }
+    Instruction {
+        steps: &STEPS,
+        // STOP is followed by a byte the cpu skips, usually 0x00
+        info: InstructionInfo::new("STOP", [Operand::Imm8], 4),
+    }
 }
 
 pub fn daa() -> Instruction {
@@ -133,6 +145,7 @@ pub fn daa() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("DAA", [], 4),
     }
 }
 
@@ -145,6 +158,7 @@ pub fn cpl() -> Instruction {
             cpu.set_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("CPL", [], 4),
     }
 }
 
@@ -157,6 +171,7 @@ pub fn scf() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("SCF", [], 4),
     }
 }
 
@@ -173,6 +188,7 @@ pub fn ccf() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("CCF", [], 4),
     }
 }
 
@@ -188,6 +204,7 @@ pub fn rlca() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("RLCA", [], 4),
     }
 }
 
@@ -206,6 +223,7 @@ pub fn rrca() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("RRCA", [], 4),
     }
 }
 
@@ -224,6 +242,7 @@ pub fn rla() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("RLA", [], 4),
     }
 }
 
@@ -239,6 +258,7 @@ pub fn rra() -> Instruction {
             cpu.clear_flag(Flag::H);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("RRA", [], 4),
     }
 }
 
@@ -256,6 +276,7 @@ pub fn ld_r16_u16<R: Reg16>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Reg(R::NAME), Operand::Imm16], 12),
     }
 }
 
@@ -270,6 +291,7 @@ pub fn ld_u16_sp() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Mem16, Operand::Reg("SP")], 20),
     }
 }
 
@@ -279,6 +301,7 @@ pub fn ld_sp_hl() -> Instruction {
             cpu.sp = cpu.hl.into();
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg("SP"), Operand::Reg("HL")], 8),
     }
 }
 
@@ -301,6 +324,7 @@ pub fn ld_hl_sp_i8() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Reg("HL"), Operand::SpRel8], 12),
     }
 }
 
@@ -314,6 +338,7 @@ pub fn ld_mem_u16_a() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Mem16, Operand::Reg("A")], 16),
     }
 }
 
@@ -327,6 +352,7 @@ pub fn ld_a_mem_u16() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Reg("A"), Operand::Mem16], 16),
     }
 }
 
@@ -339,6 +365,7 @@ pub fn ld_ff00_u8_a() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LDH", [Operand::HighMem8, Operand::Reg("A")], 12),
     }
 }
 
@@ -351,6 +378,7 @@ pub fn ld_a_ff00_u8() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LDH", [Operand::Reg("A"), Operand::HighMem8], 12),
     }
 }
 
@@ -360,6 +388,7 @@ pub fn ld_ff00_c_a() -> Instruction {
             bus.write_u8(0xFF00 + cpu.bc.lo as u16, cpu.af.hi);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::HighMemC, Operand::Reg("A")], 8),
     }
 }
 
@@ -369,6 +398,7 @@ pub fn ld_a_ff00_c() -> Instruction {
             cpu.af.hi = bus.read_u8(0xFF00 + cpu.bc.lo as u16);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg("A"), Operand::HighMemC], 8),
     }
 }
 
@@ -381,6 +411,7 @@ pub fn ld_r8_u8<R: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Reg(R::NAME), Operand::Imm8], 8),
     }
 }
 
@@ -394,6 +425,7 @@ pub fn ld_r8_r8<Dst: Reg8, Src: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::Reg(Dst::NAME), Operand::Reg(Src::NAME)], 4),
     }
 }
 
@@ -404,6 +436,7 @@ pub fn ld_r8_hlmem<Dst: Reg8>() -> Instruction {
             Dst::write(cpu, v);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg(Dst::NAME), Operand::RegMem("HL")], 8),
     }
 }
 
@@ -413,6 +446,7 @@ pub fn ld_hlmem_r8<Src: Reg8>() -> Instruction {
             bus.write_u8(cpu.hl.into(), Src::read(cpu));
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::RegMem("HL"), Operand::Reg(Src::NAME)], 8),
     }
 }
 
@@ -425,6 +459,7 @@ pub fn ld_hlmem_u8() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("LD", [Operand::RegMem("HL"), Operand::Imm8], 12),
     }
 }
 
@@ -434,6 +469,7 @@ pub fn ld_mem_a<R: Reg16>() -> Instruction {
             bus.write_u8(R::read(cpu), cpu.af.hi);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::RegMem(R::NAME), Operand::Reg("A")], 8),
     }
 }
 
@@ -444,6 +480,7 @@ pub fn ld_mem_a_hl_inc() -> Instruction {
             cpu.hl += 1;
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::RegMem("HL+"), Operand::Reg("A")], 8),
     }
 }
 
@@ -454,6 +491,7 @@ pub fn ld_mem_a_hl_dec() -> Instruction {
             cpu.hl -= 1;
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::RegMem("HL-"), Operand::Reg("A")], 8),
     }
 }
 
@@ -463,6 +501,7 @@ pub fn ld_a_mem<R: Reg16>() -> Instruction {
             cpu.af.hi = bus.read_u8(R::read(cpu));
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg("A"), Operand::RegMem(R::NAME)], 8),
     }
 }
 
@@ -473,6 +512,7 @@ pub fn ld_a_mem_hl_inc() -> Instruction {
             cpu.hl += 1;
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg("A"), Operand::RegMem("HL+")], 8),
     }
 }
 
@@ -483,6 +523,7 @@ pub fn ld_a_mem_hl_dec() -> Instruction {
             cpu.hl -= 1;
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("LD", [Operand::Reg("A"), Operand::RegMem("HL-")], 8),
     }
 }
 
@@ -492,6 +533,7 @@ pub fn inc_r16<R: Reg16>() -> Instruction {
             R::inc(cpu);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("INC", [Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -501,6 +543,7 @@ pub fn dec_r16<R: Reg16>() -> Instruction {
             R::dec(cpu);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("DEC", [Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -516,6 +559,7 @@ pub fn inc_r8<R: Reg8>() -> Instruction {
             cpu.clear_flag(Flag::N);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("INC", [Operand::Reg(R::NAME)], 4),
     }
 }
 
@@ -531,6 +575,7 @@ pub fn dec_r8<R: Reg8>() -> Instruction {
             cpu.set_flag(Flag::N);
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("DEC", [Operand::Reg(R::NAME)], 4),
     }
 }
 
@@ -551,6 +596,7 @@ pub fn inc_hlmem() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("INC", [Operand::RegMem("HL")], 12),
     }
 }
 
@@ -571,6 +617,7 @@ pub fn dec_hlmem() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("DEC", [Operand::RegMem("HL")], 12),
     }
 }
 
@@ -590,6 +637,7 @@ pub fn add_hl_r16<R: Reg16>() -> Instruction {
             cpu.hl = result.into();
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("ADD", [Operand::Reg("HL"), Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -613,54 +661,64 @@ pub fn add_sp_i8() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("ADD", [Operand::Reg("SP"), Operand::SignedImm8], 16),
     }
 }
 
 macro_rules! alu_builders {
-    ($name:ident, $method:ident) => {
+    ($name:ident, $method:ident, $mnemonic:literal) => {
         paste::paste! {
             pub fn [<$name _a_r8>]<R: Reg8>() -> Instruction {
-                Instruction { steps: &[InstructionStep::Instant(|cpu, _| {
-                    let src = R::read(cpu);
-                    cpu.$method(src);
-                    InstructionState::Finished
-                })] }
+                Instruction {
+                    steps: &[InstructionStep::Instant(|cpu, _| {
+                        let src = R::read(cpu);
+                        cpu.$method(src);
+                        InstructionState::Finished
+                    })],
+                    info: InstructionInfo::new($mnemonic, [Operand::Reg("A"), Operand::Reg(R::NAME)], 4),
+                }
             }
 
             pub fn [<$name _a_imm8>]() -> Instruction {
-                Instruction { steps: &[
-                    FETCH_OP8_EXEC_NEXT,
-                    InstructionStep::Instant(|cpu, _| {
-                        cpu.$method(cpu.operand8);
-                        InstructionState::Finished
-                    }),
-                ]}
+                Instruction {
+                    steps: &[
+                        FETCH_OP8_EXEC_NEXT,
+                        InstructionStep::Instant(|cpu, _| {
+                            cpu.$method(cpu.operand8);
+                            InstructionState::Finished
+                        }),
+                    ],
+                    info: InstructionInfo::new($mnemonic, [Operand::Reg("A"), Operand::Imm8], 8),
+                }
             }
 
             pub fn [<$name _a_hlmem>]() -> Instruction {
-                Instruction { steps: &[
-                    InstructionStep::Standard(|cpu, bus| {
-                        cpu.temp8 = bus.read_u8(cpu.hl.into());
-                        InstructionState::ExecNextInstantly
-                    }),
-                    InstructionStep::Instant(|cpu, _| {
-                        cpu.$method(cpu.temp8);
-                        InstructionState::Finished
-                    }),
-                ]}
+                Instruction {
+                    steps: &[
+                        InstructionStep::Standard(|cpu, bus| {
+                            cpu.temp8 = bus.read_u8(cpu.hl.into());
+                            InstructionState::ExecNextInstantly
+                        }),
+                        InstructionStep::Instant(|cpu, _| {
+                            cpu.$method(cpu.temp8);
+                            InstructionState::Finished
+                        }),
+                    ],
+                    info: InstructionInfo::new($mnemonic, [Operand::Reg("A"), Operand::RegMem("HL")], 8),
+                }
             }
         }
     };
 }
 
-alu_builders!(add, alu_add);
-alu_builders!(adc, alu_adc);
-alu_builders!(sub, alu_sub);
-alu_builders!(sbc, alu_sbc);
-alu_builders!(and, alu_and);
-alu_builders!(xor, alu_xor);
-alu_builders!(or, alu_or);
-alu_builders!(cp, alu_cp);
+alu_builders!(add, alu_add, "ADD");
+alu_builders!(adc, alu_adc, "ADC");
+alu_builders!(sub, alu_sub, "SUB");
+alu_builders!(sbc, alu_sbc, "SBC");
+alu_builders!(and, alu_and, "AND");
+alu_builders!(xor, alu_xor, "XOR");
+alu_builders!(or, alu_or, "OR");
+alu_builders!(cp, alu_cp, "CP");
 
 pub fn push_r16<R: Reg16>() -> Instruction {
     Instruction {
@@ -677,6 +735,7 @@ pub fn push_r16<R: Reg16>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("PUSH", [Operand::Reg(R::NAME)], 16),
     }
 }
 
@@ -694,6 +753,7 @@ pub fn pop_r16<R: Reg16>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("POP", [Operand::Reg(R::NAME)], 12),
     }
 }
 
@@ -707,6 +767,7 @@ pub fn jp_u16() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("JP", [Operand::Addr16], 16),
     }
 }
 
@@ -721,6 +782,8 @@ pub fn jp_cc_u16<Cnd: Cond>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("JP", [Operand::Cond(Cnd::NAME), Operand::Addr16], 12)
+            .branch(16),
     }
 }
 
@@ -730,12 +793,14 @@ pub fn jp_hl() -> Instruction {
             cpu.pc = cpu.hl.into();
             InstructionState::Finished
         })],
+        info: InstructionInfo::new("JP", [Operand::Reg("HL")], 4),
     }
 }
 
 pub fn jr_i8() -> Instruction {
     Instruction {
         steps: &[FETCH_OP8, JR_STEP],
+        info: InstructionInfo::new("JR", [Operand::Rel8], 12),
     }
 }
 
@@ -746,6 +811,7 @@ pub fn jr_cc_i8<Cnd: Cond>() -> Instruction {
             InstructionStep::Instant(|cpu, _| InstructionState::Branch(Cnd::matches(cpu))),
             JR_STEP,
         ],
+        info: InstructionInfo::new("JR", [Operand::Cond(Cnd::NAME), Operand::Rel8], 8).branch(12),
     }
 }
 
@@ -775,6 +841,7 @@ pub fn call_u16() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("CALL", [Operand::Addr16], 24),
     }
 }
 
@@ -795,6 +862,8 @@ pub fn call_cc_u16<Cnd: Cond>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("CALL", [Operand::Cond(Cnd::NAME), Operand::Addr16], 12)
+            .branch(24),
     }
 }
 
@@ -811,6 +880,7 @@ pub fn ret() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RET", [], 16),
     }
 }
 
@@ -828,6 +898,7 @@ pub fn reti() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RETI", [], 16),
     }
 }
 
@@ -846,6 +917,7 @@ pub fn ret_cc<Cnd: Cond>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RET", [Operand::Cond(Cnd::NAME)], 8).branch(20),
     }
 }
 
@@ -863,6 +935,7 @@ pub fn rst<const ADDR: u16>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RST", [Operand::Vector(ADDR)], 16),
     }
 }
 
@@ -889,6 +962,10 @@ pub fn interrupt_service_routine() -> Instruction {
                 let vector = match state {
                     Some(flag) => {
                         bus.interrupts.clear_interupt(flag);
+
+                        #[cfg(feature = "debug_info")]
+                        bus.breakpoints.on_interrupt_dispatch(flag);
+
                         flag.vector()
                     }
                     None => 0,
@@ -898,6 +975,7 @@ pub fn interrupt_service_routine() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("ISR", [], 20),
     }
 }
 
@@ -912,6 +990,7 @@ pub fn cb_op_r8<Op: CbOp, R: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new(Op::NAME, [Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -929,6 +1008,7 @@ pub fn cb_op_hlmem<Op: CbOp>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new(Op::NAME, [Operand::RegMem("HL")], 16),
     }
 }
 
@@ -944,6 +1024,7 @@ pub fn bit<const N: u8, R: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("BIT", [Operand::Bit(N), Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -960,6 +1041,7 @@ pub fn bit_hlmem<const N: u8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("BIT", [Operand::Bit(N), Operand::RegMem("HL")], 12),
     }
 }
 
@@ -973,6 +1055,7 @@ pub fn res<const N: u8, R: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RES", [Operand::Bit(N), Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -990,6 +1073,7 @@ pub fn res_hlmem<const N: u8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("RES", [Operand::Bit(N), Operand::RegMem("HL")], 16),
     }
 }
 
@@ -1003,6 +1087,7 @@ pub fn set<const N: u8, R: Reg8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("SET", [Operand::Bit(N), Operand::Reg(R::NAME)], 8),
     }
 }
 
@@ -1020,5 +1105,6 @@ pub fn set_hlmem<const N: u8>() -> Instruction {
                 InstructionState::Finished
             }),
         ],
+        info: InstructionInfo::new("SET", [Operand::Bit(N), Operand::RegMem("HL")], 16),
     }
 }
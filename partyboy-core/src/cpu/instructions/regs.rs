@@ -2,6 +2,8 @@ use super::super::Cpu;
 use super::super::register::Flag;
 
 pub trait Reg8 {
+    const NAME: &'static str;
+
     fn read(cpu: &Cpu) -> u8;
     fn write(cpu: &mut Cpu, v: u8);
 }
@@ -15,6 +17,8 @@ pub struct H;
 pub struct L;
 
 impl Reg8 for A {
+    const NAME: &'static str = "A";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.af.hi
@@ -26,6 +30,8 @@ impl Reg8 for A {
 }
 
 impl Reg8 for B {
+    const NAME: &'static str = "B";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.bc.hi
@@ -37,6 +43,8 @@ impl Reg8 for B {
 }
 
 impl Reg8 for C {
+    const NAME: &'static str = "C";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.bc.lo
@@ -48,6 +56,8 @@ impl Reg8 for C {
 }
 
 impl Reg8 for D {
+    const NAME: &'static str = "D";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.de.hi
@@ -59,6 +69,8 @@ impl Reg8 for D {
 }
 
 impl Reg8 for E {
+    const NAME: &'static str = "E";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.de.lo
@@ -70,6 +82,8 @@ impl Reg8 for E {
 }
 
 impl Reg8 for H {
+    const NAME: &'static str = "H";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.hl.hi
@@ -81,6 +95,8 @@ impl Reg8 for H {
 }
 
 impl Reg8 for L {
+    const NAME: &'static str = "L";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u8 {
         cpu.hl.lo
@@ -92,6 +108,8 @@ impl Reg8 for L {
 }
 
 pub trait Reg16 {
+    const NAME: &'static str;
+
     fn read(cpu: &Cpu) -> u16;
     fn read_hi(cpu: &Cpu) -> u8;
     fn read_lo(cpu: &Cpu) -> u8;
@@ -108,6 +126,8 @@ pub struct AF;
 pub struct SP;
 
 impl Reg16 for BC {
+    const NAME: &'static str = "BC";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u16 {
         cpu.bc.into()
@@ -139,6 +159,8 @@ impl Reg16 for BC {
 }
 
 impl Reg16 for DE {
+    const NAME: &'static str = "DE";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u16 {
         cpu.de.into()
@@ -170,6 +192,8 @@ impl Reg16 for DE {
 }
 
 impl Reg16 for HL {
+    const NAME: &'static str = "HL";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u16 {
         cpu.hl.into()
@@ -201,6 +225,8 @@ impl Reg16 for HL {
 }
 
 impl Reg16 for AF {
+    const NAME: &'static str = "AF";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u16 {
         cpu.af.into()
@@ -234,6 +260,8 @@ impl Reg16 for AF {
 }
 
 impl Reg16 for SP {
+    const NAME: &'static str = "SP";
+
     #[inline(always)]
     fn read(cpu: &Cpu) -> u16 {
         cpu.sp
@@ -268,6 +296,8 @@ pub mod cond {
     use super::*;
 
     pub trait Cond {
+        const NAME: &'static str;
+
         fn matches(cpu: &Cpu) -> bool;
     }
 
@@ -277,24 +307,32 @@ pub mod cond {
     pub struct NotCarry;
 
     impl Cond for Z {
+        const NAME: &'static str = "Z";
+
         #[inline(always)]
         fn matches(cpu: &Cpu) -> bool {
             cpu.is_flag_set(Flag::Z)
         }
     }
     impl Cond for NZ {
+        const NAME: &'static str = "NZ";
+
         #[inline(always)]
         fn matches(cpu: &Cpu) -> bool {
             !cpu.is_flag_set(Flag::Z)
         }
     }
     impl Cond for Carry {
+        const NAME: &'static str = "C";
+
         #[inline(always)]
         fn matches(cpu: &Cpu) -> bool {
             cpu.is_flag_set(Flag::C)
         }
     }
     impl Cond for NotCarry {
+        const NAME: &'static str = "NC";
+
         #[inline(always)]
         fn matches(cpu: &Cpu) -> bool {
             !cpu.is_flag_set(Flag::C)
@@ -10,11 +10,11 @@ use serde::{Deserialize, Serialize};
 
 use std::fmt::Debug;
 
-use crate::bus::Bus;
-use instructions::{Instruction, InstructionOpcode, InstructionState, InstructionStep};
+use crate::{bus::Bus, trace::TraceRegisters};
+use instructions::{Instruction, InstructionState, InstructionStep};
 use register::Register;
 
-pub use instructions::InstructionCache;
+pub use instructions::{InstructionCache, InstructionInfo, InstructionOpcode, Operand};
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub(crate) struct Cpu {
@@ -46,6 +46,14 @@ pub(crate) struct Cpu {
     halt_bug_triggered: bool,
     ei_delay: bool,
     ei_delay_cycles: u8,
+
+    /// PC of the instruction fetched during the last tick, for tracing
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fetched_pc: Option<u16>,
+
+    #[cfg(feature = "debug_info")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) call_stack: crate::debug::call_stack::CallStack,
 }
 
 impl Debug for Cpu {
@@ -97,6 +105,11 @@ impl Cpu {
             halt_bug_triggered: false,
             ei_delay: false,
             ei_delay_cycles: 0,
+
+            fetched_pc: None,
+
+            #[cfg(feature = "debug_info")]
+            call_stack: Default::default(),
         }
     }
 
@@ -123,6 +136,24 @@ impl Cpu {
         self.is_fetching = true;
     }
 
+    /// The registers as the DMG boot rom leaves them
+    pub(crate) fn set_dmg_boot_registers(&mut self) {
+        self.af = 0x01B0.into();
+        self.bc = 0x0013.into();
+        self.de = 0x00D8.into();
+        self.hl = 0x014D.into();
+        self.sp = 0xFFFE;
+    }
+
+    /// The registers as the SGB boot rom leaves them
+    pub(crate) fn set_sgb_boot_registers(&mut self) {
+        self.af = 0x0100.into();
+        self.bc = 0x0014.into();
+        self.de = 0x0000.into();
+        self.hl = 0xC060.into();
+        self.sp = 0xFFFE;
+    }
+
     pub fn stopped(&self) -> bool {
         self.stopped
     }
@@ -135,6 +166,44 @@ impl Cpu {
         self.instruction_opcode.is_some()
     }
 
+    #[cfg(feature = "debug_info")]
+    pub fn registers(&self) -> crate::debug::GBRegisters {
+        crate::debug::GBRegisters {
+            af: self.af.into(),
+            bc: self.bc.into(),
+            de: self.de.into(),
+            hl: self.hl.into(),
+            sp: self.sp,
+            pc: self.pc,
+        }
+    }
+
+    #[cfg(feature = "debug_info")]
+    pub fn set_registers(&mut self, registers: &crate::debug::GBRegisters) {
+        // the low nibble of F doesn't exist in hardware and always reads as 0
+        self.af = (registers.af & 0xFFF0).into();
+        self.bc = registers.bc.into();
+        self.de = registers.de.into();
+        self.hl = registers.hl.into();
+        self.sp = registers.sp;
+        self.pc = registers.pc;
+    }
+
+    pub(crate) fn trace_registers(&self) -> TraceRegisters {
+        TraceRegisters {
+            af: self.af.into(),
+            bc: self.bc.into(),
+            de: self.de.into(),
+            hl: self.hl.into(),
+            sp: self.sp,
+        }
+    }
+
+    /// The PC of the instruction fetched during the last tick, if one was
+    pub(crate) fn take_fetched_pc(&mut self) -> Option<u16> {
+        self.fetched_pc.take()
+    }
+
     fn fetch(&mut self, bus: &mut Bus) -> u8 {
         let op = bus.read_u8(self.pc);
         self.pc += 1;
@@ -167,7 +236,16 @@ impl Cpu {
         }
 
         if self.instruction_opcode.is_none() {
+            #[cfg(feature = "debug_info")]
+            {
+                let bus: &Bus = bus;
+                if bus.breakpoints.on_fetch(self.pc, || self.registers(), bus) {
+                    return;
+                }
+            }
+
             self.is_fetching = true;
+            self.fetched_pc = Some(self.pc);
 
             let opcode = self.fetch(bus);
 
@@ -237,6 +315,12 @@ impl Cpu {
             InstructionState::InProgress => {}
             InstructionState::ExecNextInstantly => self.exec(instruction, bus),
             InstructionState::Finished => {
+                #[cfg(feature = "debug_info")]
+                if let Some(opcode) = self.instruction_opcode {
+                    self.call_stack
+                        .on_instruction_finished(opcode, self.pc, self.sp, bus);
+                }
+
                 self.handle_instruction_finish();
             }
             InstructionState::Branch(continue_exec) => {
@@ -122,3 +122,29 @@ macro_rules! define_opcode_timing_tests {
 define_opcode_timing_tests!();
 
 define_opcode_timing_tests!(CB);
+
+#[test]
+fn instruction_info_matches_cycle_tables() {
+    let instruction_cache = InstructionCache::new();
+
+    for op in 0..=255u8 {
+        let info = instruction_cache.info(InstructionOpcode::Unprefixed(op));
+        if op != 0xCB {
+            // The timing table was recorded with Z and C clear, so NZ/NC branches are taken
+            let expected = CYCLE_TABLE[op as usize];
+            assert!(
+                info.cycles as u64 == expected
+                    || info.branch_cycles.map(u64::from) == Some(expected),
+                "Opcode {op:#04X} has the wrong cycle count in its instruction info"
+            );
+        }
+
+        let info = instruction_cache.info(InstructionOpcode::Prefixed(op));
+        assert_eq!(
+            info.cycles as u64,
+            CB_CYCLE_TABLE[op as usize],
+            "Opcode {:#06X} has the wrong cycle count in its instruction info",
+            0xCB00 + op as u16
+        );
+    }
+}
@@ -0,0 +1,659 @@
+use std::{cell::Cell, fmt::Display, ops::RangeInclusive};
+
+pub use crate::interrupts::InterruptFlag;
+use crate::{
+    bus::Bus,
+    ppu::{Ppu, PpuMode},
+};
+
+use super::GBRegisters;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BreakpointId(usize);
+
+impl Display for BreakpointId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpuRegister {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConditionOperand {
+    Register(CpuRegister),
+    /// The byte at the given address, read without triggering watchpoints
+    Memory(u16),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct BreakCondition {
+    pub operand: ConditionOperand,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl BreakCondition {
+    fn evaluate(&self, registers: &GBRegisters, bus: &Bus) -> bool {
+        let lhs = match self.operand {
+            ConditionOperand::Register(reg) => registers.get(reg),
+            ConditionOperand::Memory(addr) => bus.peek_u8(addr) as u16,
+        };
+
+        match self.comparison {
+            Comparison::Eq => lhs == self.value,
+            Comparison::Ne => lhs != self.value,
+            Comparison::Lt => lhs < self.value,
+            Comparison::Le => lhs <= self.value,
+            Comparison::Gt => lhs > self.value,
+            Comparison::Ge => lhs >= self.value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchAccess {
+    fn matches(&self, access: WatchAccess) -> bool {
+        matches!(self, WatchAccess::ReadWrite) || *self == access
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Opcode {
+    Unprefixed(u8),
+    Prefixed(u8),
+}
+
+impl Opcode {
+    /// `LD B,B`, commonly used by homebrew as a software breakpoint
+    pub const SOFTWARE_BREAKPOINT: Opcode = Opcode::Unprefixed(0x40);
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Opcode::Unprefixed(op) => write!(f, "{:#04X}", op),
+            Opcode::Prefixed(op) => write!(f, "{:#06X}", 0xCB00 + *op as u16),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Breakpoint {
+    /// Break before the instruction at `addr` is executed. If `bank` is set, the
+    /// bank mapped at `addr` must match too.
+    Execution {
+        addr: u16,
+        bank: Option<usize>,
+        condition: Option<BreakCondition>,
+    },
+    Watch {
+        range: RangeInclusive<u16>,
+        access: WatchAccess,
+    },
+    /// Break once the cpu has jumped to the interrupt vector. `None` matches any interrupt.
+    Interrupt(Option<InterruptFlag>),
+    /// Break before an instruction with the given opcode is executed
+    Opcode(Opcode),
+    PpuLine(u8),
+    PpuMode(PpuMode),
+}
+
+impl Breakpoint {
+    pub fn execution(addr: u16) -> Self {
+        Breakpoint::Execution {
+            addr,
+            bank: None,
+            condition: None,
+        }
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Execution {
+                addr,
+                bank,
+                condition,
+            } => {
+                match bank {
+                    Some(bank) => write!(f, "Exec {:02X}:{:04X}", bank, addr)?,
+                    None => write!(f, "Exec {:#06X}", addr)?,
+                }
+
+                if let Some(condition) = condition {
+                    write!(
+                        f,
+                        " if {:?} {:?} {:#06X}",
+                        condition.operand, condition.comparison, condition.value
+                    )?;
+                }
+
+                Ok(())
+            }
+            Breakpoint::Watch { range, access } => write!(
+                f,
+                "Watch {:?} {:#06X}..={:#06X}",
+                access,
+                range.start(),
+                range.end()
+            ),
+            Breakpoint::Interrupt(Some(interrupt)) => write!(f, "Interrupt {}", interrupt),
+            Breakpoint::Interrupt(None) => write!(f, "Any interrupt"),
+            Breakpoint::Opcode(opcode) => write!(f, "Opcode {}", opcode),
+            Breakpoint::PpuLine(ly) => write!(f, "LY = {}", ly),
+            Breakpoint::PpuMode(mode) => write!(f, "PPU {:?}", mode),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BreakReason {
+    Execution {
+        id: BreakpointId,
+        addr: u16,
+    },
+    Watch {
+        id: BreakpointId,
+        addr: u16,
+        access: WatchAccess,
+        value: u8,
+    },
+    Interrupt {
+        id: BreakpointId,
+        interrupt: InterruptFlag,
+    },
+    Opcode {
+        id: BreakpointId,
+        addr: u16,
+        opcode: Opcode,
+    },
+    PpuLine {
+        id: BreakpointId,
+        ly: u8,
+    },
+    PpuMode {
+        id: BreakpointId,
+        mode: PpuMode,
+    },
+}
+
+impl BreakReason {
+    pub fn id(&self) -> BreakpointId {
+        match self {
+            BreakReason::Execution { id, .. }
+            | BreakReason::Watch { id, .. }
+            | BreakReason::Interrupt { id, .. }
+            | BreakReason::Opcode { id, .. }
+            | BreakReason::PpuLine { id, .. }
+            | BreakReason::PpuMode { id, .. } => *id,
+        }
+    }
+}
+
+impl Display for BreakReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BreakReason::Execution { id, addr } => {
+                write!(f, "Breakpoint {} hit at {:#06X}", id, addr)
+            }
+            BreakReason::Watch {
+                id,
+                addr,
+                access,
+                value,
+            } => write!(
+                f,
+                "Watchpoint {} hit: {:?} {:#04X} at {:#06X}",
+                id, access, value, addr
+            ),
+            BreakReason::Interrupt { id, interrupt } => {
+                write!(
+                    f,
+                    "Breakpoint {} hit: {} interrupt dispatched",
+                    id, interrupt
+                )
+            }
+            BreakReason::Opcode { id, addr, opcode } => {
+                write!(
+                    f,
+                    "Breakpoint {} hit: opcode {} at {:#06X}",
+                    id, opcode, addr
+                )
+            }
+            BreakReason::PpuLine { id, ly } => write!(f, "Breakpoint {} hit: LY = {}", id, ly),
+            BreakReason::PpuMode { id, mode } => {
+                write!(f, "Breakpoint {} hit: PPU entered {:?}", id, mode)
+            }
+        }
+    }
+}
+
+/// Holds all breakpoints and watchpoints, and the reason the emulator last stopped.
+///
+/// Each kind of breakpoint has a flag that is checked before doing any real work,
+/// so hooks are a single branch when nothing of that kind is set.
+#[derive(Default)]
+pub struct Breakpoints {
+    next_id: usize,
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+
+    any_execution: bool,
+    any_watch: bool,
+    any_interrupt: bool,
+    any_ppu: bool,
+
+    last_ppu_state: Option<(u8, PpuMode)>,
+
+    // reads go through `&Bus`, so the hit needs to be settable from a shared ref
+    hit: Cell<Option<BreakReason>>,
+    /// The PC the cpu stopped at before fetching, which is let through on the next fetch
+    /// so resuming doesn't stop at the same breakpoint again
+    resume_pc: Cell<Option<u16>>,
+}
+
+impl Breakpoints {
+    pub fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let id = BreakpointId(self.next_id);
+        self.next_id += 1;
+
+        self.breakpoints.push((id, breakpoint));
+        self.update_flags();
+        id
+    }
+
+    pub fn remove(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        let index = self
+            .breakpoints
+            .iter()
+            .position(|(bp_id, _)| *bp_id == id)?;
+        let (_, breakpoint) = self.breakpoints.remove(index);
+        self.update_flags();
+        Some(breakpoint)
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.update_flags();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(BreakpointId, Breakpoint)> {
+        self.breakpoints.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Returns the reason the emulator stopped, if a breakpoint was hit since the last call
+    pub fn take_hit(&mut self) -> Option<BreakReason> {
+        self.hit.take()
+    }
+
+    pub(crate) fn set_hit(&mut self, hit: Option<BreakReason>) {
+        self.hit.set(hit);
+    }
+
+    fn update_flags(&mut self) {
+        self.any_execution = false;
+        self.any_watch = false;
+        self.any_interrupt = false;
+        self.any_ppu = false;
+
+        for (_, breakpoint) in &self.breakpoints {
+            match breakpoint {
+                Breakpoint::Execution { .. } | Breakpoint::Opcode(_) => self.any_execution = true,
+                Breakpoint::Watch { .. } => self.any_watch = true,
+                Breakpoint::Interrupt(_) => self.any_interrupt = true,
+                Breakpoint::PpuLine(_) | Breakpoint::PpuMode(_) => self.any_ppu = true,
+            }
+        }
+
+        self.last_ppu_state = None;
+    }
+
+    fn trigger(&self, reason: BreakReason) {
+        // keep the first event, that's the one the emulator stopped at
+        if self.hit.get().is_none() {
+            self.hit.set(Some(reason));
+        }
+    }
+
+    /// Called right before the cpu fetches the instruction at `pc`. Returns whether a
+    /// breakpoint was hit, in which case the cpu stops without fetching. The registers are
+    /// only read when a breakpoint with a condition is at `pc`
+    #[inline]
+    pub(crate) fn on_fetch(&self, pc: u16, registers: impl Fn() -> GBRegisters, bus: &Bus) -> bool {
+        let resume_pc = self.resume_pc.take();
+        if !self.any_execution || resume_pc == Some(pc) {
+            return false;
+        }
+
+        let mut hit = false;
+        for (id, breakpoint) in &self.breakpoints {
+            match breakpoint {
+                Breakpoint::Execution {
+                    addr,
+                    bank,
+                    condition,
+                } => {
+                    if *addr != pc {
+                        continue;
+                    }
+
+                    if bank.is_some_and(|bank| bank != bus.mapped_bank(pc)) {
+                        continue;
+                    }
+
+                    if condition.is_some_and(|condition| !condition.evaluate(&registers(), bus)) {
+                        continue;
+                    }
+
+                    self.trigger(BreakReason::Execution { id: *id, addr: pc });
+                    hit = true;
+                }
+                Breakpoint::Opcode(opcode) => {
+                    let current = match bus.peek_u8(pc) {
+                        0xCB => Opcode::Prefixed(bus.peek_u8(pc.wrapping_add(1))),
+                        op => Opcode::Unprefixed(op),
+                    };
+
+                    if current == *opcode {
+                        self.trigger(BreakReason::Opcode {
+                            id: *id,
+                            addr: pc,
+                            opcode: current,
+                        });
+                        hit = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if hit {
+            self.resume_pc.set(Some(pc));
+        }
+        hit
+    }
+
+    #[inline]
+    pub(crate) fn on_access(&self, addr: u16, value: u8, access: WatchAccess) {
+        if !self.any_watch {
+            return;
+        }
+
+        for (id, breakpoint) in &self.breakpoints {
+            if let Breakpoint::Watch {
+                range,
+                access: watch_access,
+            } = breakpoint
+            {
+                if range.contains(&addr) && watch_access.matches(access) {
+                    self.trigger(BreakReason::Watch {
+                        id: *id,
+                        addr,
+                        access,
+                        value,
+                    });
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_interrupt_dispatch(&self, interrupt: InterruptFlag) {
+        if !self.any_interrupt {
+            return;
+        }
+
+        for (id, breakpoint) in &self.breakpoints {
+            if let Breakpoint::Interrupt(flag) = breakpoint {
+                if flag.is_none_or(|flag| flag == interrupt) {
+                    self.trigger(BreakReason::Interrupt { id: *id, interrupt });
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn on_ppu_tick(&mut self, ppu: &Ppu) {
+        if !self.any_ppu {
+            return;
+        }
+
+        let state = (ppu.ly, ppu.get_mode_stat());
+        let Some((prev_ly, prev_mode)) = self.last_ppu_state.replace(state) else {
+            return;
+        };
+
+        let (ly, mode) = state;
+        for (id, breakpoint) in &self.breakpoints {
+            match breakpoint {
+                Breakpoint::PpuLine(line) if ly != prev_ly && ly == *line => {
+                    self.trigger(BreakReason::PpuLine { id: *id, ly });
+                }
+                Breakpoint::PpuMode(bp_mode) if mode != prev_mode && mode == *bp_mode => {
+                    self.trigger(BreakReason::PpuMode { id: *id, mode });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BreakCondition, BreakReason, Breakpoint, Comparison, ConditionOperand, CpuRegister,
+        InterruptFlag, Opcode, WatchAccess,
+    };
+    use crate::{CYCLES_PER_FRAME, GameBoy, debug::test_util::gb_with_program, ppu::PpuMode};
+
+    /// LD A,$80; LDH ($40),A; JR -2
+    const LCD_ON: [u8; 6] = [0x3E, 0x80, 0xE0, 0x40, 0x18, 0xFE];
+    /// INC A; JR -3
+    const COUNT_A: [u8; 3] = [0x3C, 0x18, 0xFD];
+
+    fn run_until_break(gb: &mut GameBoy) -> Option<BreakReason> {
+        for _ in 0..CYCLES_PER_FRAME {
+            gb.tick();
+            if let Some(reason) = gb.take_break_reason() {
+                return Some(reason);
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn execution_breakpoint_stops_before_instruction() {
+        let mut gb = gb_with_program(&[]);
+        gb.breakpoints_mut().add(Breakpoint::execution(0x0010));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(
+            reason,
+            BreakReason::Execution { addr: 0x0010, .. }
+        ));
+        assert_eq!(gb.cpu.pc, 0x0010);
+        assert!(!gb.cpu.is_processing_instruction());
+    }
+
+    #[test]
+    fn execution_breakpoint_on_first_instruction_and_resume() {
+        let mut gb = gb_with_program(&COUNT_A);
+        gb.breakpoints_mut().add(Breakpoint::execution(0x0000));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(
+            reason,
+            BreakReason::Execution { addr: 0x0000, .. }
+        ));
+        assert_eq!(gb.registers().af >> 8, 0);
+
+        // resuming runs the instruction it stopped at, then stops again the next time round
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit again");
+        assert!(matches!(
+            reason,
+            BreakReason::Execution { addr: 0x0000, .. }
+        ));
+        assert_eq!(gb.registers().af >> 8, 1);
+    }
+
+    #[test]
+    fn conditional_breakpoint() {
+        let mut gb = gb_with_program(&COUNT_A);
+        gb.breakpoints_mut().add(Breakpoint::Execution {
+            addr: 0x0000,
+            bank: None,
+            condition: Some(BreakCondition {
+                operand: ConditionOperand::Register(CpuRegister::A),
+                comparison: Comparison::Eq,
+                value: 5,
+            }),
+        });
+
+        run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert_eq!(gb.registers().af >> 8, 5);
+        assert_eq!(gb.cpu.pc, 0x0000);
+    }
+
+    #[test]
+    fn bank_filtered_breakpoint() {
+        let mut gb = gb_with_program(&COUNT_A);
+        let bank = |bank| Breakpoint::Execution {
+            addr: 0x0000,
+            bank: Some(bank),
+            condition: None,
+        };
+        // the bios is mapped, which counts as bank 0
+        gb.breakpoints_mut().add(bank(1));
+        let id = gb.breakpoints_mut().add(bank(0));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert_eq!(reason.id(), id);
+    }
+
+    #[test]
+    fn interrupt_breakpoint() {
+        // LD A,$04; LDH ($FF),A; LDH ($0F),A; EI; NOP; NOP
+        let mut gb = gb_with_program(&[0x3E, 0x04, 0xE0, 0xFF, 0xE0, 0x0F, 0xFB, 0x00, 0x00]);
+        gb.breakpoints_mut()
+            .add(Breakpoint::Interrupt(Some(InterruptFlag::VBlank)));
+        let id = gb
+            .breakpoints_mut()
+            .add(Breakpoint::Interrupt(Some(InterruptFlag::Timer)));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(
+            reason,
+            BreakReason::Interrupt {
+                interrupt: InterruptFlag::Timer,
+                ..
+            }
+        ));
+        assert_eq!(reason.id(), id);
+    }
+
+    #[test]
+    fn ppu_line_breakpoint() {
+        let mut gb = gb_with_program(&LCD_ON);
+        gb.breakpoints_mut().add(Breakpoint::PpuLine(2));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(reason, BreakReason::PpuLine { ly: 2, .. }));
+        assert_eq!(gb.bus.ppu.ly, 2);
+    }
+
+    #[test]
+    fn ppu_mode_breakpoint() {
+        let mut gb = gb_with_program(&LCD_ON);
+        gb.breakpoints_mut()
+            .add(Breakpoint::PpuMode(PpuMode::VBlank));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(
+            reason,
+            BreakReason::PpuMode {
+                mode: PpuMode::VBlank,
+                ..
+            }
+        ));
+        assert_eq!(gb.bus.ppu.ly, 144);
+    }
+
+    #[test]
+    fn write_watchpoint_reports_value() {
+        // LD A, 0x42; LD (0xC000), A
+        let mut gb = gb_with_program(&[0x3E, 0x42, 0xEA, 0x00, 0xC0]);
+        gb.breakpoints_mut().add(Breakpoint::Watch {
+            range: 0xC000..=0xC0FF,
+            access: WatchAccess::Write,
+        });
+
+        let reason = run_until_break(&mut gb).expect("Watchpoint was not hit");
+        assert!(matches!(
+            reason,
+            BreakReason::Watch {
+                addr: 0xC000,
+                access: WatchAccess::Write,
+                value: 0x42,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn software_breakpoint_opcode() {
+        // NOP; NOP; LD B,B
+        let mut gb = gb_with_program(&[0x00, 0x00, 0x40]);
+        gb.breakpoints_mut()
+            .add(Breakpoint::Opcode(Opcode::SOFTWARE_BREAKPOINT));
+
+        let reason = run_until_break(&mut gb).expect("Breakpoint was not hit");
+        assert!(matches!(reason, BreakReason::Opcode { addr: 0x0002, .. }));
+    }
+
+    #[test]
+    fn removed_breakpoint_is_not_hit() {
+        let mut gb = gb_with_program(&[]);
+        let id = gb.breakpoints_mut().add(Breakpoint::execution(0x0010));
+        gb.breakpoints_mut().remove(id);
+
+        assert!(run_until_break(&mut gb).is_none());
+    }
+}
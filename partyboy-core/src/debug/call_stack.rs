@@ -0,0 +1,139 @@
+use std::fmt::Display;
+
+use crate::{bus::Bus, cpu::InstructionOpcode};
+
+/// Frames deeper than this are dropped from the bottom, so runaway recursion
+/// (or code that never returns) can't grow the stack forever
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CallKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    /// Address the call jumped to
+    pub target: u16,
+    /// Bank mapped at `target` when the call happened
+    pub bank: usize,
+    /// Address the matching return will go back to
+    pub return_addr: u16,
+    /// SP right after the return address was pushed
+    sp: u16,
+}
+
+impl Display for CallFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {:02X}:{:04X} (returns to {:04X})",
+            self.kind, self.bank, self.target, self.return_addr
+        )
+    }
+}
+
+/// Tracks calls and returns as they execute. This is a best effort view: code that
+/// manipulates the return address on the stack itself won't be followed exactly, but
+/// frames are dropped as soon as SP moves above them so the stack resyncs on the next return
+#[derive(Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+}
+
+impl CallStack {
+    /// Frames from outermost to innermost
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub(crate) fn on_instruction_finished(
+        &mut self,
+        opcode: InstructionOpcode,
+        pc: u16,
+        sp: u16,
+        bus: &Bus,
+    ) {
+        let kind = match opcode {
+            InstructionOpcode::InterruptServiceRoutine => Some(CallKind::Interrupt),
+            // CALL / CALL cc
+            InstructionOpcode::Unprefixed(0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC) => Some(CallKind::Call),
+            // RST
+            InstructionOpcode::Unprefixed(op) if op & 0xC7 == 0xC7 => Some(CallKind::Rst),
+            // RET / RETI / RET cc
+            InstructionOpcode::Unprefixed(0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8) => None,
+            _ => return,
+        };
+
+        let Some(kind) = kind else {
+            // anything below the current SP has been returned from (or discarded)
+            self.frames.retain(|frame| frame.sp >= sp);
+            return;
+        };
+
+        // a frame in the slot we just pushed to is stale
+        self.frames.retain(|frame| frame.sp > sp);
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+
+        let return_addr = u16::from_le_bytes([bus.peek_u8(sp), bus.peek_u8(sp.wrapping_add(1))]);
+        self.frames.push(CallFrame {
+            kind,
+            target: pc,
+            bank: bus.mapped_bank(pc),
+            return_addr,
+            sp,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CallKind;
+    use crate::{
+        GameBoy, bus::Bus, cartridge::rom::create_test_rom, debug::breakpoint::Breakpoint,
+    };
+
+    fn run_to(gb: &mut GameBoy, addr: u16) {
+        let id = gb.breakpoints_mut().add(Breakpoint::execution(addr));
+        for _ in 0..1000 {
+            gb.tick();
+            if gb.take_break_reason().is_some() {
+                gb.breakpoints_mut().remove(id);
+                return;
+            }
+        }
+
+        panic!("Never reached {addr:#06X}");
+    }
+
+    #[test]
+    fn tracks_nested_calls_and_returns() {
+        let mut bios = [0; 0x900];
+        // LD SP, 0xFFFE; CALL 0x0010
+        bios[..6].copy_from_slice(&[0x31, 0xFE, 0xFF, 0xCD, 0x10, 0x00]);
+        // CALL 0x0020
+        bios[0x10..0x13].copy_from_slice(&[0xCD, 0x20, 0x00]);
+        // RET
+        bios[0x20] = 0xC9;
+
+        let mut gb = GameBoy::new(None, bios, Bus::get_handle_blargg_output());
+        gb.bus.cartridge = Some(create_test_rom());
+
+        run_to(&mut gb, 0x0020);
+        let frames = gb.call_stack();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].kind, CallKind::Call);
+        assert_eq!((frames[0].target, frames[0].return_addr), (0x0010, 0x0006));
+        assert_eq!((frames[1].target, frames[1].return_addr), (0x0020, 0x0013));
+
+        run_to(&mut gb, 0x0013);
+        let frames = gb.call_stack();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].target, 0x0010);
+    }
+}
@@ -0,0 +1,711 @@
+//! A GDB remote serial protocol stub, so ROMs can be debugged with gdb (or anything else
+//! that speaks RSP) over TCP.
+//!
+//! GDB has no SM83 architecture, so the register layout is described with a target
+//! description: `af bc de hl sp pc`, all 16 bits and little endian in `g`/`G` packets.
+//!
+//! The stub doesn't own a thread or the emulator. Call [`GdbStub::poll`] from whatever
+//! loop drives the `GameBoy`, keep ticking while it says to continue, and report
+//! breakpoint hits with [`GdbStub::notify_stopped`]. [`GdbStub::run`] does all of that
+//! for headless use.
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use crate::GameBoy;
+
+use super::{
+    GBRegisters,
+    breakpoint::{BreakReason, Breakpoint, BreakpointId, WatchAccess},
+};
+
+/// Largest packet we accept, advertised to gdb in `qSupported`
+const PACKET_SIZE: usize = 0x1000;
+/// Ticks to run between checking the socket for an interrupt when using `run`
+const RUN_CHUNK_TICKS: usize = 4096;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.partyboy.sm83">
+    <reg name="af" bitsize="16" type="uint16" regnum="0"/>
+    <reg name="bc" bitsize="16" type="uint16"/>
+    <reg name="de" bitsize="16" type="uint16"/>
+    <reg name="hl" bitsize="16" type="uint16"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// What the emulator should do after a call to [`GdbStub::poll`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GdbEvent {
+    /// Nothing changed
+    None,
+    /// gdb asked to continue, keep ticking until a breakpoint is hit
+    Continue,
+    /// Emulation stopped, either after a single step or because gdb interrupted it
+    Stopped,
+    /// gdb detached or the connection was closed. The stub should be dropped
+    Detached,
+}
+
+enum Incoming {
+    Packet(Vec<u8>),
+    Interrupt,
+}
+
+/// Breakpoints gdb inserted, keyed the way gdb refers to them in `z` packets
+struct GdbBreakpoint {
+    kind: u8,
+    addr: u16,
+    len: u16,
+    id: BreakpointId,
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    no_ack: bool,
+    running: bool,
+    last_stop: String,
+    breakpoints: Vec<GdbBreakpoint>,
+}
+
+impl GdbStub {
+    /// Waits for gdb to connect, e.g. with `target remote localhost:<port>`
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, addr) = listener.accept()?;
+        log::info!("gdb connected from {}", addr);
+        Self::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            no_ack: false,
+            running: false,
+            last_stop: stop_reply(None),
+            breakpoints: Vec::new(),
+        })
+    }
+
+    /// Whether gdb expects the emulator to be running
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Handles any packets gdb has sent since the last call. Never blocks.
+    ///
+    /// Memory, register and breakpoint requests are applied to `gb` directly, and single
+    /// steps are run to completion before this returns
+    pub fn poll(&mut self, gb: &mut GameBoy) -> io::Result<GdbEvent> {
+        if !self.read_available()? {
+            self.remove_breakpoints(gb);
+            return Ok(GdbEvent::Detached);
+        }
+
+        let mut event = GdbEvent::None;
+        while let Some(incoming) = self.next_incoming()? {
+            let handled = match incoming {
+                Incoming::Interrupt if self.running => {
+                    self.notify_stopped(None)?;
+                    GdbEvent::Stopped
+                }
+                Incoming::Interrupt => GdbEvent::None,
+                Incoming::Packet(packet) => self.handle_packet(gb, &packet)?,
+            };
+
+            if handled == GdbEvent::Detached {
+                self.remove_breakpoints(gb);
+                return Ok(GdbEvent::Detached);
+            }
+            if handled != GdbEvent::None {
+                event = handled;
+            }
+        }
+
+        Ok(event)
+    }
+
+    /// Tells gdb the emulator stopped. Pass the breakpoint that was hit, if any, so
+    /// watchpoints can be reported with the address that triggered them
+    pub fn notify_stopped(&mut self, reason: Option<BreakReason>) -> io::Result<()> {
+        self.running = false;
+        self.last_stop = match reason {
+            Some(reason) => self.stop_reply_for(reason),
+            None => stop_reply(Some(SIGINT)),
+        };
+
+        let reply = self.last_stop.clone();
+        self.send(reply.as_bytes())
+    }
+
+    /// Drives `gb` on behalf of gdb until it detaches. Runs as fast as possible while
+    /// continuing, there's no frame pacing
+    pub fn run(&mut self, gb: &mut GameBoy) -> io::Result<()> {
+        loop {
+            if self.poll(gb)? == GdbEvent::Detached {
+                return Ok(());
+            }
+
+            if !self.running {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            for _ in 0..RUN_CHUNK_TICKS {
+                gb.tick();
+                if let Some(reason) = gb.take_break_reason() {
+                    self.notify_stopped(Some(reason))?;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Reads whatever is waiting on the socket. Returns false if the connection closed
+    fn read_available(&mut self) -> io::Result<bool> {
+        self.stream.set_nonblocking(true)?;
+
+        let mut chunk = [0; 1024];
+        let result = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Ok(false),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => break Ok(false),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    /// Pops the next complete packet or interrupt off the buffer, acking packets as we go
+    fn next_incoming(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            let Some(&first) = self.buffer.first() else {
+                return Ok(None);
+            };
+
+            match first {
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Incoming::Interrupt));
+                }
+                b'$' => {
+                    let Some(end) = self.buffer.iter().position(|b| *b == b'#') else {
+                        return Ok(None);
+                    };
+                    if self.buffer.len() < end + 3 {
+                        return Ok(None);
+                    }
+
+                    let frame: Vec<u8> = self.buffer.drain(..end + 3).collect();
+                    let data = &frame[1..end];
+                    let checksum = std::str::from_utf8(&frame[end + 1..])
+                        .ok()
+                        .and_then(|cs| u8::from_str_radix(cs, 16).ok());
+
+                    if checksum != Some(checksum_of(data)) {
+                        log::warn!("gdb packet with a bad checksum, asking for a resend");
+                        if !self.no_ack {
+                            self.stream.write_all(b"-")?;
+                        }
+                        continue;
+                    }
+
+                    if !self.no_ack {
+                        self.stream.write_all(b"+")?;
+                    }
+                    return Ok(Some(Incoming::Packet(data.to_vec())));
+                }
+                // acks for our own packets, and anything else between packets
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(data.len() + 4);
+        frame.push(b'$');
+        frame.extend_from_slice(data);
+        frame.extend_from_slice(format!("#{:02x}", checksum_of(data)).as_bytes());
+
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+
+    fn handle_packet(&mut self, gb: &mut GameBoy, packet: &[u8]) -> io::Result<GdbEvent> {
+        let Ok(packet) = std::str::from_utf8(packet) else {
+            self.send(b"")?;
+            return Ok(GdbEvent::None);
+        };
+        // an empty reply tells gdb the packet isn't supported
+        let Some((command, args)) = packet.split_at_checked(1) else {
+            self.send(b"")?;
+            return Ok(GdbEvent::None);
+        };
+
+        let reply = match command {
+            "?" => self.last_stop.clone(),
+            "g" => encode_registers(&gb.registers()),
+            "G" => match decode_registers(args) {
+                Some(registers) => {
+                    gb.set_registers(registers);
+                    "OK".to_string()
+                }
+                None => error_reply(),
+            },
+            "p" => match parse_hex(args).and_then(|n| register(&gb.registers(), n)) {
+                Some(value) => hex_encode(&value.to_le_bytes()),
+                None => error_reply(),
+            },
+            "P" => match self.write_register(gb, args) {
+                Some(()) => "OK".to_string(),
+                None => error_reply(),
+            },
+            "m" => match parse_range(args) {
+                Some((addr, len)) => {
+                    let bytes = (0..len)
+                        .map(|i| gb.peek_u8(addr.wrapping_add(i)))
+                        .collect::<Vec<_>>();
+                    hex_encode(&bytes)
+                }
+                None => error_reply(),
+            },
+            "M" => match write_memory(gb, args) {
+                Some(()) => "OK".to_string(),
+                None => error_reply(),
+            },
+            "c" => {
+                if let Some(pc) = parse_hex(args) {
+                    gb.set_registers(GBRegisters {
+                        pc,
+                        ..gb.registers()
+                    });
+                }
+
+                // the reply is sent once we stop
+                self.running = true;
+                return Ok(GdbEvent::Continue);
+            }
+            "s" => {
+                if let Some(pc) = parse_hex(args) {
+                    gb.set_registers(GBRegisters {
+                        pc,
+                        ..gb.registers()
+                    });
+                }
+
+                let reason = gb.step_instruction();
+                self.running = false;
+                self.last_stop = match reason {
+                    Some(reason) => self.stop_reply_for(reason),
+                    None => stop_reply(Some(SIGTRAP)),
+                };
+
+                let reply = self.last_stop.clone();
+                self.send(reply.as_bytes())?;
+                return Ok(GdbEvent::Stopped);
+            }
+            "Z" => match self.insert_breakpoint(gb, args) {
+                Some(reply) => reply,
+                None => error_reply(),
+            },
+            "z" => match self.remove_breakpoint(gb, args) {
+                Some(()) => "OK".to_string(),
+                None => error_reply(),
+            },
+            "D" => {
+                self.send(b"OK")?;
+                return Ok(GdbEvent::Detached);
+            }
+            "k" => return Ok(GdbEvent::Detached),
+            "H" => "OK".to_string(),
+            _ => self.handle_query(packet),
+        };
+
+        self.send(reply.as_bytes())?;
+
+        if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+        }
+
+        Ok(GdbEvent::None)
+    }
+
+    fn handle_query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+");
+        }
+
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, len)) = range.split_once(',') else {
+                return error_reply();
+            };
+            let (Ok(offset), Ok(len)) = (
+                usize::from_str_radix(offset, 16),
+                usize::from_str_radix(len, 16),
+            ) else {
+                return error_reply();
+            };
+
+            let start = offset.min(TARGET_XML.len());
+            let end = (start + len).min(TARGET_XML.len());
+            let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+            return format!("{marker}{}", &TARGET_XML[start..end]);
+        }
+
+        match packet {
+            "QStartNoAckMode" => "OK",
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            // an empty reply tells gdb the packet isn't supported
+            _ => "",
+        }
+        .to_string()
+    }
+
+    fn write_register(&self, gb: &mut GameBoy, args: &str) -> Option<()> {
+        let (n, value) = args.split_once('=')?;
+        let n = parse_hex(n)?;
+        let bytes = hex_decode(value)?;
+        let value = u16::from_le_bytes(bytes.try_into().ok()?);
+
+        let mut registers = gb.registers();
+        match n {
+            0 => registers.af = value,
+            1 => registers.bc = value,
+            2 => registers.de = value,
+            3 => registers.hl = value,
+            4 => registers.sp = value,
+            5 => registers.pc = value,
+            _ => return None,
+        }
+
+        gb.set_registers(registers);
+        Some(())
+    }
+
+    /// `Z type,addr,kind`. Types 0 and 1 are software and hardware breakpoints, which are
+    /// the same thing here, and 2-4 are write, read and access watchpoints
+    fn insert_breakpoint(&mut self, gb: &mut GameBoy, args: &str) -> Option<String> {
+        let (kind, addr, len) = parse_breakpoint(args)?;
+
+        let breakpoint = match kind {
+            0 | 1 => Breakpoint::execution(addr),
+            2..=4 => Breakpoint::Watch {
+                range: addr..=addr.saturating_add(len.max(1) - 1),
+                access: match kind {
+                    2 => WatchAccess::Write,
+                    3 => WatchAccess::Read,
+                    _ => WatchAccess::ReadWrite,
+                },
+            },
+            _ => return Some(String::new()),
+        };
+
+        let id = gb.breakpoints_mut().add(breakpoint);
+        self.breakpoints.push(GdbBreakpoint {
+            kind,
+            addr,
+            len,
+            id,
+        });
+        Some("OK".to_string())
+    }
+
+    fn remove_breakpoint(&mut self, gb: &mut GameBoy, args: &str) -> Option<()> {
+        let (kind, addr, len) = parse_breakpoint(args)?;
+        let index = self
+            .breakpoints
+            .iter()
+            .position(|bp| bp.kind == kind && bp.addr == addr && bp.len == len)?;
+
+        let removed = self.breakpoints.remove(index);
+        gb.breakpoints_mut().remove(removed.id);
+        Some(())
+    }
+
+    /// Cleans up after gdb goes away, so its breakpoints don't linger
+    fn remove_breakpoints(&mut self, gb: &mut GameBoy) {
+        for bp in self.breakpoints.drain(..) {
+            gb.breakpoints_mut().remove(bp.id);
+        }
+        self.running = false;
+    }
+
+    fn stop_reply_for(&self, reason: BreakReason) -> String {
+        let BreakReason::Watch { id, addr, .. } = reason else {
+            return stop_reply(Some(SIGTRAP));
+        };
+
+        // report watchpoints the way gdb set them, even if they were added elsewhere
+        let kind = self
+            .breakpoints
+            .iter()
+            .find(|bp| bp.id == id)
+            .map(|bp| bp.kind);
+        let name = match kind {
+            Some(3) => "rwatch",
+            Some(4) => "awatch",
+            _ => "watch",
+        };
+
+        format!("T{SIGTRAP:02x}{name}:{addr:x};")
+    }
+}
+
+fn stop_reply(signal: Option<u8>) -> String {
+    format!("S{:02x}", signal.unwrap_or(SIGTRAP))
+}
+
+fn error_reply() -> String {
+    "E01".to_string()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Option<u16> {
+    u16::from_str_radix(hex, 16).ok()
+}
+
+/// `addr,len`, with the length clamped so the range doesn't go past 0xFFFF
+fn parse_range(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    let addr = parse_hex(addr)?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    let len = len.min(0x10000 - addr as usize).min(PACKET_SIZE / 2);
+    Some((addr, len as u16))
+}
+
+fn parse_breakpoint(args: &str) -> Option<(u8, u16, u16)> {
+    let mut parts = args.split(',');
+    let kind = parts.next()?.parse().ok()?;
+    let addr = parse_hex(parts.next()?)?;
+    let len = parse_hex(parts.next()?.split(';').next()?)?;
+    Some((kind, addr, len))
+}
+
+fn write_memory(gb: &mut GameBoy, args: &str) -> Option<()> {
+    let (range, data) = args.split_once(':')?;
+    let (addr, len) = parse_range(range)?;
+    let bytes = hex_decode(data)?;
+    if bytes.len() != len as usize {
+        return None;
+    }
+
+    for (i, byte) in bytes.into_iter().enumerate() {
+        gb.poke_u8(addr.wrapping_add(i as u16), byte);
+    }
+    Some(())
+}
+
+fn register(registers: &GBRegisters, n: u16) -> Option<u16> {
+    Some(match n {
+        0 => registers.af,
+        1 => registers.bc,
+        2 => registers.de,
+        3 => registers.hl,
+        4 => registers.sp,
+        5 => registers.pc,
+        _ => return None,
+    })
+}
+
+fn encode_registers(registers: &GBRegisters) -> String {
+    (0..6)
+        .filter_map(|n| register(registers, n))
+        .map(|value| hex_encode(&value.to_le_bytes()))
+        .collect()
+}
+
+fn decode_registers(hex: &str) -> Option<GBRegisters> {
+    let bytes = hex_decode(hex)?;
+    if bytes.len() != 12 {
+        return None;
+    }
+
+    let value = |n: usize| u16::from_le_bytes([bytes[n * 2], bytes[n * 2 + 1]]);
+    Some(GBRegisters {
+        af: value(0),
+        bc: value(1),
+        de: value(2),
+        hl: value(3),
+        sp: value(4),
+        pc: value(5),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+        time::Duration,
+    };
+
+    use super::GdbStub;
+    use crate::debug::test_util::gb_with_program;
+
+    /// Just enough of a gdb client to drive the stub
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(port: u16) -> Self {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+
+            Self {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        fn send(&mut self, packet: &str) {
+            let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+            write!(self.writer, "${packet}#{checksum:02x}").unwrap();
+        }
+
+        fn receive(&mut self) -> String {
+            let mut reply = Vec::new();
+            self.reader.read_until(b'#', &mut reply).unwrap();
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum).unwrap();
+
+            let start = reply.iter().position(|b| *b == b'$').unwrap();
+            String::from_utf8(reply[start + 1..reply.len() - 1].to_vec()).unwrap()
+        }
+
+        fn request(&mut self, packet: &str) -> String {
+            self.send(packet);
+            self.receive()
+        }
+    }
+
+    fn run_session(program: &[u8], session: impl FnOnce(&mut Client) + Send + 'static) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut client = Client::connect(port);
+            session(&mut client);
+            assert_eq!(client.request("D"), "OK");
+        });
+
+        let mut gb = gb_with_program(program);
+        let mut stub = GdbStub::accept(&listener).unwrap();
+        stub.run(&mut gb).unwrap();
+
+        client.join().unwrap();
+        assert!(gb.breakpoints().is_empty());
+    }
+
+    #[test]
+    fn reads_and_writes_registers_and_memory() {
+        run_session(&[], |client| {
+            assert!(
+                client
+                    .request("qSupported:swbreak+")
+                    .contains("qXfer:features:read+")
+            );
+            assert!(
+                client
+                    .request("qXfer:features:read:target.xml:0,fff")
+                    .starts_with("l<?xml")
+            );
+            assert_eq!(client.request("?"), "S05");
+            assert_eq!(client.request("g"), "000000000000000000000000");
+
+            assert_eq!(client.request("P1=3412"), "OK");
+            assert_eq!(client.request("p1"), "3412");
+
+            assert_eq!(client.request("Mc000,2:abcd"), "OK");
+            assert_eq!(client.request("mc000,3"), "abcd00");
+        });
+    }
+
+    #[test]
+    fn ignores_unknown_packets() {
+        run_session(&[], |client| {
+            assert_eq!(client.request("vMustReplyEmpty"), "");
+            assert_eq!(client.request("\u{e9}1"), "");
+            assert_eq!(client.request("?"), "S05");
+        });
+    }
+
+    #[test]
+    fn stops_on_breakpoints_and_steps() {
+        run_session(&[], |client| {
+            assert_eq!(client.request("Z0,10,1"), "OK");
+            assert_eq!(client.request("c"), "S05");
+            assert_eq!(&client.request("g")[20..], "1000");
+
+            assert_eq!(client.request("z0,10,1"), "OK");
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(&client.request("g")[20..], "1100");
+        });
+    }
+
+    #[test]
+    fn reports_watchpoints() {
+        // LD A, $42; LD ($C000), A
+        run_session(&[0x3E, 0x42, 0xEA, 0x00, 0xC0], |client| {
+            assert_eq!(client.request("Z2,c000,1"), "OK");
+            assert_eq!(client.request("c"), "T05watch:c000;");
+            assert_eq!(client.request("mc000,1"), "42");
+        });
+    }
+
+    #[test]
+    fn interrupts_while_running() {
+        // JR -2
+        run_session(&[0x18, 0xFE], |client| {
+            client.send("c");
+            thread::sleep(Duration::from_millis(20));
+            client.writer.write_all(&[0x03]).unwrap();
+            assert_eq!(client.receive(), "S02");
+        });
+    }
+}
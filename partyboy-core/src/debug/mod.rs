@@ -0,0 +1,362 @@
+#![allow(dead_code)]
+
+pub mod breakpoint;
+pub mod call_stack;
+pub mod gdb;
+#[cfg(test)]
+mod test_util;
+
+pub use crate::bus::CgbCompatibility;
+use crate::{
+    GameBoy,
+    disasm::{self, DecodedInstruction},
+    ppu::rgb::Rgb,
+};
+
+use self::breakpoint::{BreakReason, Breakpoints, CpuRegister};
+use self::call_stack::CallFrame;
+
+/// Cycles in one frame, the most `step_instruction` will run for
+const STEP_CYCLE_LIMIT: usize = 70_224;
+
+#[derive(Default)]
+pub struct GBDebugInfo {
+    pub fps: Option<f64>,
+    pub compatibility_mode: CgbCompatibility,
+    pub palette: GBPalleteData,
+    pub tiles: GBTileData,
+    pub ppu_info: GBPpuInfo,
+    pub map_data: GBMapInfo,
+    pub tile_attr_data: GBTileAttributeInfo,
+    pub registers: GBRegisters,
+    /// Active calls, outermost first
+    pub call_stack: Vec<CallFrame>,
+    /// Instructions surrounding the program counter
+    pub disassembly: Vec<DecodedInstruction>,
+}
+
+#[derive(Default)]
+pub struct GBPalleteData {
+    pub bg_palette_dmg_map: [usize; 4],
+    pub sprite_palette_dmg_map: [[usize; 4]; 2],
+    pub bg: [[Rgb; 4]; 8],
+    pub sprite: [[Rgb; 4]; 8],
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct GBTile {
+    pub data: [[u8; 8]; 8],
+}
+
+pub struct GBTileData {
+    pub bank_0: Vec<GBTile>,
+    pub bank_1: Vec<GBTile>,
+}
+
+impl Default for GBTileData {
+    fn default() -> Self {
+        let default_bank = [GBTile::default(); 384];
+        Self {
+            bank_0: default_bank.to_vec(),
+            bank_1: default_bank.to_vec(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GBPpuInfo {
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct GBRegisters {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl GBRegisters {
+    pub fn get(&self, reg: CpuRegister) -> u16 {
+        match reg {
+            CpuRegister::A => self.af >> 8,
+            CpuRegister::F => self.af & 0xFF,
+            CpuRegister::B => self.bc >> 8,
+            CpuRegister::C => self.bc & 0xFF,
+            CpuRegister::D => self.de >> 8,
+            CpuRegister::E => self.de & 0xFF,
+            CpuRegister::H => self.hl >> 8,
+            CpuRegister::L => self.hl & 0xFF,
+            CpuRegister::AF => self.af,
+            CpuRegister::BC => self.bc,
+            CpuRegister::DE => self.de,
+            CpuRegister::HL => self.hl,
+            CpuRegister::SP => self.sp,
+            CpuRegister::PC => self.pc,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub struct GBMapInfo {
+    pub bg_map_9800: [u8; 32 * 32],
+    pub bg_map_9C00: [u8; 32 * 32],
+}
+
+impl Default for GBMapInfo {
+    fn default() -> Self {
+        let default_map = [0; 32 * 32];
+        Self {
+            bg_map_9800: default_map,
+            bg_map_9C00: default_map,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+pub struct GBTileAttributeInfo {
+    pub tile_attr_9800: [u8; 32 * 32],
+    pub tile_attr_9C00: [u8; 32 * 32],
+}
+
+impl Default for GBTileAttributeInfo {
+    fn default() -> Self {
+        let default_attr = [0; 32 * 32];
+        Self {
+            tile_attr_9800: default_attr,
+            tile_attr_9C00: default_attr,
+        }
+    }
+}
+
+impl GameBoy {
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.bus.breakpoints
+    }
+
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        &mut self.bus.breakpoints
+    }
+
+    /// Returns why the emulator stopped, if a breakpoint was hit during the last `tick`.
+    /// Frontends should check this after every tick and stop running when it's `Some`
+    pub fn take_break_reason(&mut self) -> Option<BreakReason> {
+        self.bus.breakpoints.take_hit()
+    }
+
+    pub fn registers(&self) -> GBRegisters {
+        self.cpu.registers()
+    }
+
+    /// Overwrites the cpu registers. Only call this between instructions, e.g. while
+    /// stopped on a breakpoint, or the instruction in flight will see a mix of both
+    pub fn set_registers(&mut self, registers: GBRegisters) {
+        self.cpu.set_registers(&registers);
+    }
+
+    /// Reads memory without triggering watchpoints or any other side effects
+    pub fn peek_u8(&self, addr: u16) -> u8 {
+        self.bus.peek_u8(addr)
+    }
+
+    /// Writes to memory the same way the cpu would, so writes to ROM go to the MBC
+    /// and writes to IO registers take effect. Watchpoints are not triggered
+    pub fn poke_u8(&mut self, addr: u16, val: u8) {
+        let pending = self.bus.breakpoints.take_hit();
+        self.bus.write_u8(addr, val);
+        self.bus.breakpoints.set_hit(pending);
+    }
+
+    /// Runs until the next instruction (or interrupt dispatch) has finished.
+    ///
+    /// Returns early if a breakpoint is hit. If the cpu is halted or stopped, gives up
+    /// after a frame's worth of cycles so callers never hang
+    pub fn step_instruction(&mut self) -> Option<BreakReason> {
+        let mut started = false;
+
+        for _ in 0..STEP_CYCLE_LIMIT {
+            self.tick();
+
+            if let Some(reason) = self.take_break_reason() {
+                return Some(reason);
+            }
+
+            if self.cpu.is_processing_instruction() {
+                started = true;
+            } else if started {
+                break;
+            }
+        }
+
+        None
+    }
+
+    /// Calls that haven't returned yet, outermost first
+    pub fn call_stack(&self) -> &[CallFrame] {
+        self.cpu.call_stack.frames()
+    }
+
+    /// Disassembles `count` instructions starting at `addr`, as the memory is currently mapped
+    pub fn disassemble(&self, addr: u16, count: usize) -> Vec<DecodedInstruction> {
+        let read = |addr| self.bus.peek_u8(addr);
+        let mut instructions = disasm::decode_range(&self.instruction_cache, 0, addr, count, &read);
+        self.fill_mapped_banks(&mut instructions);
+        instructions
+    }
+
+    /// Disassembles `count` instructions starting at `addr`, with ROM `bank` mapped into
+    /// 0x4000 - 0x7FFF instead of the bank the MBC currently has selected
+    pub fn disassemble_bank(
+        &self,
+        bank: usize,
+        addr: u16,
+        count: usize,
+    ) -> Vec<DecodedInstruction> {
+        let read = |addr| self.bus.peek_u8_in_bank(bank, addr);
+        let mut instructions =
+            disasm::decode_range(&self.instruction_cache, bank, addr, count, &read);
+        instructions
+            .iter_mut()
+            .filter(|instruction| !(0x4000..=0x7FFF).contains(&instruction.addr))
+            .for_each(|instruction| instruction.bank = self.bus.mapped_bank(instruction.addr));
+        instructions
+    }
+
+    /// Disassembles `before` instructions leading up to the program counter, the next
+    /// instruction to execute and `after` instructions following it
+    pub fn disassemble_around_pc(&self, before: usize, after: usize) -> Vec<DecodedInstruction> {
+        let read = |addr| self.bus.peek_u8(addr);
+        let mut instructions = disasm::decode_around(
+            &self.instruction_cache,
+            0,
+            self.cpu.pc,
+            before,
+            after,
+            &read,
+        );
+        self.fill_mapped_banks(&mut instructions);
+        instructions
+    }
+
+    fn fill_mapped_banks(&self, instructions: &mut [DecodedInstruction]) {
+        for instruction in instructions {
+            instruction.bank = self.bus.mapped_bank(instruction.addr);
+        }
+    }
+
+    pub fn debug_info(&self) -> GBDebugInfo {
+        GBDebugInfo {
+            fps: None,
+            compatibility_mode: self.bus().console_compatibility_mode,
+            palette: self.color_palettes(),
+            tiles: self.tile_data(),
+            ppu_info: self.ppu_general(),
+            map_data: self.map_data(),
+            tile_attr_data: self.tile_attribute_data(),
+            registers: self.registers(),
+            call_stack: self.call_stack().to_vec(),
+            disassembly: self.disassemble_around_pc(16, 32),
+        }
+    }
+
+    fn color_palettes(&self) -> GBPalleteData {
+        let ppu = &self.bus().ppu;
+        GBPalleteData {
+            bg: ppu.bg_color_palette(),
+            sprite: ppu.sprite_color_palette(),
+            bg_palette_dmg_map: ppu.bg_palette(),
+            sprite_palette_dmg_map: ppu.sprite_palette(),
+        }
+    }
+
+    // Tile data is stored in 0x8000 - 0x97FF
+    // if in CGB mode then vram bank [1] also has
+    // tiles in that location
+
+    // in vram bank [0], 0x9800 - 0x9BFF and 0x9C00 - 0x9FFF
+    // contain tile maps
+
+    // in vram bank [1], 0x9800 - 0x9FFF contain attributes for the
+    // to apply to the corresponding tile map at vram bank [0]
+
+    fn get_tile_data_from_bank(&self, bank: usize) -> Vec<GBTile> {
+        let ppu = &self.bus().ppu;
+        let mut bank_tiles = Vec::new();
+
+        let mut index = 0x8000;
+        while index < 0x9800 {
+            let mut tile_data_index = 0;
+            let mut tile_row_index = 0;
+            let mut tile = GBTile::default();
+
+            while tile_data_index < 16 {
+                let b1 = ppu.gpu_vram[bank][index + tile_data_index - 0x8000];
+                let b2 = ppu.gpu_vram[bank][index + tile_data_index + 1 - 0x8000];
+
+                let mut tile_row = [0u8; 8];
+                for i in (0..8usize).rev() {
+                    let shift_i = i as u8;
+                    tile_row[7 - i] = ((b1 & (1 << shift_i)) >> shift_i)
+                        | ((b2 & (1 << shift_i)) >> shift_i) << 1;
+                }
+
+                tile.data[tile_row_index] = tile_row;
+
+                tile_data_index += 2;
+                tile_row_index += 1;
+            }
+
+            bank_tiles.push(tile);
+            index += 16;
+        }
+
+        bank_tiles
+    }
+
+    fn tile_data(&self) -> GBTileData {
+        GBTileData {
+            bank_0: self.get_tile_data_from_bank(0),
+            bank_1: self.get_tile_data_from_bank(1),
+        }
+    }
+
+    fn ppu_general(&self) -> GBPpuInfo {
+        let ppu = &self.bus().ppu;
+        GBPpuInfo {
+            lcdc: ppu.lcdc,
+            stat: ppu.stat,
+            scx: ppu.scx,
+            scy: ppu.scy,
+        }
+    }
+
+    fn get_data_at_addr(&self, addr: usize, bank: usize) -> [u8; 32 * 32] {
+        let ppu = &self.bus().ppu;
+        let mut map = [0; 32 * 32];
+
+        map.iter_mut()
+            .enumerate()
+            .for_each(|(i, element)| *element = ppu.gpu_vram[bank][addr + i - 0x8000]);
+
+        map
+    }
+
+    fn map_data(&self) -> GBMapInfo {
+        GBMapInfo {
+            bg_map_9800: self.get_data_at_addr(0x9800, 0),
+            bg_map_9C00: self.get_data_at_addr(0x9C00, 0),
+        }
+    }
+
+    fn tile_attribute_data(&self) -> GBTileAttributeInfo {
+        GBTileAttributeInfo {
+            tile_attr_9800: self.get_data_at_addr(0x9800, 1),
+            tile_attr_9C00: self.get_data_at_addr(0x9C00, 1),
+        }
+    }
+}
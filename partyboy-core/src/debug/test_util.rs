@@ -0,0 +1,11 @@
+use crate::{GameBoy, bus::Bus, cartridge::rom::create_test_rom};
+
+/// A Game Boy that runs `program` from the start of the bios, with an empty rom inserted
+pub fn gb_with_program(program: &[u8]) -> GameBoy {
+    let mut bios = [0; 0x900];
+    bios[..program.len()].copy_from_slice(program);
+
+    let mut gb = GameBoy::new(None, bios, Bus::get_handle_blargg_output());
+    gb.bus.cartridge = Some(create_test_rom());
+    gb
+}
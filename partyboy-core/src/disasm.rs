@@ -0,0 +1,374 @@
+//! Sharp SM83 disassembler.
+//!
+//! Decoding is driven by the same opcode tables the CPU executes from, so the mnemonic,
+//! operand encoding and timing of an instruction can't drift from how it's emulated.
+
+use std::fmt::Display;
+
+use crate::cpu::InstructionCache;
+use crate::cpu::InstructionOpcode;
+pub use crate::cpu::{InstructionInfo, Operand};
+
+/// Longest encoding of any instruction, e.g. `LD BC,$1234`
+const MAX_INSTRUCTION_LEN: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct DecodedInstruction {
+    pub bank: usize,
+    pub addr: u16,
+    pub info: InstructionInfo,
+    bytes: [u8; MAX_INSTRUCTION_LEN],
+    size: u8,
+}
+
+impl DecodedInstruction {
+    /// The raw bytes of the instruction, including the 0xCB prefix
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.size as usize]
+    }
+
+    /// Number of bytes the instruction occupies
+    pub fn size(&self) -> u16 {
+        self.size as u16
+    }
+
+    pub fn next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.size())
+    }
+
+    fn imm8(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    fn imm16(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
+
+    /// The address an operand refers to, if any. Jump and call targets, `RST` vectors
+    /// and direct memory operands all count
+    pub fn target(&self) -> Option<u16> {
+        self.info.operands.iter().find_map(|op| match op {
+            Operand::Addr16 | Operand::Mem16 => Some(self.imm16()),
+            Operand::HighMem8 => Some(0xFF00 | self.imm8() as u16),
+            Operand::Rel8 => Some(self.next_addr().wrapping_add(self.imm8() as i8 as u16)),
+            Operand::Vector(addr) => Some(*addr),
+            _ => None,
+        })
+    }
+
+    /// Formats the instruction, replacing any address operand with the label returned by `symbol`
+    pub fn format_with_symbols<'a>(&self, symbol: impl Fn(u16) -> Option<&'a str>) -> String {
+        let operands = self
+            .info
+            .operands
+            .iter()
+            .filter(|op| **op != Operand::None)
+            .map(|op| self.format_operand(op, &symbol))
+            .collect::<Vec<_>>();
+
+        if operands.is_empty() {
+            self.info.mnemonic.to_string()
+        } else {
+            format!("{} {}", self.info.mnemonic, operands.join(", "))
+        }
+    }
+
+    fn format_operand<'a>(&self, op: &Operand, symbol: &impl Fn(u16) -> Option<&'a str>) -> String {
+        let signed = |v: i8| {
+            if v < 0 {
+                format!("-${:02X}", v.unsigned_abs())
+            } else {
+                format!("+${v:02X}")
+            }
+        };
+
+        match *op {
+            Operand::None => String::new(),
+            Operand::Reg(name) | Operand::Cond(name) => name.to_string(),
+            Operand::RegMem(name) => format!("({name})"),
+            Operand::HighMemC => "($FF00+C)".to_string(),
+            Operand::Imm8 => format!("${:02X}", self.imm8()),
+            Operand::SignedImm8 => signed(self.imm8() as i8),
+            Operand::Imm16 => format!("${:04X}", self.imm16()),
+            Operand::SpRel8 => format!("SP{}", signed(self.imm8() as i8)),
+            Operand::Bit(n) => n.to_string(),
+            Operand::Byte(b) => format!("${b:02X}"),
+
+            Operand::Addr16 | Operand::Rel8 => {
+                // unwrap: both operand kinds always have a target
+                let target = self.target().unwrap();
+                match symbol(target) {
+                    Some(label) => label.to_string(),
+                    None => format!("${target:04X}"),
+                }
+            }
+            Operand::Mem16 | Operand::HighMem8 => {
+                let target = self.target().unwrap();
+                match symbol(target) {
+                    Some(label) => format!("({label})"),
+                    None if *op == Operand::HighMem8 => format!("($FF00+${:02X})", self.imm8()),
+                    None => format!("(${target:04X})"),
+                }
+            }
+            Operand::Vector(addr) => match symbol(addr) {
+                Some(label) => label.to_string(),
+                None => format!("${addr:02X}"),
+            },
+        }
+    }
+}
+
+impl Display for DecodedInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format_with_symbols(|_| None))
+    }
+}
+
+/// Standalone disassembler, for decoding code that isn't loaded into a running `GameBoy`
+pub struct Disassembler {
+    instruction_cache: InstructionCache,
+}
+
+impl Default for Disassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Disassembler {
+    pub fn new() -> Self {
+        Self {
+            instruction_cache: InstructionCache::new(),
+        }
+    }
+
+    /// Decodes the instruction at `addr`. `bank` is only recorded on the result,
+    /// `read` is expected to already read from the right bank
+    pub fn decode(&self, bank: usize, addr: u16, read: impl Fn(u16) -> u8) -> DecodedInstruction {
+        decode(&self.instruction_cache, bank, addr, &read)
+    }
+
+    /// Decodes up to `count` consecutive instructions starting at `addr`
+    pub fn decode_range(
+        &self,
+        bank: usize,
+        addr: u16,
+        count: usize,
+        read: impl Fn(u16) -> u8,
+    ) -> Vec<DecodedInstruction> {
+        decode_range(&self.instruction_cache, bank, addr, count, &read)
+    }
+
+    /// Decodes `before` instructions leading up to `addr`, the instruction at `addr`,
+    /// and `after` instructions following it
+    pub fn decode_around(
+        &self,
+        bank: usize,
+        addr: u16,
+        before: usize,
+        after: usize,
+        read: impl Fn(u16) -> u8,
+    ) -> Vec<DecodedInstruction> {
+        decode_around(&self.instruction_cache, bank, addr, before, after, &read)
+    }
+}
+
+pub(crate) fn decode(
+    instruction_cache: &InstructionCache,
+    bank: usize,
+    addr: u16,
+    read: &impl Fn(u16) -> u8,
+) -> DecodedInstruction {
+    let mut bytes = [0; MAX_INSTRUCTION_LEN];
+    bytes[0] = read(addr);
+
+    let (info, size) = if bytes[0] == 0xCB {
+        bytes[1] = read(addr.wrapping_add(1));
+        (
+            instruction_cache.info(InstructionOpcode::Prefixed(bytes[1])),
+            2,
+        )
+    } else {
+        let info = instruction_cache.info(InstructionOpcode::Unprefixed(bytes[0]));
+        let operand_len: u16 = info.operands.iter().map(Operand::encoded_len).sum();
+        for i in 1..=operand_len {
+            bytes[i as usize] = read(addr.wrapping_add(i));
+        }
+        (info, 1 + operand_len as u8)
+    };
+
+    DecodedInstruction {
+        bank,
+        addr,
+        info: *info,
+        bytes,
+        size,
+    }
+}
+
+pub(crate) fn decode_range(
+    instruction_cache: &InstructionCache,
+    bank: usize,
+    addr: u16,
+    count: usize,
+    read: &impl Fn(u16) -> u8,
+) -> Vec<DecodedInstruction> {
+    let mut instructions = Vec::new();
+    let mut addr = addr as u32;
+
+    while instructions.len() < count && addr <= 0xFFFF {
+        let instruction = decode(instruction_cache, bank, addr as u16, read);
+        addr += instruction.size() as u32;
+        instructions.push(instruction);
+    }
+
+    instructions
+}
+
+pub(crate) fn decode_around(
+    instruction_cache: &InstructionCache,
+    bank: usize,
+    addr: u16,
+    before: usize,
+    after: usize,
+    read: &impl Fn(u16) -> u8,
+) -> Vec<DecodedInstruction> {
+    // Instructions are variable length so there's no way to walk backwards.
+    // Instead decode forwards from progressively closer start points and take the
+    // first run that lands exactly on `addr`, which is the one most likely to be in sync
+    let max_lookback = (before * MAX_INSTRUCTION_LEN).min(addr as usize) as u16;
+    let mut leading = Vec::new();
+
+    for lookback in (1..=max_lookback).rev() {
+        let mut candidate = Vec::new();
+        let mut cursor = (addr - lookback) as u32;
+        while cursor < addr as u32 {
+            let instruction = decode(instruction_cache, bank, cursor as u16, read);
+            cursor += instruction.size() as u32;
+            candidate.push(instruction);
+        }
+
+        if cursor == addr as u32 {
+            leading = candidate;
+            break;
+        }
+    }
+
+    let skip = leading.len().saturating_sub(before);
+    leading.drain(..skip);
+    leading.extend(decode_range(instruction_cache, bank, addr, after + 1, read));
+    leading
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disassemble(program: &[u8]) -> Vec<String> {
+        let read = |addr: u16| program.get(addr as usize).copied().unwrap_or(0);
+        Disassembler::new()
+            .decode_range(0, 0, program.len(), read)
+            .into_iter()
+            .take_while(|instruction| (instruction.addr as usize) < program.len())
+            .map(|instruction| instruction.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn decodes_operands() {
+        let program = [
+            0x00, // NOP
+            0x01, 0x34, 0x12, // LD BC, $1234
+            0x3E, 0x42, // LD A, $42
+            0x77, // LD (HL), A
+            0x2A, // LD A, (HL+)
+            0xE0, 0x40, // LDH ($FF00+$40), A
+            0xE2, // LD ($FF00+C), A
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0xF8, 0xFE, // LD HL, SP-$02
+            0xE8, 0x02, // ADD SP, +$02
+            0xCB, 0x7C, // BIT 7, H
+            0xCB, 0x36, // SWAP (HL)
+            0x10, 0x00, // STOP $00
+            0xD3, // unused
+        ];
+
+        assert_eq!(
+            disassemble(&program),
+            [
+                "NOP",
+                "LD BC, $1234",
+                "LD A, $42",
+                "LD (HL), A",
+                "LD A, (HL+)",
+                "LDH ($FF00+$40), A",
+                "LD ($FF00+C), A",
+                "LD ($C000), A",
+                "LD HL, SP-$02",
+                "ADD SP, +$02",
+                "BIT 7, H",
+                "SWAP (HL)",
+                "STOP $00",
+                "DB $D3",
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_branches_with_symbols() {
+        let program = [
+            0x20, 0xFE, // JR NZ, $0000
+            0xCD, 0x50, 0x01, // CALL $0150
+            0xFF, // RST $38
+            0xF0, 0x44, // LDH A, (rLY)
+        ];
+        let read = |addr: u16| program.get(addr as usize).copied().unwrap_or(0);
+        let symbol = |addr| match addr {
+            0x0000 => Some("Loop"),
+            0x0150 => Some("Main"),
+            0xFF44 => Some("rLY"),
+            _ => None,
+        };
+
+        let lines = Disassembler::new()
+            .decode_range(0, 0, 4, read)
+            .iter()
+            .map(|instruction| instruction.format_with_symbols(symbol))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            ["JR NZ, Loop", "CALL Main", "RST $38", "LDH A, (rLY)"]
+        );
+    }
+
+    #[test]
+    fn reports_size_and_cycles() {
+        let disassembler = Disassembler::new();
+        let program = [0xC4, 0x00, 0x10, 0xCB, 0x46];
+        let read = |addr: u16| program.get(addr as usize).copied().unwrap_or(0);
+
+        let call = disassembler.decode(0, 0, read);
+        assert_eq!(call.size(), 3);
+        assert_eq!(call.bytes(), [0xC4, 0x00, 0x10]);
+        assert_eq!(call.info.cycles, 12);
+        assert_eq!(call.info.branch_cycles, Some(24));
+
+        let bit = disassembler.decode(0, call.next_addr(), read);
+        assert_eq!(bit.bytes()[0], 0xCB);
+        assert_eq!(bit.size(), 2);
+        assert_eq!(bit.info.cycles, 12);
+    }
+
+    #[test]
+    fn decodes_around_address() {
+        // a 3 byte instruction followed by single byte ones
+        let program = [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let read = |addr: u16| program.get(addr as usize).copied().unwrap_or(0);
+
+        let lines = Disassembler::new().decode_around(0, 5, 2, 1, read);
+        let addrs = lines.iter().map(|i| i.addr).collect::<Vec<_>>();
+
+        assert_eq!(addrs, [3, 4, 5, 6]);
+    }
+}
@@ -0,0 +1,227 @@
+//! The CGB infrared port (RP, 0xFF56).
+//!
+//! The LED and the sensor are sampled once per tick. Two emulators can be pointed at
+//! each other by calling [`crate::GameBoy::link_infrared`] after ticking both, or the
+//! light can come from an [`InfraredSource`], e.g. a [`RecordedLight`] file.
+
+use thiserror::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const LED_ON: u8 = 0b0000_0001;
+const NO_LIGHT: u8 = 0b0000_0010;
+const READ_ENABLE: u8 = 0b1100_0000;
+
+/// Whatever is in front of the sensor
+pub trait InfraredSource: Send {
+    /// Called every tick with the state of our own LED, returns whether light
+    /// reaches the sensor
+    fn tick(&mut self, led_on: bool) -> bool;
+}
+
+/// No other device nearby, the sensor never sees any light
+pub struct NothingNearby;
+
+impl InfraredSource for NothingNearby {
+    fn tick(&mut self, _led_on: bool) -> bool {
+        false
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum InfraredRecordingError {
+    #[error("Invalid infrared recording on line {line}: {contents:?}")]
+    InvalidLine { line: usize, contents: String },
+}
+
+/// Replays light changes from a recording.
+///
+/// Each line is `tick on|off`, with the tick count in decimal and the lines in order.
+/// Blank lines and `;` comments are ignored, so an empty file is a source where
+/// nothing is ever nearby
+#[derive(Default, Clone, Debug)]
+pub struct RecordedLight {
+    changes: Vec<(u64, bool)>,
+    next_change: usize,
+    ticks: u64,
+    light: bool,
+}
+
+impl RecordedLight {
+    pub fn parse(contents: &str) -> Result<Self, InfraredRecordingError> {
+        let mut changes = Vec::new();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || InfraredRecordingError::InvalidLine {
+                line: index + 1,
+                contents: line.to_owned(),
+            };
+
+            let (tick, state) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let tick = tick.parse::<u64>().map_err(|_| invalid())?;
+            let light = match state.trim() {
+                "on" => true,
+                "off" => false,
+                _ => return Err(invalid()),
+            };
+
+            changes.push((tick, light));
+        }
+
+        Ok(Self {
+            changes,
+            ..Default::default()
+        })
+    }
+}
+
+impl InfraredSource for RecordedLight {
+    fn tick(&mut self, _led_on: bool) -> bool {
+        while let Some(&(tick, light)) = self.changes.get(self.next_change) {
+            if tick > self.ticks {
+                break;
+            }
+
+            self.light = light;
+            self.next_change += 1;
+        }
+
+        self.ticks += 1;
+        self.light
+    }
+}
+
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct InfraredPort {
+    rp: u8,
+    light_received: bool,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<Box<dyn InfraredSource>>,
+}
+
+impl InfraredPort {
+    pub fn read(&self) -> u8 {
+        let sensor = match self.rp & READ_ENABLE == READ_ENABLE && self.light_received {
+            true => 0,
+            false => NO_LIGHT,
+        };
+
+        0b0011_1100 | (self.rp & (READ_ENABLE | LED_ON)) | sensor
+    }
+
+    pub fn write(&mut self, val: u8) {
+        self.rp = val & (READ_ENABLE | LED_ON);
+    }
+
+    pub fn led_on(&self) -> bool {
+        self.rp & LED_ON != 0
+    }
+
+    pub fn set_light_received(&mut self, light: bool) {
+        self.light_received = light;
+    }
+
+    pub fn set_source(&mut self, source: Option<Box<dyn InfraredSource>>) {
+        self.source = source;
+    }
+
+    pub fn take_source(&mut self) -> Option<Box<dyn InfraredSource>> {
+        self.source.take()
+    }
+
+    pub fn tick(&mut self) {
+        let led_on = self.led_on();
+        if let Some(source) = &mut self.source {
+            self.light_received = source.tick(led_on);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sensor_only_reads_when_enabled() {
+        let mut port = InfraredPort::default();
+        port.set_light_received(true);
+
+        port.write(0x00);
+        assert_eq!(port.read(), 0b0011_1110);
+
+        port.write(0xC1);
+        assert_eq!(port.read(), 0b1111_1101);
+        assert!(port.led_on());
+
+        port.set_light_received(false);
+        assert_eq!(port.read(), 0b1111_1111);
+    }
+
+    #[test]
+    fn replays_recording() {
+        let recording = "; light from tick 2 to 4\n2 on\n4 off\n";
+        let mut port = InfraredPort::default();
+        port.set_source(Some(Box::new(RecordedLight::parse(recording).unwrap())));
+        port.write(0xC0);
+
+        let seen = (0..6)
+            .map(|_| {
+                port.tick();
+                port.read() & NO_LIGHT == 0
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(seen, [false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn linked_gameboys_see_each_other() {
+        let build = || {
+            crate::GameBoy::builder()
+                .rom(vec![0; 0x8000])
+                .build()
+                .unwrap()
+        };
+        let (mut a, mut b) = (build(), build());
+        a.bus.write_u8(0xFF4C, 0xC0);
+        b.bus.write_u8(0xFF4C, 0xC0);
+
+        a.bus.write_u8(0xFF56, 0xC1);
+        b.bus.write_u8(0xFF56, 0xC0);
+        a.tick();
+        b.tick();
+        a.link_infrared(&mut b);
+
+        assert_eq!(b.bus.read_u8(0xFF56) & NO_LIGHT, 0);
+        assert_eq!(a.bus.read_u8(0xFF56) & NO_LIGHT, NO_LIGHT);
+    }
+
+    #[test]
+    fn dmg_has_no_port() {
+        let mut gb = crate::GameBoy::builder()
+            .rom(vec![0; 0x8000])
+            .build()
+            .unwrap();
+        gb.bus.write_u8(0xFF4C, 0x04);
+
+        gb.bus.write_u8(0xFF56, 0xC1);
+        assert!(!gb.infrared_led_on());
+        assert_eq!(gb.bus.read_u8(0xFF56), 0xFF);
+    }
+
+    #[test]
+    fn rejects_invalid_recording() {
+        let err = RecordedLight::parse("10 on\nbright\n").unwrap_err();
+        assert!(matches!(
+            err,
+            InfraredRecordingError::InvalidLine { line: 2, .. }
+        ));
+    }
+}
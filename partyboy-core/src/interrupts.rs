@@ -15,7 +15,7 @@ pub(crate) struct Interrupts {
     pub halt_interrupt_pending: bool,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum InterruptFlag {
     VBlank = 0b00000001,
     Stat = 0b00000010,
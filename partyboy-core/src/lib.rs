@@ -6,14 +6,22 @@ mod common;
 mod cpu;
 #[cfg(feature = "debug_info")]
 pub mod debug;
+pub mod disasm;
 mod dma;
+pub mod infrared;
 pub mod input;
 mod interrupts;
+pub mod patch;
 pub mod ppu;
+pub mod serial;
+pub mod sgb;
+pub mod symbols;
 mod timer;
+pub mod trace;
 
 use apu::Sample;
 use cartridge::Cartridge;
+pub use cartridge::camera;
 #[cfg(not(feature = "web"))]
 use ppu::rgb::Rgb;
 #[cfg(feature = "serde")]
@@ -25,13 +33,22 @@ use self::builder::GameBoyBuilder;
 use self::{
     builder::SerialWriteHandler,
     bus::Bus,
+    camera::CameraSource,
     cpu::{Cpu, InstructionCache},
     dma::{
         hdma::{DmaType, Hdma, HdmaController},
         oam::OamDma,
     },
+    infrared::InfraredSource,
     input::Keycode,
     interrupts::Interrupts,
+    ppu::{
+        frame_blend::{FrameBlendMode, FrameBlender},
+        pixel_format::PixelFormat,
+        rgb::ColorCorrection,
+    },
+    serial::SerialDevice,
+    trace::Tracer,
 };
 
 /// Number of cycles per second the gameboy does in single speed mode.
@@ -39,6 +56,12 @@ use self::{
 /// as the `tick` function will internally tick twice
 pub const SPEED: u64 = 4_194_304;
 
+/// Number of cycles between two frames while the lcd is on
+pub const CYCLES_PER_FRAME: u64 = 70_224;
+
+/// Number of audio samples `tick` returns per second of emulated time
+pub const SAMPLE_RATE: u64 = SPEED / apu::TICKS_PER_SAMPLE as u64;
+
 #[cfg_attr(feature = "web", wasm_bindgen)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GameBoy {
@@ -47,23 +70,23 @@ pub struct GameBoy {
     cpu: Cpu,
     bus: Bus,
     hdma_controller: HdmaController,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    tracer: Option<Tracer>,
 }
 
 #[cfg_attr(feature = "web", wasm_bindgen)]
 impl GameBoy {
     fn new(
-        rom: Option<Vec<u8>>,
-        ram: Option<Vec<u8>>,
+        cartridge: Option<Cartridge>,
         bios: [u8; 0x900],
         serial_write_handler: SerialWriteHandler,
     ) -> Self {
-        let cartridge = rom.map(|rom| Cartridge::new(rom, ram));
-
         Self {
             instruction_cache: InstructionCache::new(),
             cpu: Cpu::new(),
             bus: Bus::new(cartridge, serial_write_handler, bios),
             hdma_controller: HdmaController::default(),
+            tracer: None,
         }
     }
 
@@ -74,14 +97,35 @@ impl GameBoy {
     fn tick_cpu_related(&mut self) {
         Interrupts::tick(&mut self.bus.interrupts, &mut self.cpu);
         self.cpu.tick(&mut self.bus, &mut self.instruction_cache);
+        self.trace_fetched_instruction();
 
         if self.bus.cpu_speed_controller.is_double_speed() {
             self.cpu.tick(&mut self.bus, &mut self.instruction_cache);
+            self.trace_fetched_instruction();
+        }
+    }
+
+    fn trace_fetched_instruction(&mut self) {
+        let Some(pc) = self.cpu.take_fetched_pc() else {
+            return;
+        };
+        let Some(tracer) = &mut self.tracer else {
+            return;
+        };
+
+        let regs = self.cpu.trace_registers();
+        if let Err(err) = tracer.trace(&regs, pc, &self.bus, &self.instruction_cache) {
+            log::error!("Unable to write trace, disabling tracing: {}", err);
+            self.tracer = None;
         }
     }
 
     #[cfg(not(feature = "web"))]
     pub fn tick(&mut self) -> Option<(Sample, Sample)> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.tick();
+        }
+
         if self.cpu.stopped() {
             return self.bus.apu.tick_sample_only();
         }
@@ -107,10 +151,17 @@ impl GameBoy {
 
         OamDma::dma_tick(&mut self.bus);
         self.bus.timer.tick(&mut self.bus.interrupts);
+        self.bus.serial.tick(&mut self.bus.interrupts);
 
         if self.bus.cpu_speed_controller.is_double_speed() {
             OamDma::dma_tick(&mut self.bus);
             self.bus.timer.tick(&mut self.bus.interrupts);
+            self.bus.serial.tick(&mut self.bus.interrupts);
+        }
+
+        self.bus.infrared.tick();
+        if let Some(cartridge) = &mut self.bus.cartridge {
+            cartridge.tick();
         }
 
         self.bus.apu.tick(
@@ -122,6 +173,10 @@ impl GameBoy {
     // FIXME: Avoid having two different tick functions if possible
     #[cfg(feature = "web")]
     pub fn tick(&mut self) -> Option<Box<[f32]>> {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.tick();
+        }
+
         if self.cpu.stopped() {
             return self
                 .bus
@@ -152,10 +207,17 @@ impl GameBoy {
 
         OamDma::dma_tick(&mut self.bus);
         self.bus.timer.tick(&mut self.bus.interrupts);
+        self.bus.serial.tick(&mut self.bus.interrupts);
 
         if self.bus.cpu_speed_controller.is_double_speed() {
             OamDma::dma_tick(&mut self.bus);
             self.bus.timer.tick(&mut self.bus.interrupts);
+            self.bus.serial.tick(&mut self.bus.interrupts);
+        }
+
+        self.bus.infrared.tick();
+        if let Some(cartridge) = &mut self.bus.cartridge {
+            cartridge.tick();
         }
 
         self.bus
@@ -174,19 +236,87 @@ impl GameBoy {
 
     #[cfg(feature = "web")]
     pub fn get_frame_buffer(&self) -> Vec<u8> {
+        let mut frame = vec![0; 160 * 144 * PixelFormat::Rgb888.bytes_per_pixel()];
+        self.render_frame_buffer(PixelFormat::Rgb888, &mut frame);
+        frame
+    }
+
+    /// Writes the frame buffer into `out` in `format`, which must be exactly
+    /// `160 * 144 * format.bytes_per_pixel()` bytes
+    pub fn render_frame_buffer(&self, format: PixelFormat, out: &mut [u8]) {
+        self.bus.ppu.render_frame_buffer(format, out);
+    }
+
+    /// Whether the cart is running in Super Game Boy mode
+    pub fn is_sgb(&self) -> bool {
+        self.bus.sgb.is_some()
+    }
+
+    /// The 256x224 Super Game Boy screen, with the border drawn around the game.
+    /// `None` when not running in SGB mode
+    #[cfg(not(feature = "web"))]
+    pub fn get_sgb_frame_buffer(&self) -> Option<&[Rgb]> {
+        self.bus.sgb.as_ref().map(|sgb| sgb.output())
+    }
+
+    #[cfg(feature = "web")]
+    pub fn get_sgb_frame_buffer(&self) -> Option<Vec<u8>> {
+        self.bus.sgb.as_ref().map(|sgb| {
+            sgb.output()
+                .iter()
+                .flat_map(|px| [px.r, px.g, px.b])
+                .collect()
+        })
+    }
+
+    pub fn set_color_correction(&mut self, color_correction: ColorCorrection) {
+        self.bus.ppu.set_color_correction(color_correction);
+    }
+
+    /// Starts keeping a blended copy of every frame, see [`GameBoy::get_blended_frame_buffer`].
+    /// `None` turns it off again
+    pub fn set_frame_blending(&mut self, mode: Option<FrameBlendMode>) {
+        if self
+            .bus
+            .frame_blender
+            .as_ref()
+            .map(|blender| blender.mode())
+            != mode
+        {
+            self.bus.frame_blender = mode.map(FrameBlender::new);
+        }
+    }
+
+    /// The frame buffer with LCD ghosting applied, `None` when frame blending is off.
+    /// The SGB border isn't part of it
+    #[cfg(not(feature = "web"))]
+    pub fn get_blended_frame_buffer(&self) -> Option<&[Rgb]> {
         self.bus
-            .ppu
-            .get_frame_buffer()
-            .to_vec()
-            .iter()
-            .flat_map(|px| [px.r, px.g, px.b])
-            .collect()
+            .frame_blender
+            .as_ref()
+            .map(|blender| blender.output())
+    }
+
+    #[cfg(feature = "web")]
+    pub fn get_blended_frame_buffer(&self) -> Option<Vec<u8>> {
+        self.bus.frame_blender.as_ref().map(|blender| {
+            blender
+                .output()
+                .iter()
+                .flat_map(|px| [px.r, px.g, px.b])
+                .collect()
+        })
     }
 
     pub fn consume_draw_flag(&mut self) -> bool {
         self.bus.ppu.consume_draw_flag()
     }
 
+    /// Returns whether the cartridge ram was written to since the last call
+    pub fn consume_cartridge_ram_dirty_flag(&mut self) -> bool {
+        std::mem::take(&mut self.bus.cartridge_ram_dirty)
+    }
+
     pub fn key_down(&mut self, key: Keycode) {
         if self.bus.input.key_down(key) {
             // self.cpu.stopped = false;
@@ -210,19 +340,124 @@ impl GameBoy {
     }
 
     pub fn load_snapshot(&mut self, mut snapshot: GameBoy) {
-        if let (Some(old_cart), Some(new_cart)) =
+        if let (Some(mut old_cart), Some(new_cart)) =
             (self.bus.cartridge.take(), snapshot.bus.cartridge.as_mut())
         {
+            if let (Cartridge::PocketCamera(old), Cartridge::PocketCamera(new)) =
+                (&mut old_cart, &mut *new_cart)
+            {
+                new.set_source(old.take_source());
+            }
             new_cart.load_rom(old_cart.take_rom());
         }
 
+        snapshot.tracer = self.tracer.take();
+        snapshot
+            .bus
+            .serial
+            .set_device(self.bus.serial.take_device());
+        snapshot
+            .bus
+            .infrared
+            .set_source(self.bus.infrared.take_source());
+        snapshot.bus.frame_blender = self.bus.frame_blender.take();
+        // the palettes in the snapshot might have been decoded with a different correction
+        snapshot
+            .bus
+            .ppu
+            .set_color_correction(self.bus.ppu.color_correction());
+        // the snapshot brings its own cartridge ram, which the save file doesn't have yet
+        snapshot.bus.cartridge_ram_dirty = true;
         *self = snapshot;
     }
 
+    /// The cartridge ram followed by the clock of MBC3 carts that have one, if the cart has
+    /// either. Use [`GameBoy::has_battery`] to decide whether it should be saved
     pub fn try_read_cartridge_ram(&self) -> Option<Box<[u8]>> {
         self.bus
             .cartridge
             .as_ref()
-            .map(|cart| cart.iter_ram().collect::<Vec<_>>().into_boxed_slice())
+            .and_then(|cart| cart.save_data())
+            .map(Vec::into_boxed_slice)
+    }
+
+    /// Whether the cartridge has a battery, so its ram would survive a power cycle
+    pub fn has_battery(&self) -> bool {
+        self.bus
+            .cartridge
+            .as_ref()
+            .is_some_and(|cart| cart.has_battery())
+    }
+
+    /// Whether the rumble motor of the cartridge is on. Games pulse it to vary the
+    /// strength, so sample it over a frame rather than once
+    pub fn rumble(&self) -> bool {
+        self.bus
+            .cartridge
+            .as_ref()
+            .is_some_and(|cart| cart.rumble())
+    }
+}
+
+impl GameBoy {
+    /// Attaches a tracer that logs every executed instruction, or detaches the current one
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        if let Some(mut old) = std::mem::replace(&mut self.tracer, tracer) {
+            let _ = old.flush();
+        }
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Matches the state gameboy-doctor's reference logs were made in: the registers the
+    /// DMG boot rom leaves behind, and `LY` always reading `$90`. Games won't run properly
+    /// like this, it's only meant for comparing traces
+    pub fn enable_gameboy_doctor_mode(&mut self) {
+        self.cpu.set_dmg_boot_registers();
+        self.bus.fixed_ly = Some(0x90);
+    }
+
+    /// Plugs a device (e.g. a [`serial::printer::Printer`]) into the link port,
+    /// or unplugs the current one
+    pub fn set_serial_device(&mut self, device: Option<Box<dyn SerialDevice>>) {
+        self.bus.serial.set_device(device);
+    }
+
+    /// Sets where the Game Boy Camera gets its pictures from. Returns false if the
+    /// cart isn't a camera
+    pub fn set_camera_source(&mut self, source: Option<Box<dyn CameraSource>>) -> bool {
+        match &mut self.bus.cartridge {
+            Some(Cartridge::PocketCamera(cart)) => {
+                cart.set_source(source);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Points the infrared sensor at a source of light, or removes the current one
+    pub fn set_infrared_source(&mut self, source: Option<Box<dyn InfraredSource>>) {
+        self.bus.infrared.set_source(source);
+    }
+
+    /// Whether the infrared LED is currently on
+    pub fn infrared_led_on(&self) -> bool {
+        self.bus.infrared.led_on()
+    }
+
+    /// Sets whether light reaches the infrared sensor. Overridden on every tick
+    /// while a source is set
+    pub fn set_infrared_light(&mut self, light: bool) {
+        self.bus.infrared.set_light_received(light);
+    }
+
+    /// Lets two emulators see each other's infrared LED. Call this after every
+    /// `tick` of both to keep them in lock-step
+    pub fn link_infrared(&mut self, other: &mut GameBoy) {
+        let (ours, theirs) = (self.infrared_led_on(), other.infrared_led_on());
+        self.set_infrared_light(theirs);
+        other.set_infrared_light(ours);
     }
 }
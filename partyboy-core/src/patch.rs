@@ -0,0 +1,436 @@
+//! Soft-patching of roms with IPS, UPS and BPS patches, as used by translations and
+//! rom hacks. The format is detected from the magic at the start of the patch.
+//!
+//! UPS and BPS patches carry CRC32s of the original rom, the patched rom and the patch
+//! itself, which are all checked. IPS has no checksums, so any rom is accepted.
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PatchError {
+    #[error("Unknown patch format, expected an IPS, UPS or BPS patch")]
+    UnknownFormat,
+    #[error("The patch is truncated or malformed")]
+    Malformed,
+    #[error("The patch is for a rom of {expected} bytes, but the rom is {actual} bytes")]
+    SourceSizeMismatch { expected: usize, actual: usize },
+    #[error("The patch is for a different rom (checksum mismatch)")]
+    SourceChecksumMismatch,
+    #[error("The patched rom doesn't have the expected checksum")]
+    TargetChecksumMismatch,
+    #[error("The patch is corrupted (checksum mismatch)")]
+    PatchChecksumMismatch,
+    #[error("The patched rom would be {0} bytes, more than a cartridge can hold")]
+    TargetTooLarge(usize),
+}
+
+/// The largest rom size the cartridge header can describe
+const MAX_TARGET_SIZE: usize = 8 * 1024 * 1024;
+
+fn check_target_size(size: usize) -> Result<(), PatchError> {
+    match size <= MAX_TARGET_SIZE {
+        true => Ok(()),
+        false => Err(PatchError::TargetTooLarge(size)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<Self> {
+        match patch {
+            [b'P', b'A', b'T', b'C', b'H', ..] => Some(Self::Ips),
+            [b'U', b'P', b'S', b'1', ..] => Some(Self::Ups),
+            [b'B', b'P', b'S', b'1', ..] => Some(Self::Bps),
+            _ => None,
+        }
+    }
+}
+
+/// Applies `patch` to `rom`, returning the patched rom
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match PatchFormat::detect(patch).ok_or(PatchError::UnknownFormat)? {
+        PatchFormat::Ips => apply_ips(rom, patch),
+        PatchFormat::Ups => apply_ups(rom, patch),
+        PatchFormat::Bps => apply_bps(rom, patch),
+    }
+}
+
+/// Walks over the bytes of a patch, failing on reads past the end
+struct PatchReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let end = self.pos.checked_add(len).ok_or(PatchError::Malformed)?;
+        let bytes = self.data.get(self.pos..end).ok_or(PatchError::Malformed)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn big_endian(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | byte as usize))
+    }
+
+    /// The variable length numbers used by UPS and BPS
+    fn number(&mut self) -> Result<usize, PatchError> {
+        let mut number = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.u8()?;
+            number = ((byte & 0x7F) as usize)
+                .checked_mul(shift)
+                .and_then(|value| number.checked_add(value))
+                .ok_or(PatchError::Malformed)?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+
+            shift = shift.checked_mul(0x80).ok_or(PatchError::Malformed)?;
+            number = number.checked_add(shift).ok_or(PatchError::Malformed)?;
+        }
+    }
+
+    /// BPS relative offsets, the lowest bit is the sign
+    fn signed_number(&mut self) -> Result<isize, PatchError> {
+        let number = self.number()?;
+        let magnitude = (number >> 1) as isize;
+        Ok(if number & 1 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        })
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut out = rom.to_vec();
+    let mut reader = PatchReader::new(patch, 5);
+
+    loop {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == b"EOF" {
+            break;
+        }
+        let offset = offset_bytes
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | byte as usize);
+
+        let (len, value) = match reader.big_endian(2)? {
+            // run length encoded record
+            0 => (reader.big_endian(2)?, None),
+            len => (len, Some(reader.bytes(len)?)),
+        };
+
+        if out.len() < offset + len {
+            check_target_size(offset + len)?;
+            out.resize(offset + len, 0);
+        }
+        match value {
+            Some(bytes) => out[offset..offset + len].copy_from_slice(bytes),
+            None => out[offset..offset + len].fill(reader.u8()?),
+        }
+    }
+
+    // extension: some patches end with the size to truncate the rom to
+    if let Ok(size) = reader.big_endian(3) {
+        out.truncate(size);
+    }
+
+    Ok(out)
+}
+
+/// Checks the CRC32s at the end of a UPS or BPS patch, the body ends before them
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<(u32, usize), PatchError> {
+    let body_end = patch.len().checked_sub(12).ok_or(PatchError::Malformed)?;
+    let crc = |offset: usize| u32::from_le_bytes(patch[offset..offset + 4].try_into().unwrap());
+
+    if crc32(&patch[..patch.len() - 4]) != crc(body_end + 8) {
+        return Err(PatchError::PatchChecksumMismatch);
+    }
+    if crc32(rom) != crc(body_end) {
+        return Err(PatchError::SourceChecksumMismatch);
+    }
+
+    Ok((crc(body_end + 4), body_end))
+}
+
+fn check_source_size(rom: &[u8], expected: usize) -> Result<(), PatchError> {
+    match rom.len() == expected {
+        true => Ok(()),
+        false => Err(PatchError::SourceSizeMismatch {
+            expected,
+            actual: rom.len(),
+        }),
+    }
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (target_crc, body_end) = check_footer(rom, patch)?;
+    let mut reader = PatchReader::new(&patch[..body_end], 4);
+
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    check_source_size(rom, source_size)?;
+    check_target_size(target_size)?;
+
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+
+    // every hunk skips some bytes and xors the ones after it, up to and including a 0
+    let mut pos = 0usize;
+    while reader.pos < body_end {
+        pos = pos
+            .checked_add(reader.number()?)
+            .ok_or(PatchError::Malformed)?;
+        loop {
+            let xor = reader.u8()?;
+            if let Some(byte) = out.get_mut(pos) {
+                *byte ^= xor;
+            }
+            pos += 1;
+
+            if xor == 0 {
+                break;
+            }
+        }
+    }
+
+    match crc32(&out) == target_crc {
+        true => Ok(out),
+        false => Err(PatchError::TargetChecksumMismatch),
+    }
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (target_crc, body_end) = check_footer(rom, patch)?;
+    let mut reader = PatchReader::new(&patch[..body_end], 4);
+
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    check_source_size(rom, source_size)?;
+    check_target_size(target_size)?;
+
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_offset = 0usize;
+    let mut target_offset = 0usize;
+
+    let relative = |offset: usize, delta: isize| {
+        offset
+            .checked_add_signed(delta)
+            .ok_or(PatchError::Malformed)
+    };
+
+    while reader.pos < body_end {
+        let action = reader.number()?;
+        let len = (action >> 2) + 1;
+        if out
+            .len()
+            .checked_add(len)
+            .is_none_or(|end| end > target_size)
+        {
+            return Err(PatchError::Malformed);
+        }
+
+        match action & 0b11 {
+            // source read
+            0 => {
+                let start = out.len();
+                let bytes = rom.get(start..start + len).ok_or(PatchError::Malformed)?;
+                out.extend_from_slice(bytes);
+            }
+            // target read
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // source copy
+            2 => {
+                source_offset = relative(source_offset, reader.signed_number()?)?;
+                let end = source_offset
+                    .checked_add(len)
+                    .ok_or(PatchError::Malformed)?;
+                let bytes = rom.get(source_offset..end).ok_or(PatchError::Malformed)?;
+                out.extend_from_slice(bytes);
+                source_offset = end;
+            }
+            // target copy, byte by byte since the ranges can overlap
+            _ => {
+                target_offset = relative(target_offset, reader.signed_number()?)?;
+                for _ in 0..len {
+                    let byte = *out.get(target_offset).ok_or(PatchError::Malformed)?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size {
+        return Err(PatchError::Malformed);
+    }
+
+    match crc32(&out) == target_crc {
+        true => Ok(out),
+        false => Err(PatchError::TargetChecksumMismatch),
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn applies_ips() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);
+        // run length encoded past the end of the rom
+        patch.extend([0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+        patch.extend(b"EOF");
+
+        let patched = apply(&[0; 8], &patch).unwrap();
+        assert_eq!(patched, [0, 0, 0xAA, 0xBB, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC]);
+    }
+
+    #[test]
+    fn applies_ups() {
+        let source = [1, 2, 3, 4];
+        let target = [1, 5, 3, 4, 9];
+
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(1));
+        patch.extend([2 ^ 5, 0]);
+        patch.extend(number(1));
+        patch.extend([9, 0]);
+        let patch = with_footer(patch, &source, &target);
+
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+        assert!(matches!(
+            apply(&[1, 2, 3, 5], &patch),
+            Err(PatchError::SourceChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn applies_bps() {
+        let source = [1, 2, 3, 4];
+        let target = [1, 2, 9, 9, 9, 9, 3, 4];
+
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(0));
+        // source read 2, target read 1, target copy 3 from 2, source copy 2 from 2
+        patch.extend(number(1 << 2));
+        patch.extend(number(1));
+        patch.push(9);
+        patch.extend(number((2 << 2) | 3));
+        patch.extend(number(2 << 1));
+        patch.extend(number((1 << 2) | 2));
+        patch.extend(number(2 << 1));
+        let patch = with_footer(patch, &source, &target);
+
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn rejects_corrupted_patch() {
+        let source = [1, 2, 3, 4];
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(number(4));
+        patch.extend(number(4));
+        let mut patch = with_footer(patch, &source, &source);
+        let len = patch.len();
+        patch[len - 1] ^= 0xFF;
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(PatchError::PatchChecksumMismatch)
+        ));
+        assert!(matches!(
+            apply(&source, b"nope"),
+            Err(PatchError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_target() {
+        let source = [1, 2, 3, 4];
+        for magic in [b"UPS1", b"BPS1"] {
+            let mut patch = magic.to_vec();
+            patch.extend(number(source.len()));
+            patch.extend(number(usize::MAX >> 8));
+            patch.extend(number(0));
+            let patch = with_footer(patch, &source, &source);
+
+            assert!(matches!(
+                apply(&source, &patch),
+                Err(PatchError::TargetTooLarge(_))
+            ));
+        }
+
+        // a bps action can't write past the target size either
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(source.len()));
+        patch.extend(number(0));
+        patch.extend(number((usize::MAX >> 9) << 2 | 3));
+        patch.extend(number(0));
+        let patch = with_footer(patch, &source, &source);
+        assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed)));
+    }
+}
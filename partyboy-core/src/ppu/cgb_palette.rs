@@ -8,6 +8,94 @@
 
 use crate::cartridge::Cartridge;
 
+#[cfg(feature = "web")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The D-pad + button combos that pick one of the CGB bios palettes by hand, instead of
+/// the one chosen by the title hash. Held while the boot logo is shown on real hardware
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "web", wasm_bindgen)]
+pub enum BootPaletteCombo {
+    /// Brown
+    Up,
+    /// Red
+    UpA,
+    /// Dark brown
+    UpB,
+    /// Blue
+    Left,
+    /// Dark blue
+    LeftA,
+    /// Grayscale
+    LeftB,
+    /// Pastel mix
+    Down,
+    /// Orange
+    DownA,
+    /// Yellow
+    DownB,
+    /// Green
+    Right,
+    /// Dark green, which is also what games without a palette of their own get
+    RightA,
+    /// Inverted
+    RightB,
+}
+
+pub fn get_combo_palettes(combo: BootPaletteCombo) -> [u32; 12] {
+    // These are RGB32 colors
+    match combo {
+        BootPaletteCombo::Up => [
+            0xFFFFFF, 0xFFAD63, 0x843100, 0x000000, 0xFFFFFF, 0xFFAD63, 0x843100, 0x000000,
+            0xFFFFFF, 0xFFAD63, 0x843100, 0x000000,
+        ],
+        BootPaletteCombo::UpA => [
+            0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000, 0xFFFFFF, 0x7BFF31, 0x008400, 0x000000,
+            0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000,
+        ],
+        BootPaletteCombo::UpB => [
+            0xFFE6C5, 0xCE9C84, 0x846B29, 0x5A3108, 0xFFFFFF, 0xFFAD63, 0x843100, 0x000000,
+            0xFFFFFF, 0xFFAD63, 0x843100, 0x000000,
+        ],
+        BootPaletteCombo::Left => [
+            0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000, 0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000,
+            0xFFFFFF, 0x7BFF31, 0x008400, 0x000000,
+        ],
+        BootPaletteCombo::LeftA => [
+            0xFFFFFF, 0x8C8CDE, 0x52528C, 0x000000, 0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000,
+            0xFFFFFF, 0xFFAD63, 0x843100, 0x000000,
+        ],
+        BootPaletteCombo::LeftB => [
+            0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000, 0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000,
+            0xFFFFFF, 0xA5A5A5, 0x525252, 0x000000,
+        ],
+        BootPaletteCombo::Down => [
+            0xFFFFA5, 0xFF9494, 0x9494FF, 0x000000, 0xFFFFA5, 0xFF9494, 0x9494FF, 0x000000,
+            0xFFFFA5, 0xFF9494, 0x9494FF, 0x000000,
+        ],
+        BootPaletteCombo::DownA => [
+            0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000, 0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000,
+            0xFFFFFF, 0xFFFF00, 0xFF0000, 0x000000,
+        ],
+        BootPaletteCombo::DownB => [
+            0xFFFFFF, 0xFFFF00, 0x7B4A00, 0x000000, 0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000,
+            0xFFFFFF, 0x7BFF31, 0x008400, 0x000000,
+        ],
+        BootPaletteCombo::Right => [
+            0xFFFFFF, 0x52FF00, 0xFF4200, 0x000000, 0xFFFFFF, 0x52FF00, 0xFF4200, 0x000000,
+            0xFFFFFF, 0x52FF00, 0xFF4200, 0x000000,
+        ],
+        BootPaletteCombo::RightA => [
+            0xFFFFFF, 0x7BFF31, 0x0063C5, 0x000000, 0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000,
+            0xFFFFFF, 0xFF8484, 0x943A3A, 0x000000,
+        ],
+        BootPaletteCombo::RightB => [
+            0x000000, 0x008484, 0xFFDE00, 0xFFFFFF, 0x000000, 0x008484, 0xFFDE00, 0xFFFFFF,
+            0x000000, 0x008484, 0xFFDE00, 0xFFFFFF,
+        ],
+    }
+}
+
 pub fn get_color_palettes(cartridge: &Cartridge) -> [u32; 12] {
     let hash = (0..16)
         .map(|i| cartridge.read_rom(0x134 + i) as u64)
@@ -345,3 +433,20 @@ pub fn get_color_palettes(cartridge: &Cartridge) -> [u32; 12] {
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn right_a_is_the_fallback_palette() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x134..0x134 + 5].copy_from_slice(b"\x01\x02\x03\x04\x05");
+        let cartridge = Cartridge::new(rom, None).unwrap();
+
+        assert_eq!(
+            get_color_palettes(&cartridge),
+            get_combo_palettes(BootPaletteCombo::RightA)
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Emulates the slow response of the LCD, which games rely on to make sprites that are
+//! only drawn every other frame look transparent instead of flickering.
+//!
+//! The blended frames are kept apart from the frame buffer, which stays the raw output of
+//! the ppu.
+
+use crate::{common::BoxedSlice, ppu::rgb::Rgb};
+
+#[cfg(feature = "web")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// How much of the previous output is left after every frame with
+/// [`FrameBlendMode::LcdResponse`]
+const LCD_PERSISTENCE: f32 = 0.55;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "web", wasm_bindgen)]
+pub enum FrameBlendMode {
+    /// Every frame is mixed 50/50 with the one before it
+    Mix,
+    /// Pixels fade from their previous color over several frames
+    LcdResponse,
+}
+
+pub(crate) struct FrameBlender {
+    mode: FrameBlendMode,
+    /// The previous raw frame for [`FrameBlendMode::Mix`]
+    previous: BoxedSlice<Rgb, { 160 * 144 }>,
+    output: BoxedSlice<Rgb, { 160 * 144 }>,
+    /// Whether there was a frame before, so the first one doesn't get blended with black
+    has_frame: bool,
+}
+
+impl FrameBlender {
+    pub fn new(mode: FrameBlendMode) -> Self {
+        Self {
+            mode,
+            previous: BoxedSlice::default(),
+            output: BoxedSlice::default(),
+            has_frame: false,
+        }
+    }
+
+    pub fn mode(&self) -> FrameBlendMode {
+        self.mode
+    }
+
+    pub fn output(&self) -> &[Rgb] {
+        &*self.output
+    }
+
+    pub fn on_frame(&mut self, frame: &[Rgb]) {
+        if !self.has_frame {
+            self.previous.copy_from_slice(frame);
+            self.output.copy_from_slice(frame);
+            self.has_frame = true;
+            return;
+        }
+
+        match self.mode {
+            FrameBlendMode::Mix => {
+                for ((out, previous), px) in
+                    self.output.iter_mut().zip(&mut *self.previous).zip(frame)
+                {
+                    *out = blend(*px, *previous, 0.5);
+                    *previous = *px;
+                }
+            }
+            FrameBlendMode::LcdResponse => {
+                for (out, px) in self.output.iter_mut().zip(frame) {
+                    *out = blend(*px, *out, LCD_PERSISTENCE);
+                }
+            }
+        }
+    }
+}
+
+/// Mixes `weight` of `previous` into `current`. The difference is truncated so a fading
+/// pixel always ends up at `current` instead of getting stuck one step away
+fn blend(current: Rgb, previous: Rgb, weight: f32) -> Rgb {
+    let channel = |current: u8, previous: u8| {
+        let diff = (previous as f32 - current as f32) * weight;
+        (current as i16 + diff as i16) as u8
+    };
+
+    Rgb {
+        r: channel(current.r, previous.r),
+        g: channel(current.g, previous.g),
+        b: channel(current.b, previous.b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(val: u8) -> Vec<Rgb> {
+        vec![Rgb::const_mono(val); 160 * 144]
+    }
+
+    #[test]
+    fn mix_averages_last_two_frames() {
+        let mut blender = FrameBlender::new(FrameBlendMode::Mix);
+
+        blender.on_frame(&frame(0xFF));
+        assert_eq!(blender.output()[0], Rgb::const_mono(0xFF));
+
+        blender.on_frame(&frame(0x00));
+        assert_eq!(blender.output()[0], Rgb::const_mono(0x7F));
+
+        // only the last two frames count
+        blender.on_frame(&frame(0x00));
+        assert_eq!(blender.output()[0], Rgb::const_mono(0x00));
+    }
+
+    #[test]
+    fn lcd_response_fades() {
+        let mut blender = FrameBlender::new(FrameBlendMode::LcdResponse);
+        blender.on_frame(&frame(0xFF));
+
+        let mut last = 0xFF;
+        for _ in 0..4 {
+            blender.on_frame(&frame(0x00));
+            let val = blender.output()[0].r;
+            assert!(val < last && val > 0);
+            last = val;
+        }
+
+        for _ in 0..32 {
+            blender.on_frame(&frame(0x00));
+        }
+        assert_eq!(blender.output()[0], Rgb::const_mono(0x00));
+    }
+}
@@ -1,12 +1,15 @@
 pub mod cgb_palette;
+pub mod frame_blend;
+pub mod pixel_format;
 mod pixel_slice_fetcher;
 pub mod rgb;
 
 use std::{collections::VecDeque, hint::unreachable_unchecked};
 
 use self::{
+    pixel_format::PixelFormat,
     pixel_slice_fetcher::{BackgroundFetchMode, FetchMode, PixelSliceFetcherState},
-    rgb::Rgb,
+    rgb::{ColorCorrection, Rgb},
 };
 use super::interrupts::{InterruptFlag, Interrupts};
 use crate::{
@@ -30,6 +33,13 @@ pub(crate) struct Ppu {
     pub sprite_palette: [[usize; 4]; 2],
 
     pub frame_buffer: BoxedSlice<Rgb, { 160 * 144 }>,
+    /// The 2 bit shade of every pixel in `frame_buffer` after the DMG palettes are applied,
+    /// used by the SGB to colorize the screen and read `*_TRN` data
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub shade_buffer: BoxedSlice<u8, { 160 * 144 }>,
+    /// Every pixel of `frame_buffer` as [`PixelFormat::Raw`]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    raw_buffer: BoxedSlice<u16, { 160 * 144 }>,
     draw_flag: bool,
 
     bg_palette: [usize; 4],
@@ -82,6 +92,10 @@ pub(crate) struct Ppu {
     /// Is wy == ly? Comparison is checked at the beginning of mode 2
     /// and stored in this variable
     wy_ly_equality_latch: bool,
+
+    /// What the decoded color palettes were corrected with
+    #[cfg_attr(feature = "serde", serde(default))]
+    color_correction: ColorCorrection,
 }
 
 #[derive(Clone, Copy)]
@@ -121,7 +135,7 @@ pub enum LcdControlFlag {
     LCDDisplayEnable = 0b1000_0000,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[allow(clippy::upper_case_acronyms)]
 pub enum PpuMode {
@@ -275,6 +289,8 @@ impl Ppu {
             sprite_palette: [[0, 1, 2, 3], [0, 1, 2, 3]],
 
             frame_buffer: BoxedSlice::default(),
+            shade_buffer: BoxedSlice::default(),
+            raw_buffer: BoxedSlice::default(),
             draw_flag: false,
 
             bg_palette: [0, 1, 2, 3],
@@ -327,13 +343,62 @@ impl Ppu {
             fifo_state: FifoState::default(),
 
             wy_ly_equality_latch: false,
+
+            color_correction: ColorCorrection::None,
         }
     }
 
+    /// Writes the palettes to palette ram, like the CGB bios does for DMG games
     pub fn override_color_palettes(&mut self, palettes: &[Rgb; 12]) {
-        self.bg_color_palette[0].copy_from_slice(&palettes[0..4]);
-        self.sprite_color_palette[0].copy_from_slice(&palettes[4..8]);
-        self.sprite_color_palette[1].copy_from_slice(&palettes[8..12]);
+        let write = |palette_ram: &mut BoxedSlice<u8, 64>, start: usize, colors: &[Rgb]| {
+            for (i, color) in colors.iter().enumerate() {
+                let [lo, hi] = color.to_bgr555().to_le_bytes();
+                palette_ram[start + i * 2] = lo;
+                palette_ram[start + i * 2 + 1] = hi;
+            }
+        };
+
+        write(&mut self.bg_color_palette_ram, 0, &palettes[0..4]);
+        write(&mut self.sprite_color_palette_ram, 0, &palettes[4..8]);
+        write(&mut self.sprite_color_palette_ram, 8, &palettes[8..12]);
+
+        // some of the colors aren't exactly representable in 15 bits, keep them as is
+        // unless they get corrected anyway
+        match self.color_correction {
+            ColorCorrection::None => {
+                self.bg_color_palette[0].copy_from_slice(&palettes[0..4]);
+                self.sprite_color_palette[0].copy_from_slice(&palettes[4..8]);
+                self.sprite_color_palette[1].copy_from_slice(&palettes[8..12]);
+            }
+            _ => self.decode_color_palettes(),
+        }
+    }
+
+    pub fn color_correction(&self) -> ColorCorrection {
+        self.color_correction
+    }
+
+    pub fn set_color_correction(&mut self, color_correction: ColorCorrection) {
+        if self.color_correction != color_correction {
+            self.color_correction = color_correction;
+            self.decode_color_palettes();
+        }
+    }
+
+    /// The color in palette ram at `index`, which can point at either of its bytes
+    fn decode_color(&self, palette_ram: &BoxedSlice<u8, 64>, index: usize) -> Rgb {
+        let bgr555 = u16::from_le_bytes([palette_ram[index & !1], palette_ram[index | 1]]);
+        Rgb::from_bgr555_corrected(bgr555, self.color_correction)
+    }
+
+    fn decode_color_palettes(&mut self) {
+        for index in (0..64).step_by(2) {
+            let (palette_index, color) = (index >> 3, (index & 7) >> 1);
+            self.bg_color_palette[palette_index][color] =
+                self.decode_color(&self.bg_color_palette_ram, index);
+            self.sprite_color_palette[palette_index][color] =
+                self.decode_color(&self.sprite_color_palette_ram, index);
+        }
     }
 
     pub fn override_obj_prio_mode(&mut self, mode: ObjectPriorityMode) {
@@ -379,6 +444,27 @@ impl Ppu {
         *self.frame_buffer
     }
 
+    pub fn render_frame_buffer(&self, format: PixelFormat, out: &mut [u8]) {
+        pixel_format::encode_frame(format, &*self.frame_buffer, &*self.raw_buffer, out);
+    }
+
+    /// The value of a pixel for [`PixelFormat::Raw`], `color_index` is the shade after the
+    /// DMG palette in DMG mode
+    fn raw_pixel(
+        &self,
+        palette_ram: &BoxedSlice<u8, 64>,
+        palette_index: usize,
+        color_index: usize,
+    ) -> u16 {
+        match self.console_compatibility_mode {
+            CgbCompatibility::CgbOnly => {
+                let index = palette_index * 8 + color_index * 2;
+                u16::from_le_bytes([palette_ram[index], palette_ram[index + 1]])
+            }
+            _ => color_index as u16,
+        }
+    }
+
     pub fn consume_draw_flag(&mut self) -> bool {
         let flag = self.draw_flag;
         self.draw_flag = false;
@@ -504,9 +590,7 @@ impl Ppu {
                 let index = self.bg_color_palette_index;
                 self.bg_color_palette_ram[self.bg_color_palette_index] = val;
 
-                let bgr555: u16 = ((self.bg_color_palette_ram[index | 1] as u16) << 8)
-                    | (self.bg_color_palette_ram[index & !1] as u16);
-                let rgb = Rgb::from_bgr555(bgr555);
+                let rgb = self.decode_color(&self.bg_color_palette_ram, index);
 
                 let palette_index = self.bg_color_palette_index >> 3;
                 let palette_color_bit = (self.bg_color_palette_index & 7) >> 1;
@@ -531,9 +615,7 @@ impl Ppu {
                 let index = self.sprite_color_palette_index;
                 self.sprite_color_palette_ram[self.sprite_color_palette_index] = val;
 
-                let bgr555: u16 = ((self.sprite_color_palette_ram[index | 1] as u16) << 8)
-                    | (self.sprite_color_palette_ram[index & !1] as u16);
-                let rgb = Rgb::from_bgr555(bgr555);
+                let rgb = self.decode_color(&self.sprite_color_palette_ram, index);
 
                 let palette_index = self.sprite_color_palette_index >> 3;
                 let palette_color_bit = (self.sprite_color_palette_index & 7) >> 1;
@@ -883,6 +965,12 @@ impl Ppu {
             _ => self.bg_palette[bg_px.color_index as usize] as u8,
         };
         let mut color = self.bg_color_palette[bg_px.palette_index as usize][color_index as usize];
+        let mut shade = color_index;
+        let mut raw = self.raw_pixel(
+            &self.bg_color_palette_ram,
+            bg_px.palette_index as usize,
+            color_index as usize,
+        );
 
         // Check/handle sprite fifo
         if let Some(sprite_px) = self.fifo_state.sprite_fifo.pop_front() {
@@ -898,11 +986,19 @@ impl Ppu {
                 };
 
                 color = self.sprite_color_palette[sprite_px.palette_index as usize][color_index];
+                shade = color_index as u8;
+                raw = self.raw_pixel(
+                    &self.sprite_color_palette_ram,
+                    sprite_px.palette_index as usize,
+                    color_index,
+                );
             }
         }
 
         let frame_buffer_px_index = (self.ly as usize * 160) + self.fifo_state.lx as usize;
         self.frame_buffer[frame_buffer_px_index] = color;
+        self.shade_buffer[frame_buffer_px_index] = shade;
+        self.raw_buffer[frame_buffer_px_index] = raw;
 
         self.fifo_state.lx += 1;
         self.fifo_state.lx == 160
@@ -0,0 +1,109 @@
+//! Encodes the frame buffer straight into the layout a frontend draws with, so it doesn't
+//! have to convert every frame itself.
+
+use crate::ppu::rgb::Rgb;
+
+#[cfg(feature = "web")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "web", wasm_bindgen)]
+pub enum PixelFormat {
+    /// Red, green, blue and a fully opaque alpha byte
+    Rgba8888,
+    /// Blue, green, red and a fully opaque alpha byte
+    Bgra8888,
+    /// Red, green and blue without alpha
+    Rgb888,
+    /// Little endian 16 bit color with red in the top 5 bits
+    Rgb565,
+    /// Little endian 16 bit value the ppu picked for the pixel: the BGR555 color from
+    /// palette ram in CGB mode, otherwise the 2 bit shade. Color correction and SGB
+    /// colorization aren't applied
+    Raw,
+}
+
+impl PixelFormat {
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => 4,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgb565 | PixelFormat::Raw => 2,
+        }
+    }
+}
+
+/// Writes every pixel into `out` in `format`, `raw` holds the values for [`PixelFormat::Raw`]
+///
+/// # Panics
+///
+/// If `out` isn't exactly the size of the frame in `format`
+pub(crate) fn encode_frame(format: PixelFormat, frame: &[Rgb], raw: &[u16], out: &mut [u8]) {
+    let bytes_per_pixel = format.bytes_per_pixel();
+    assert_eq!(
+        out.len(),
+        frame.len() * bytes_per_pixel,
+        "output buffer doesn't fit a {format:?} frame"
+    );
+
+    for ((px, raw), out) in frame
+        .iter()
+        .zip(raw)
+        .zip(out.chunks_exact_mut(bytes_per_pixel))
+    {
+        match format {
+            PixelFormat::Rgba8888 => out.copy_from_slice(&[px.r, px.g, px.b, 0xFF]),
+            PixelFormat::Bgra8888 => out.copy_from_slice(&[px.b, px.g, px.r, 0xFF]),
+            PixelFormat::Rgb888 => out.copy_from_slice(&[px.r, px.g, px.b]),
+            PixelFormat::Rgb565 => {
+                let rgb565 =
+                    ((px.r as u16 >> 3) << 11) | ((px.g as u16 >> 2) << 5) | (px.b as u16 >> 3);
+                out.copy_from_slice(&rgb565.to_le_bytes());
+            }
+            PixelFormat::Raw => out.copy_from_slice(&raw.to_le_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: [Rgb; 2] = [
+        Rgb {
+            r: 0xFF,
+            g: 0x80,
+            b: 0x08,
+        },
+        Rgb::const_mono(0),
+    ];
+    const RAW: [u16; 2] = [0x7C1F, 3];
+
+    fn encode(format: PixelFormat) -> Vec<u8> {
+        let mut out = vec![0; FRAME.len() * format.bytes_per_pixel()];
+        encode_frame(format, &FRAME, &RAW, &mut out);
+        out
+    }
+
+    #[test]
+    fn encodes_all_formats() {
+        assert_eq!(
+            encode(PixelFormat::Rgba8888),
+            [0xFF, 0x80, 0x08, 0xFF, 0, 0, 0, 0xFF]
+        );
+        assert_eq!(
+            encode(PixelFormat::Bgra8888),
+            [0x08, 0x80, 0xFF, 0xFF, 0, 0, 0, 0xFF]
+        );
+        assert_eq!(encode(PixelFormat::Rgb888), [0xFF, 0x80, 0x08, 0, 0, 0]);
+        // 11111 100000 00001
+        assert_eq!(encode(PixelFormat::Rgb565), [0x01, 0xFC, 0, 0]);
+        assert_eq!(encode(PixelFormat::Raw), [0x1F, 0x7C, 3, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_wrong_buffer_size() {
+        encode_frame(PixelFormat::Rgba8888, &FRAME, &RAW, &mut [0; 4]);
+    }
+}
@@ -37,17 +37,29 @@ impl Rgb {
         Rgb { r, g, b }
     }
 
-    pub(super) fn from_bgr555(bgr555: u16) -> Self {
-        Self {
-            r: (bgr555 & 0x1F) as u8,
-            g: ((bgr555 >> 5) & 0x1F) as u8,
-            b: ((bgr555 >> 10) & 0x1F) as u8,
+    pub(crate) fn from_bgr555(bgr555: u16) -> Self {
+        Self::from_bgr555_corrected(bgr555, ColorCorrection::None)
+    }
+
+    pub(crate) fn from_bgr555_corrected(bgr555: u16, color_correction: ColorCorrection) -> Self {
+        let r = (bgr555 & 0x1F) as u8;
+        let g = ((bgr555 >> 5) & 0x1F) as u8;
+        let b = ((bgr555 >> 10) & 0x1F) as u8;
+
+        match color_correction {
+            ColorCorrection::None => Self { r, g, b }.convert_555_to_888(),
+            ColorCorrection::Byuu => Self::byuu_correction(r, g, b),
+            ColorCorrection::ModernBalanced => Self::modern_balanced_correction(r, g, b),
+            ColorCorrection::GbaBacklit => Self::gba_backlit_correction(r, g, b),
         }
-        .convert_555_to_888()
+    }
+
+    /// The inverse of [`Rgb::from_bgr555`], the lowest 3 bits of every channel are lost
+    pub(crate) fn to_bgr555(self) -> u16 {
+        ((self.b as u16 >> 3) << 10) | ((self.g as u16 >> 3) << 5) | (self.r as u16 >> 3)
     }
 
     // see also: https://stackoverflow.com/questions/4409763/how-to-convert-from-rgb555-to-rgb888-in-c
-    #[allow(dead_code)]
     fn convert_555_to_888(mut self) -> Self {
         self.r = (self.r << 3) | (self.r >> 2);
         self.g = (self.g << 3) | (self.g >> 2);
@@ -56,12 +68,125 @@ impl Rgb {
         self
     }
 
-    #[allow(dead_code)]
-    pub(super) fn byuu_correction(mut self) -> Self {
-        self.r = cmp::min(240, (26 * self.r + 4 * self.g + 2 * self.b) >> 2);
-        self.g = cmp::min(240, (24 * self.g + 8 * self.b) >> 2);
-        self.b = cmp::min(240, (6 * self.r + 4 * self.g + 22 * self.b) >> 2);
+    // https://near.sh/articles/video/color-emulation
+    fn byuu_correction(r: u8, g: u8, b: u8) -> Self {
+        let (r, g, b) = (r as u16, g as u16, b as u16);
+        let channel = |val: u16| (cmp::min(960, val) >> 2) as u8;
 
-        self
+        Self {
+            r: channel(r * 26 + g * 4 + b * 2),
+            g: channel(g * 24 + b * 8),
+            b: channel(r * 6 + g * 4 + b * 22),
+        }
+    }
+
+    /// The CGB screen bleeds blue into green. Mixing that in dims the green, so everything
+    /// is scaled back up afterwards to keep the brightest channel as bright as before
+    fn modern_balanced_correction(r: u8, g: u8, b: u8) -> Self {
+        let [r, g, b] = [r, g, b].map(to_linear);
+        let mixed_g = (g * 3.0 + b) / 4.0;
+
+        let old_max = r.max(g).max(b);
+        let new_max = r.max(mixed_g).max(b);
+        let scale = if new_max > 0.0 {
+            old_max / new_max
+        } else {
+            1.0
+        };
+
+        Self {
+            r: from_linear(r * scale),
+            g: from_linear(mixed_g * scale),
+            b: from_linear(b * scale),
+        }
+    }
+
+    /// A backlit GBA shows CGB games with less of the washed out look of the CGB, only
+    /// the channels bleed into each other a bit. White stays white
+    fn gba_backlit_correction(r: u8, g: u8, b: u8) -> Self {
+        let [r, g, b] = [r, g, b].map(to_linear);
+
+        Self {
+            r: from_linear(r * 0.86 + g * 0.10 + b * 0.04),
+            g: from_linear(r * 0.03 + g * 0.87 + b * 0.10),
+            b: from_linear(r * 0.02 + g * 0.13 + b * 0.85),
+        }
+    }
+}
+
+const GAMMA: f32 = 2.2;
+
+fn to_linear(channel: u8) -> f32 {
+    (channel as f32 / 31.0).powf(GAMMA)
+}
+
+fn from_linear(channel: f32) -> u8 {
+    (channel.clamp(0.0, 1.0).powf(1.0 / GAMMA) * 255.0).round() as u8
+}
+
+/// How the 15 bit CGB colors are turned into RGB. Games were made for the washed out CGB
+/// screen, so showing the raw colors makes them look oversaturated
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "web", wasm_bindgen)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ColorCorrection {
+    /// The raw colors
+    #[default]
+    None,
+    /// The color matrix used by byuu in higan
+    Byuu,
+    /// Modelled after SameBoy's "modern - balanced" mode
+    ModernBalanced,
+    /// The CGB game running on a backlit GBA SP
+    GbaBacklit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORRECTIONS: [ColorCorrection; 4] = [
+        ColorCorrection::None,
+        ColorCorrection::Byuu,
+        ColorCorrection::ModernBalanced,
+        ColorCorrection::GbaBacklit,
+    ];
+
+    #[test]
+    fn black_and_white_stay_neutral() {
+        for correction in CORRECTIONS {
+            let black = Rgb::from_bgr555_corrected(0x0000, correction);
+            let white = Rgb::from_bgr555_corrected(0x7FFF, correction);
+
+            assert_eq!(black, Rgb::const_mono(0), "{:?}", correction);
+            assert!(white.r == white.g && white.g == white.b, "{:?}", correction);
+        }
+    }
+
+    #[test]
+    fn corrections_desaturate() {
+        let raw = Rgb::from_bgr555(0x7C00);
+        assert_eq!(
+            raw,
+            Rgb {
+                r: 0,
+                g: 0,
+                b: 0xFF
+            }
+        );
+        assert_eq!(raw.to_bgr555(), 0x7C00);
+
+        assert_eq!(
+            Rgb::from_bgr555_corrected(0x7C00, ColorCorrection::Byuu),
+            Rgb {
+                r: 0x0F,
+                g: 0x3E,
+                b: 0xAA
+            }
+        );
+        for correction in &CORRECTIONS[2..] {
+            let blue = Rgb::from_bgr555_corrected(0x7C00, *correction);
+            assert!(blue.g > 0, "{:?}", correction);
+        }
     }
 }
@@ -0,0 +1,163 @@
+pub mod printer;
+
+use crate::interrupts::{InterruptFlag, Interrupts};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// T-cycles it takes to shift out a whole byte at 8192Hz
+const TRANSFER_CYCLES: u16 = 8 * 512;
+/// T-cycles it takes with the CGB fast clock (262144Hz)
+const FAST_TRANSFER_CYCLES: u16 = 8 * 16;
+
+/// Something plugged into the link port, like a printer or another gameboy.
+///
+/// The gameboy always drives the clock, so a device only ever gets to answer
+/// the byte that was sent with the byte it shifted out at the same time
+pub trait SerialDevice: Send {
+    fn exchange(&mut self, byte: u8) -> u8;
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct Serial {
+    sb: u8,
+    sc: u8,
+    /// T-cycles until the running transfer completes
+    cycles_left: u16,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    device: Option<Box<dyn SerialDevice>>,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            cycles_left: 0,
+            device: None,
+        }
+    }
+
+    pub fn set_device(&mut self, device: Option<Box<dyn SerialDevice>>) {
+        self.device = device;
+    }
+
+    pub fn take_device(&mut self) -> Option<Box<dyn SerialDevice>> {
+        self.device.take()
+    }
+
+    /// Outside of CGB mode there is no fast clock, and its bit reads as 1
+    pub fn read(&self, addr: u16, cgb_mode: bool) -> u8 {
+        match addr {
+            0xFF01 => self.sb,
+            0xFF02 if cgb_mode => 0b0111_1100 | self.sc,
+            0xFF02 => 0b0111_1110 | self.sc,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, val: u8, cgb_mode: bool) {
+        match addr {
+            0xFF01 => self.sb = val,
+            0xFF02 => {
+                let val = if cgb_mode { val } else { val & !0b10 };
+                self.sc = val & 0b1000_0011;
+
+                // only transfers using the internal clock ever complete, there
+                // is nothing on the other end that could drive the clock
+                let start = val & 0b1000_0001 == 0b1000_0001;
+                self.cycles_left = match (start, val & 0b10 != 0) {
+                    (false, _) => 0,
+                    (true, false) => TRANSFER_CYCLES,
+                    (true, true) => FAST_TRANSFER_CYCLES,
+                };
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn tick(&mut self, interrupts: &mut Interrupts) {
+        if self.cycles_left == 0 {
+            return;
+        }
+
+        self.cycles_left -= 1;
+        if self.cycles_left == 0 {
+            // with nothing connected the line is pulled high
+            self.sb = match &mut self.device {
+                Some(device) => device.exchange(self.sb),
+                None => 0xFF,
+            };
+            self.sc &= 0b0111_1111;
+            interrupts.request_interupt(InterruptFlag::Serial);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl SerialDevice for Echo {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            !byte
+        }
+    }
+
+    #[test]
+    fn transfer_completes_after_8_bits() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+        serial.set_device(Some(Box::new(Echo)));
+
+        serial.write(0xFF01, 0x0F, true);
+        serial.write(0xFF02, 0x81, true);
+        for _ in 0..TRANSFER_CYCLES - 1 {
+            serial.tick(&mut interrupts);
+        }
+        assert_eq!(serial.read(0xFF02, true) & 0x80, 0x80);
+        assert_eq!(interrupts.flags, 0);
+
+        serial.tick(&mut interrupts);
+        assert_eq!(serial.read(0xFF01, true), 0xF0);
+        assert_eq!(serial.read(0xFF02, true) & 0x80, 0);
+        assert_eq!(interrupts.flags, InterruptFlag::Serial as u8);
+    }
+
+    #[test]
+    fn external_clock_never_completes() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+
+        serial.write(0xFF01, 0x42, true);
+        serial.write(0xFF02, 0x80, true);
+        for _ in 0..TRANSFER_CYCLES * 2 {
+            serial.tick(&mut interrupts);
+        }
+
+        assert_eq!(serial.read(0xFF01, true), 0x42);
+        assert_eq!(serial.read(0xFF02, true) & 0x80, 0x80);
+        assert_eq!(interrupts.flags, 0);
+    }
+
+    #[test]
+    fn dmg_has_no_fast_clock() {
+        let mut serial = Serial::new();
+        let mut interrupts = Interrupts::new();
+
+        serial.write(0xFF02, 0x83, false);
+        assert_eq!(serial.read(0xFF02, false), 0xFF);
+        for _ in 0..FAST_TRANSFER_CYCLES {
+            serial.tick(&mut interrupts);
+        }
+        assert_eq!(serial.read(0xFF02, false), 0xFF);
+
+        for _ in FAST_TRANSFER_CYCLES..TRANSFER_CYCLES {
+            serial.tick(&mut interrupts);
+        }
+        assert_eq!(serial.read(0xFF02, false), 0x7F);
+    }
+}
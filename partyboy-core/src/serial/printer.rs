@@ -0,0 +1,359 @@
+//! The Game Boy Printer, see also: https://gbdev.io/pandocs/Gameboy_Printer.html
+//!
+//! Every packet looks like `88 33 <command> <compression> <len lo> <len hi> <data...>
+//! <checksum lo> <checksum hi> 00 00`. The printer answers the last two bytes with its
+//! id (0x81) and its status. Printing finishes instantly, games just see the busy
+//! flag on the next status request.
+
+use crate::ppu::rgb::Rgb;
+
+use super::SerialDevice;
+
+pub const WIDTH: usize = 160;
+
+/// The printer holds at most 9 bands of 2 tile rows
+const MAX_BUFFER_SIZE: usize = 9 * 40 * 16;
+const MAX_PACKET_DATA: usize = 0x280;
+const DEVICE_ID: u8 = 0x81;
+
+const STATUS_CHECKSUM_ERROR: u8 = 0b0000_0001;
+const STATUS_PRINTING: u8 = 0b0000_0010;
+const STATUS_UNPROCESSED_DATA: u8 = 0b0000_1000;
+
+/// A printed strip, `WIDTH` pixels wide
+pub struct PrintedImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+pub type PrintHandler = Box<dyn FnMut(PrintedImage) + Send>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Command {
+    Init,
+    Print,
+    Data,
+    Status,
+    Unknown(u8),
+}
+
+impl From<u8> for Command {
+    fn from(val: u8) -> Self {
+        match val {
+            0x01 => Command::Init,
+            0x02 => Command::Print,
+            0x04 => Command::Data,
+            0x0F => Command::Status,
+            _ => Command::Unknown(val),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    MagicA,
+    MagicB,
+    Command,
+    Compression,
+    LenLo,
+    LenHi,
+    Data,
+    ChecksumLo,
+    ChecksumHi,
+    DeviceId,
+    Status,
+}
+
+pub struct Printer {
+    on_print: PrintHandler,
+
+    state: State,
+    command: u8,
+    compressed: bool,
+    len: usize,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+
+    status: u8,
+    /// Decompressed tile data received with `DATA` packets
+    buffer: Vec<u8>,
+}
+
+impl Printer {
+    pub fn new(on_print: PrintHandler) -> Self {
+        Self {
+            on_print,
+
+            state: State::MagicA,
+            command: 0,
+            compressed: false,
+            len: 0,
+            data: Vec::new(),
+            checksum: 0,
+            received_checksum: 0,
+
+            status: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn receive(&mut self, byte: u8) -> u8 {
+        if matches!(
+            self.state,
+            State::Command | State::Compression | State::LenLo | State::LenHi | State::Data
+        ) {
+            self.checksum = self.checksum.wrapping_add(byte as u16);
+        }
+
+        let (next, response) = match self.state {
+            State::MagicA if byte == 0x88 => (State::MagicB, 0),
+            State::MagicA => (State::MagicA, 0),
+            State::MagicB if byte == 0x33 => (State::Command, 0),
+            State::MagicB => (State::MagicA, 0),
+            State::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                (State::Compression, 0)
+            }
+            State::Compression => {
+                self.compressed = byte & 1 != 0;
+                (State::LenLo, 0)
+            }
+            State::LenLo => {
+                self.len = byte as usize;
+                (State::LenHi, 0)
+            }
+            State::LenHi => {
+                self.len |= (byte as usize) << 8;
+                self.len = self.len.min(MAX_PACKET_DATA);
+                self.data.clear();
+                match self.len {
+                    0 => (State::ChecksumLo, 0),
+                    _ => (State::Data, 0),
+                }
+            }
+            State::Data => {
+                self.data.push(byte);
+                match self.data.len() == self.len {
+                    true => (State::ChecksumLo, 0),
+                    false => (State::Data, 0),
+                }
+            }
+            State::ChecksumLo => {
+                self.received_checksum = byte as u16;
+                (State::ChecksumHi, 0)
+            }
+            State::ChecksumHi => {
+                self.received_checksum |= (byte as u16) << 8;
+                (State::DeviceId, 0)
+            }
+            State::DeviceId => (State::Status, DEVICE_ID),
+            State::Status => {
+                let status = self.handle_packet();
+                (State::MagicA, status)
+            }
+        };
+
+        self.state = next;
+        response
+    }
+
+    /// Handles a complete packet, returning the status to send back
+    fn handle_packet(&mut self) -> u8 {
+        if self.checksum != self.received_checksum {
+            log::warn!("Printer packet checksum mismatch");
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return self.status;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        // the status reflects the state from before the command is handled
+        let status = self.status;
+
+        match Command::from(self.command) {
+            Command::Init => {
+                self.buffer.clear();
+                self.status = 0;
+            }
+            Command::Data => {
+                let data = std::mem::take(&mut self.data);
+                if self.compressed {
+                    self.decompress(&data);
+                } else {
+                    self.buffer.extend_from_slice(&data);
+                }
+                self.buffer.truncate(MAX_BUFFER_SIZE);
+                self.data = data;
+
+                if !self.buffer.is_empty() {
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+            }
+            Command::Print => {
+                if let Some(&palette) = self.data.get(2) {
+                    self.print(palette);
+                }
+                self.status = STATUS_PRINTING;
+            }
+            Command::Status => self.status &= !STATUS_PRINTING,
+            Command::Unknown(command) => log::warn!("Unknown printer command {:#04X}", command),
+        }
+
+        status
+    }
+
+    /// Bytes with bit 7 set repeat the next byte `(n & 0x7F) + 2` times,
+    /// otherwise the next `n + 1` bytes are copied as is
+    fn decompress(&mut self, data: &[u8]) {
+        let mut bytes = data.iter().copied();
+        while let Some(control) = bytes.next() {
+            if control & 0x80 != 0 {
+                let Some(byte) = bytes.next() else {
+                    break;
+                };
+                let run = (control & 0x7F) as usize + 2;
+                self.buffer.extend(std::iter::repeat_n(byte, run));
+            } else {
+                self.buffer
+                    .extend(bytes.by_ref().take(control as usize + 1));
+            }
+        }
+    }
+
+    fn print(&mut self, palette: u8) {
+        // a palette of 0 is treated like the default one
+        let palette = match palette {
+            0 => 0b1110_0100,
+            _ => palette,
+        };
+
+        let tiles = self.buffer.len() / 16;
+        let height = tiles / (WIDTH / 8) * 8;
+        let mut pixels = vec![Rgb::const_mono(255); WIDTH * height];
+
+        for (tile, data) in self.buffer.chunks_exact(16).enumerate() {
+            let (tile_x, tile_y) = (tile % (WIDTH / 8), tile / (WIDTH / 8));
+            if tile_y * 8 >= height {
+                break;
+            }
+
+            for (row, bytes) in data.chunks_exact(2).enumerate() {
+                for px in 0..8 {
+                    let bit = 7 - px;
+                    let color = ((bytes[0] >> bit) & 1) | (((bytes[1] >> bit) & 1) << 1);
+                    let shade = (palette >> (color * 2)) & 0b11;
+
+                    let (x, y) = (tile_x * 8 + px, tile_y * 8 + row);
+                    pixels[y * WIDTH + x] = Rgb::const_mono(255 - shade * 85);
+                }
+            }
+        }
+
+        self.buffer.clear();
+        self.status &= !STATUS_UNPROCESSED_DATA;
+
+        if height > 0 {
+            (self.on_print)(PrintedImage {
+                width: WIDTH,
+                height,
+                pixels,
+            });
+        }
+    }
+}
+
+impl SerialDevice for Printer {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.receive(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+        let len = (data.len() as u16).to_le_bytes();
+        let mut body = vec![command, compressed as u8, len[0], len[1]];
+        body.extend_from_slice(data);
+        let checksum = body
+            .iter()
+            .fold(0u16, |sum, byte| sum.wrapping_add(*byte as u16))
+            .to_le_bytes();
+
+        [0x88, 0x33]
+            .into_iter()
+            .chain(body)
+            .chain(checksum)
+            .chain([0, 0])
+            .map(|byte| printer.exchange(byte))
+            .collect()
+    }
+
+    fn printer() -> (Printer, Arc<Mutex<Vec<PrintedImage>>>) {
+        let printed = Arc::new(Mutex::new(Vec::new()));
+        let sink = printed.clone();
+        let printer = Printer::new(Box::new(move |image| sink.lock().unwrap().push(image)));
+        (printer, printed)
+    }
+
+    #[test]
+    fn answers_status_with_device_id() {
+        let (mut printer, _) = printer();
+
+        let response = send_packet(&mut printer, 0x0F, false, &[]);
+        assert_eq!(response[response.len() - 2..], [DEVICE_ID, 0]);
+    }
+
+    #[test]
+    fn prints_uncompressed_band() {
+        let (mut printer, printed) = printer();
+
+        send_packet(&mut printer, 0x01, false, &[]);
+
+        // 40 tiles, the first one entirely black
+        let mut band = vec![0; 40 * 16];
+        band[..16].fill(0xFF);
+        let response = send_packet(&mut printer, 0x04, false, &band);
+        assert_eq!(*response.last().unwrap(), 0);
+        send_packet(&mut printer, 0x04, false, &[]);
+
+        let response = send_packet(&mut printer, 0x02, false, &[1, 0x13, 0xE4, 0x40]);
+        assert_eq!(*response.last().unwrap(), STATUS_UNPROCESSED_DATA);
+
+        let response = send_packet(&mut printer, 0x0F, false, &[]);
+        assert_eq!(*response.last().unwrap(), STATUS_PRINTING);
+        let response = send_packet(&mut printer, 0x0F, false, &[]);
+        assert_eq!(*response.last().unwrap(), 0);
+
+        let printed = printed.lock().unwrap();
+        assert_eq!(printed.len(), 1);
+        assert_eq!((printed[0].width, printed[0].height), (160, 16));
+        assert_eq!(printed[0].pixels[0], Rgb::const_mono(0));
+        assert_eq!(printed[0].pixels[8], Rgb::const_mono(255));
+    }
+
+    #[test]
+    fn decompresses_rle_data() {
+        let (mut printer, _) = printer();
+
+        // a run of 4 0xAA, then 2 literal bytes
+        send_packet(&mut printer, 0x04, true, &[0x82, 0xAA, 0x01, 0x12, 0x34]);
+        assert_eq!(printer.buffer, [0xAA, 0xAA, 0xAA, 0xAA, 0x12, 0x34]);
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let (mut printer, _) = printer();
+
+        let response = [0x88, 0x33, 0x0F, 0, 0, 0, 0xFF, 0xFF, 0, 0]
+            .into_iter()
+            .map(|byte| printer.exchange(byte))
+            .collect::<Vec<_>>();
+        assert_eq!(*response.last().unwrap(), STATUS_CHECKSUM_ERROR);
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{common::BoxedSlice, ppu::rgb::Rgb};
+
+/// 256 SNES tiles, 4 bits per pixel
+const TILE_DATA_SIZE: usize = 256 * 32;
+/// 32x32 tile map followed by palettes 4-7
+const MAP_DATA_SIZE: usize = 32 * 32 * 2 + 4 * 16 * 2;
+const PALETTES_OFFSET: usize = 32 * 32 * 2;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 224;
+
+/// The picture frame drawn around the game screen, as set up by `CHR_TRN` and `PCT_TRN`
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Border {
+    tiles: BoxedSlice<u8, TILE_DATA_SIZE>,
+    map: BoxedSlice<u8, MAP_DATA_SIZE>,
+}
+
+impl Border {
+    /// `CHR_TRN`, sets either the lower or upper 128 tiles
+    pub fn load_tiles(&mut self, upper: bool, data: &[u8]) {
+        let start = if upper { TILE_DATA_SIZE / 2 } else { 0 };
+        let len = data.len().min(TILE_DATA_SIZE / 2);
+        (*self.tiles)[start..start + len].copy_from_slice(&data[..len]);
+    }
+
+    /// `PCT_TRN`, sets the tile map and border palettes
+    pub fn load_map(&mut self, data: &[u8]) {
+        let len = data.len().min(MAP_DATA_SIZE);
+        (*self.map)[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn map_entry(&self, tile_x: usize, tile_y: usize) -> u16 {
+        let index = (tile_y * 32 + tile_x) * 2;
+        u16::from_le_bytes([self.map[index], self.map[index + 1]])
+    }
+
+    /// Color index (0-15) of a pixel in a 4bpp SNES tile
+    fn tile_pixel(&self, tile: usize, x: usize, y: usize) -> usize {
+        let tile = &(*self.tiles)[tile * 32..tile * 32 + 32];
+        let bit = 7 - x;
+
+        [
+            tile[y * 2],
+            tile[y * 2 + 1],
+            tile[16 + y * 2],
+            tile[16 + y * 2 + 1],
+        ]
+        .iter()
+        .enumerate()
+        .map(|(plane, byte)| (((byte >> bit) & 1) as usize) << plane)
+        .sum()
+    }
+
+    fn color(&self, palette: usize, index: usize) -> Rgb {
+        let offset = PALETTES_OFFSET + (palette * 16 + index) * 2;
+        Rgb::from_bgr555(u16::from_le_bytes([self.map[offset], self.map[offset + 1]]))
+    }
+
+    /// The border color at a screen position, or `None` if it's transparent there
+    pub fn pixel(&self, x: usize, y: usize) -> Option<Rgb> {
+        let entry = self.map_entry(x / 8, y / 8);
+        let (mut px, mut py) = (x % 8, y % 8);
+        if entry & 0x4000 != 0 {
+            px = 7 - px;
+        }
+        if entry & 0x8000 != 0 {
+            py = 7 - py;
+        }
+
+        let index = self.tile_pixel((entry & 0xFF) as usize, px, py);
+        if index == 0 {
+            return None;
+        }
+
+        // only palettes 4-7 are available to the border
+        let palette = ((entry >> 10) & 0b11) as usize;
+        Some(self.color(palette, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_4bpp_tiles_with_flips() {
+        let mut border = Border::default();
+
+        // tile 1: top left pixel uses color 0b1111, everything else is transparent
+        let mut tiles = vec![0; 64];
+        for offset in [32, 33, 48, 49] {
+            tiles[offset] = 0x80;
+        }
+        border.load_tiles(false, &tiles);
+
+        let mut map = vec![0; MAP_DATA_SIZE];
+        // tile (0, 0) is tile 1, palette 4. tile (1, 0) is tile 1 flipped both ways
+        map[0..2].copy_from_slice(&(1u16 | (4 << 10)).to_le_bytes());
+        map[2..4].copy_from_slice(&(1u16 | (4 << 10) | 0xC000).to_le_bytes());
+        // palette 4, color 15 is pure red
+        map[PALETTES_OFFSET + 30..PALETTES_OFFSET + 32].copy_from_slice(&0x001Fu16.to_le_bytes());
+        border.load_map(&map);
+
+        let red = Rgb { r: 255, g: 0, b: 0 };
+        assert_eq!(border.pixel(0, 0), Some(red));
+        assert_eq!(border.pixel(1, 0), None);
+        assert_eq!(border.pixel(15, 7), Some(red));
+        assert_eq!(border.pixel(8, 0), None);
+    }
+}
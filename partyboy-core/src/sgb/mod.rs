@@ -0,0 +1,535 @@
+//! Super Game Boy support.
+//!
+//! Carts that declare SGB support talk to the SNES side by sending command packets through
+//! the joypad register. This emulates the commands games actually rely on: palettes, screen
+//! attributes, screen masking, the picture frame border and multiplayer joypad polling.
+//! Sound commands and SNES code uploads are ignored.
+//!
+//! Bulk data (`*_TRN` commands) is sent by displaying it on screen, so those commands are
+//! completed once the next frame has been drawn.
+
+mod border;
+mod packet;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{cartridge::Cartridge, common::BoxedSlice, input::Input, ppu::rgb::Rgb};
+
+use self::{border::Border, packet::PacketReceiver};
+
+pub use border::{HEIGHT as SGB_SCREEN_HEIGHT, WIDTH as SGB_SCREEN_WIDTH};
+
+const GB_WIDTH: usize = 160;
+const GB_HEIGHT: usize = 144;
+/// Where the game screen sits inside the border
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+/// The game screen in 8x8 cells, which is the granularity palettes can be assigned at
+const CELLS_X: usize = 20;
+const CELLS_Y: usize = 18;
+
+const TRANSFER_SIZE: usize = 0x1000;
+const SYSTEM_PALETTES: usize = 512;
+const ATTRIBUTE_FILES: usize = 45;
+const ATTRIBUTE_FILE_SIZE: usize = CELLS_X * CELLS_Y / 4;
+
+/// The palette used until the game sets its own, in BGR555
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Mask {
+    None,
+    /// Keep showing the last frame
+    Freeze,
+    Black,
+    /// Fill the screen with color 0
+    Color0,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+enum Transfer {
+    SystemPalettes,
+    BorderTiles { upper: bool },
+    Border,
+    AttributeFiles,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct Sgb {
+    receiver: PacketReceiver,
+    joyp_lines: u8,
+    players: u8,
+    current_player: u8,
+
+    palettes: [[u16; 4]; 4],
+    system_palettes: BoxedSlice<u16, { SYSTEM_PALETTES * 4 }>,
+    attribute_files: BoxedSlice<u8, { ATTRIBUTE_FILES * ATTRIBUTE_FILE_SIZE }>,
+    /// Palette number of each cell on screen
+    attributes: BoxedSlice<u8, { CELLS_X * CELLS_Y }>,
+    mask: Mask,
+
+    pending_transfer: Option<Transfer>,
+    border: Border,
+
+    screen: BoxedSlice<Rgb, { GB_WIDTH * GB_HEIGHT }>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    output: BoxedSlice<Rgb, { SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT }>,
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Self {
+            receiver: PacketReceiver::default(),
+            joyp_lines: 0x30,
+            players: 1,
+            current_player: 0,
+
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: BoxedSlice::default(),
+            attribute_files: BoxedSlice::default(),
+            attributes: BoxedSlice::default(),
+            mask: Mask::None,
+
+            pending_transfer: None,
+            border: Border::default(),
+
+            screen: BoxedSlice::default(),
+            output: BoxedSlice::default(),
+        }
+    }
+
+    /// Whether a cartridge declares SGB support in its header
+    pub fn is_supported(cartridge: &Cartridge) -> bool {
+        cartridge.read_rom(0x146) == 0x03 && cartridge.read_rom(0x14B) == 0x33
+    }
+
+    pub fn output(&self) -> &[Rgb] {
+        &*self.output
+    }
+
+    pub fn write_joyp(&mut self, val: u8) {
+        let lines = val & 0b0011_0000;
+
+        // in multiplayer mode, every rising edge of P15 selects the next joypad
+        let p15_rising = lines & 0x20 != 0 && self.joyp_lines & 0x20 == 0;
+        if p15_rising && matches!(self.players, 2 | 4) {
+            self.current_player = (self.current_player + 1) % self.players;
+        }
+        self.joyp_lines = lines;
+
+        if let Some(command) = self.receiver.write(lines) {
+            self.handle_command(&command);
+        }
+    }
+
+    pub fn read_joyp(&self, input: &Input) -> u8 {
+        // with no buttons selected the low nibble holds the current joypad's id
+        if self.players > 1 && self.joyp_lines == 0x30 {
+            return 0b1100_0000 | (0x0F - self.current_player);
+        }
+
+        // only the first joypad is connected
+        if self.current_player != 0 {
+            return 0b1100_1111;
+        }
+
+        input.read_joyp()
+    }
+
+    fn handle_command(&mut self, command: &[u8]) {
+        let data = &command[1..];
+
+        match command[0] >> 3 {
+            0x00 => self.set_palettes(0, 1, data),
+            0x01 => self.set_palettes(2, 3, data),
+            0x02 => self.set_palettes(0, 3, data),
+            0x03 => self.set_palettes(1, 2, data),
+            0x04 => self.attr_blk(data),
+            0x05 => self.attr_lin(data),
+            0x06 => self.attr_div(data),
+            0x07 => self.attr_chr(data),
+            0x0A => self.pal_set(data),
+            0x0B => self.pending_transfer = Some(Transfer::SystemPalettes),
+            0x11 => {
+                self.players = (data[0] & 0b11) + 1;
+                self.current_player = 0;
+            }
+            0x13 => {
+                self.pending_transfer = Some(Transfer::BorderTiles {
+                    upper: data[0] & 1 != 0,
+                })
+            }
+            0x14 => self.pending_transfer = Some(Transfer::Border),
+            0x15 => self.pending_transfer = Some(Transfer::AttributeFiles),
+            0x16 => self.apply_attribute_file(data[0]),
+            0x17 => {
+                self.mask = match data[0] & 0b11 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    _ => Mask::Color0,
+                }
+            }
+            command => log::debug!("Ignoring SGB command {:#04X}", command),
+        }
+    }
+
+    /// `PAL01`, `PAL23`, `PAL03` and `PAL12`. Color 0 is shared between all palettes
+    fn set_palettes(&mut self, a: usize, b: usize, data: &[u8]) {
+        let color = |i: usize| u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
+
+        for palette in &mut self.palettes {
+            palette[0] = color(0);
+        }
+        for i in 1..4 {
+            self.palettes[a][i] = color(i);
+            self.palettes[b][i] = color(i + 3);
+        }
+    }
+
+    fn set_cell(&mut self, x: usize, y: usize, palette: u8) {
+        if x < CELLS_X && y < CELLS_Y {
+            self.attributes[y * CELLS_X + x] = palette & 0b11;
+        }
+    }
+
+    fn attr_blk(&mut self, data: &[u8]) {
+        let count = (data[0] as usize).min((data.len() - 1) / 6);
+
+        for set in data[1..].chunks_exact(6).take(count) {
+            let control = set[0];
+            let (inside, on_border, outside) =
+                (control & 1 != 0, control & 2 != 0, control & 4 != 0);
+            let palette_in = set[1] & 0b11;
+            let palette_out = (set[1] >> 4) & 0b11;
+            // with only the inside or outside flag set, the border takes the same palette
+            let palette_border = match (inside, on_border, outside) {
+                (true, false, false) => Some(palette_in),
+                (false, false, true) => Some(palette_out),
+                (_, true, _) => Some((set[1] >> 2) & 0b11),
+                _ => None,
+            };
+
+            let (x1, y1) = ((set[2] & 0x1F) as usize, (set[3] & 0x1F) as usize);
+            let (x2, y2) = ((set[4] & 0x1F) as usize, (set[5] & 0x1F) as usize);
+
+            for y in 0..CELLS_Y {
+                for x in 0..CELLS_X {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let edge = x == x1 || x == x2 || y == y1 || y == y2;
+
+                    let palette = match (within, edge) {
+                        (true, false) if inside => Some(palette_in),
+                        (true, true) => palette_border,
+                        (false, _) if outside => Some(palette_out),
+                        _ => None,
+                    };
+
+                    if let Some(palette) = palette {
+                        self.set_cell(x, y, palette);
+                    }
+                }
+            }
+        }
+    }
+
+    fn attr_lin(&mut self, data: &[u8]) {
+        let count = data[0] as usize;
+
+        for line in data[1..].iter().take(count) {
+            let position = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0b11;
+            let horizontal = line & 0x80 != 0;
+
+            if horizontal {
+                (0..CELLS_X).for_each(|x| self.set_cell(x, position, palette));
+            } else {
+                (0..CELLS_Y).for_each(|y| self.set_cell(position, y, palette));
+            }
+        }
+    }
+
+    fn attr_div(&mut self, data: &[u8]) {
+        let after = data[0] & 0b11;
+        let before = (data[0] >> 2) & 0b11;
+        let on_line = (data[0] >> 4) & 0b11;
+        let horizontal = data[0] & 0x40 != 0;
+        let position = (data[1] & 0x1F) as usize;
+
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let coordinate = if horizontal { y } else { x };
+                let palette = match coordinate.cmp(&position) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+                self.set_cell(x, y, palette);
+            }
+        }
+    }
+
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[0] as usize, data[1] as usize);
+        let count = u16::from_le_bytes([data[2], data[3]]) as usize;
+        let vertical = data[4] & 1 != 0;
+
+        let palettes = data[5..]
+            .iter()
+            .flat_map(|byte| [6, 4, 2, 0].map(|shift| (byte >> shift) & 0b11));
+
+        for palette in palettes.take(count.min(CELLS_X * CELLS_Y)) {
+            self.set_cell(x, y, palette);
+
+            if vertical {
+                y += 1;
+                if y >= CELLS_Y {
+                    y = 0;
+                    x = (x + 1) % CELLS_X;
+                }
+            } else {
+                x += 1;
+                if x >= CELLS_X {
+                    x = 0;
+                    y = (y + 1) % CELLS_Y;
+                }
+            }
+        }
+    }
+
+    /// `PAL_SET`, picks 4 of the system palettes sent with `PAL_TRN`
+    fn pal_set(&mut self, data: &[u8]) {
+        for (i, palette) in self.palettes.iter_mut().enumerate() {
+            let index = u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]) as usize & 0x1FF;
+            palette.copy_from_slice(&(*self.system_palettes)[index * 4..index * 4 + 4]);
+        }
+
+        let flags = data[8];
+        if flags & 0x80 != 0 {
+            self.apply_attribute_file(flags);
+        }
+        if flags & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    /// `ATTR_SET`, and the attribute file part of `PAL_SET`
+    fn apply_attribute_file(&mut self, flags: u8) {
+        let file = (flags & 0x3F) as usize;
+        if file < ATTRIBUTE_FILES {
+            let start = file * ATTRIBUTE_FILE_SIZE;
+            for (i, byte) in (*self.attribute_files)[start..start + ATTRIBUTE_FILE_SIZE]
+                .iter()
+                .enumerate()
+            {
+                for (j, shift) in [6, 4, 2, 0].into_iter().enumerate() {
+                    self.attributes[i * 4 + j] = (byte >> shift) & 0b11;
+                }
+            }
+        }
+
+        if flags & 0x40 != 0 {
+            self.mask = Mask::None;
+        }
+    }
+
+    /// Reads the 4KB a `*_TRN` command sends from the screen. The game displays tiles
+    /// 0-255 in order, so this re-encodes the first 256 tiles on screen as 2bpp data
+    fn transfer_data(shades: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(TRANSFER_SIZE);
+
+        for tile in 0..TRANSFER_SIZE / 16 {
+            let (tile_x, tile_y) = (tile % CELLS_X, tile / CELLS_X);
+            for row in 0..8 {
+                let start = (tile_y * 8 + row) * GB_WIDTH + tile_x * 8;
+                let (lo, hi) = shades[start..start + 8].iter().enumerate().fold(
+                    (0, 0),
+                    |(lo, hi), (px, shade)| {
+                        let bit = 7 - px;
+                        (lo | ((shade & 1) << bit), hi | (((shade >> 1) & 1) << bit))
+                    },
+                );
+                data.extend([lo, hi]);
+            }
+        }
+
+        data
+    }
+
+    fn finish_transfer(&mut self, transfer: Transfer, data: &[u8]) {
+        match transfer {
+            Transfer::SystemPalettes => {
+                for (color, bytes) in self.system_palettes.iter_mut().zip(data.chunks_exact(2)) {
+                    *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+            }
+            Transfer::BorderTiles { upper } => self.border.load_tiles(upper, data),
+            Transfer::Border => self.border.load_map(data),
+            Transfer::AttributeFiles => {
+                let len = self.attribute_files.len();
+                self.attribute_files.copy_from_slice(&data[..len]);
+            }
+        }
+    }
+
+    /// Called once a frame has been drawn. `shades` is the 2 bit color of every pixel after
+    /// the DMG palettes were applied. Colorizes `frame_buffer` in place and redraws the border
+    pub fn on_frame(&mut self, shades: &[u8], frame_buffer: &mut [Rgb]) {
+        if let Some(transfer) = self.pending_transfer.take() {
+            let data = Self::transfer_data(shades);
+            self.finish_transfer(transfer, &data);
+        }
+
+        let color = |palette: &[u16; 4]| palette.map(Rgb::from_bgr555);
+        let palettes = self.palettes.map(|palette| color(&palette));
+        let color_0 = palettes[0][0];
+
+        match self.mask {
+            Mask::None => {
+                for (i, px) in self.screen.iter_mut().enumerate() {
+                    let cell = (i / GB_WIDTH / 8) * CELLS_X + (i % GB_WIDTH) / 8;
+                    let shade = shades[i] as usize;
+                    *px = match shade {
+                        0 => color_0,
+                        _ => palettes[self.attributes[cell] as usize][shade],
+                    };
+                }
+            }
+            Mask::Freeze => {}
+            Mask::Black => self.screen.fill(Rgb::const_mono(0)),
+            Mask::Color0 => self.screen.fill(color_0),
+        }
+
+        frame_buffer.copy_from_slice(&*self.screen);
+        self.draw_output(color_0);
+    }
+
+    fn draw_output(&mut self, backdrop: Rgb) {
+        for y in 0..SGB_SCREEN_HEIGHT {
+            for x in 0..SGB_SCREEN_WIDTH {
+                let on_screen = (SCREEN_X..SCREEN_X + GB_WIDTH).contains(&x)
+                    && (SCREEN_Y..SCREEN_Y + GB_HEIGHT).contains(&y);
+
+                self.output[y * SGB_SCREEN_WIDTH + x] = if on_screen {
+                    self.screen[(y - SCREEN_Y) * GB_WIDTH + (x - SCREEN_X)]
+                } else {
+                    self.border.pixel(x, y).unwrap_or(backdrop)
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::packet::{PACKET_SIZE, tests::packet_writes};
+    use super::*;
+
+    fn send(sgb: &mut Sgb, command: u8, data: &[u8]) {
+        let packets = data.len().div_ceil(PACKET_SIZE - 1).max(1);
+        let mut bytes = vec![(command << 3) | packets as u8];
+        bytes.extend_from_slice(data);
+        bytes.resize(packets * PACKET_SIZE, 0);
+
+        sgb.write_joyp(0x30);
+        for packet in bytes.chunks_exact(PACKET_SIZE) {
+            for lines in packet_writes(packet.try_into().unwrap()) {
+                sgb.write_joyp(lines);
+            }
+        }
+    }
+
+    fn render(sgb: &mut Sgb, shades: &[u8]) -> Vec<Rgb> {
+        let mut frame = vec![Rgb::default(); GB_WIDTH * GB_HEIGHT];
+        sgb.on_frame(shades, &mut frame);
+        frame
+    }
+
+    #[test]
+    fn colors_screen_with_attribute_palettes() {
+        let mut sgb = Sgb::new();
+
+        // PAL01: color 0 black, palette 0 shade 1 red, palette 1 shade 1 blue
+        let mut pal01 = vec![0x00, 0x00, 0x1F, 0x00, 0, 0, 0, 0];
+        pal01.extend([0x00, 0x7C, 0, 0, 0, 0]);
+        send(&mut sgb, 0x00, &pal01);
+
+        // ATTR_DIV: everything right of cell column 10 uses palette 1
+        send(&mut sgb, 0x06, &[0b0000_0001, 10]);
+
+        let frame = render(&mut sgb, &[1; GB_WIDTH * GB_HEIGHT]);
+        let red = Rgb { r: 255, g: 0, b: 0 };
+        let blue = Rgb { r: 0, g: 0, b: 255 };
+        assert_eq!(frame[0], red);
+        assert_eq!(frame[11 * 8], blue);
+
+        let frame = render(&mut sgb, &[0; GB_WIDTH * GB_HEIGHT]);
+        assert_eq!(frame[11 * 8], Rgb::const_mono(0));
+    }
+
+    #[test]
+    fn attr_blk_colors_inside_and_border() {
+        let mut sgb = Sgb::new();
+
+        // one block from (2, 2) to (5, 5), inside palette 1, border palette 2
+        send(&mut sgb, 0x04, &[1, 0b011, 0b1001, 2, 2, 5, 5]);
+
+        assert_eq!(sgb.attributes[3 * CELLS_X + 3], 1);
+        assert_eq!(sgb.attributes[2 * CELLS_X + 4], 2);
+        assert_eq!(sgb.attributes[0], 0);
+    }
+
+    #[test]
+    fn multiplayer_cycles_joypad_ids() {
+        let mut sgb = Sgb::new();
+        let input = Input::new();
+
+        // MLT_REQ with 2 players
+        send(&mut sgb, 0x11, &[0x01]);
+        assert_eq!(sgb.read_joyp(&input) & 0x0F, 0x0F);
+
+        sgb.write_joyp(0x10);
+        sgb.write_joyp(0x30);
+        assert_eq!(sgb.read_joyp(&input) & 0x0F, 0x0E);
+
+        sgb.write_joyp(0x10);
+        sgb.write_joyp(0x30);
+        assert_eq!(sgb.read_joyp(&input) & 0x0F, 0x0F);
+    }
+
+    #[test]
+    fn transfers_system_palettes_from_screen() {
+        let mut sgb = Sgb::new();
+
+        // the first tile on screen starts with a row of shade 3, which encodes
+        // to bytes FF FF, so system palette 0 color 0 becomes 0xFFFF
+        let mut shades = vec![0; GB_WIDTH * GB_HEIGHT];
+        shades[..8].fill(3);
+
+        send(&mut sgb, 0x0B, &[]);
+        render(&mut sgb, &shades);
+        assert_eq!(sgb.system_palettes[0], 0xFFFF);
+        assert_eq!(sgb.system_palettes[1], 0);
+
+        // PAL_SET palette 0 to system palette 0
+        send(&mut sgb, 0x0A, &[0; 9]);
+        assert_eq!(sgb.palettes[0], [0xFFFF, 0, 0, 0]);
+    }
+
+    #[test]
+    fn mask_en_blanks_screen() {
+        let mut sgb = Sgb::new();
+
+        send(&mut sgb, 0x17, &[2]);
+        let frame = render(&mut sgb, &[3; GB_WIDTH * GB_HEIGHT]);
+        assert!(frame.iter().all(|px| *px == Rgb::const_mono(0)));
+        assert_eq!(
+            sgb.output()[SCREEN_Y * SGB_SCREEN_WIDTH + SCREEN_X],
+            Rgb::const_mono(0)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::common::BoxedSlice;
+
+pub const PACKET_SIZE: usize = 16;
+/// Commands are at most 7 packets long
+const MAX_COMMAND_SIZE: usize = PACKET_SIZE * 7;
+
+/// Receives command packets sent bit by bit through the joypad register.
+///
+/// A packet starts with a reset pulse (P14 and P15 both low), followed by 128 bits,
+/// LSB first, where pulling P14 low sends a 0 and pulling P15 low sends a 1. Each pulse
+/// has to be followed by both lines going high again. A packet ends with a 0 stop bit.
+/// Multi-packet commands keep accumulating until the length in the first byte is reached
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PacketReceiver {
+    command: BoxedSlice<u8, MAX_COMMAND_SIZE>,
+    bits_written: usize,
+
+    ready_for_pulse: bool,
+    ready_for_write: bool,
+    ready_for_stop: bool,
+}
+
+impl PacketReceiver {
+    fn command_bits(&self) -> usize {
+        let packets = (self.command[0] & 0b111).max(1) as usize;
+        packets * PACKET_SIZE * 8
+    }
+
+    fn clear(&mut self) {
+        self.bits_written = 0;
+        self.command.fill(0);
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        if self.bits_written == MAX_COMMAND_SIZE * 8 {
+            return;
+        }
+
+        if bit {
+            self.command[self.bits_written / 8] |= 1 << (self.bits_written % 8);
+        }
+
+        self.bits_written += 1;
+        self.ready_for_pulse = false;
+        self.ready_for_stop = self.bits_written % (PACKET_SIZE * 8) == 0;
+    }
+
+    /// Handles a write of the P14/P15 lines. Returns the whole command once its last
+    /// packet has been received
+    pub fn write(&mut self, lines: u8) -> Option<Vec<u8>> {
+        match lines & 0b0011_0000 {
+            // both high, between pulses
+            0x30 => self.ready_for_pulse = true,
+
+            // P14 low, a 0 bit (or the stop bit)
+            0x20 => {
+                if !self.ready_for_pulse || !self.ready_for_write {
+                    return None;
+                }
+
+                if !self.ready_for_stop {
+                    self.push_bit(false);
+                    return None;
+                }
+
+                self.ready_for_pulse = false;
+                self.ready_for_write = false;
+                self.ready_for_stop = false;
+
+                if self.bits_written == self.command_bits() {
+                    let command = (*self.command)[..self.bits_written / 8].to_vec();
+                    self.clear();
+                    return Some(command);
+                }
+            }
+
+            // P15 low, a 1 bit
+            0x10 => {
+                if !self.ready_for_pulse || !self.ready_for_write {
+                    return None;
+                }
+
+                if self.ready_for_stop {
+                    log::warn!("Corrupt SGB packet, expected a stop bit");
+                    self.ready_for_pulse = false;
+                    self.ready_for_write = false;
+                    self.clear();
+                    return None;
+                }
+
+                self.push_bit(true);
+            }
+
+            // both low, reset pulse
+            _ => {
+                if !self.ready_for_pulse {
+                    return None;
+                }
+
+                self.ready_for_write = true;
+                self.ready_for_pulse = false;
+
+                // a reset in the middle of a packet starts a new command
+                let mid_packet = self.bits_written % (PACKET_SIZE * 8) != 0;
+                if mid_packet || self.bits_written == 0 || self.ready_for_stop {
+                    self.clear();
+                    self.ready_for_stop = false;
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// The joypad writes a game makes to send `packet`
+    pub fn packet_writes(packet: &[u8; PACKET_SIZE]) -> Vec<u8> {
+        let mut writes = vec![0x00, 0x30];
+        for byte in packet {
+            for bit in 0..8 {
+                let line = if byte & (1 << bit) != 0 { 0x10 } else { 0x20 };
+                writes.extend([line, 0x30]);
+            }
+        }
+
+        writes.extend([0x20, 0x30]);
+        writes
+    }
+
+    #[test]
+    fn receives_single_packet_command() {
+        let mut packet = [0; PACKET_SIZE];
+        packet[0] = (0x11 << 3) | 1; // MLT_REQ
+        packet[1] = 0x01;
+
+        let mut receiver = PacketReceiver::default();
+        let commands = std::iter::once(0x30)
+            .chain(packet_writes(&packet))
+            .filter_map(|lines| receiver.write(lines))
+            .collect::<Vec<_>>();
+
+        assert_eq!(commands, [packet.to_vec()]);
+    }
+
+    #[test]
+    fn receives_multi_packet_command() {
+        let mut first = [0xAA; PACKET_SIZE];
+        first[0] = (0x04 << 3) | 2; // ATTR_BLK, 2 packets
+        let second = [0x55; PACKET_SIZE];
+
+        let mut receiver = PacketReceiver::default();
+        let writes = std::iter::once(0x30)
+            .chain(packet_writes(&first))
+            .chain(packet_writes(&second));
+
+        let commands = writes
+            .filter_map(|lines| receiver.write(lines))
+            .collect::<Vec<_>>();
+
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0][..PACKET_SIZE], first);
+        assert_eq!(commands[0][PACKET_SIZE..], second);
+    }
+}
@@ -0,0 +1,168 @@
+//! Symbol files, as produced by RGBDS (`rgblink -n`) and understood by no$gmb/BGB.
+//!
+//! Each line is `bank:addr label` with both numbers in hex, e.g. `01:4000 LoadLevel`.
+//! Blank lines, `;` comments and `[section]` headers are ignored.
+
+use std::collections::{BTreeMap, HashMap};
+
+use thiserror::Error;
+
+use crate::disasm::DecodedInstruction;
+
+#[derive(Error, Debug)]
+pub enum SymbolFileError {
+    #[error("Invalid symbol on line {line}: {contents:?}")]
+    InvalidLine { line: usize, contents: String },
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct SymbolTable {
+    by_location: BTreeMap<(usize, u16), String>,
+    by_name: HashMap<String, (usize, u16)>,
+}
+
+impl SymbolTable {
+    pub fn parse(contents: &str) -> Result<Self, SymbolFileError> {
+        let mut symbols = Self::default();
+
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+
+            let invalid = || SymbolFileError::InvalidLine {
+                line: index + 1,
+                contents: line.to_owned(),
+            };
+
+            let (location, label) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (bank, addr) = location.split_once(':').ok_or_else(invalid)?;
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| invalid())?;
+
+            symbols.insert(bank, addr, label.trim());
+        }
+
+        Ok(symbols)
+    }
+
+    /// Adds a symbol. If a location already has a label the first one is kept for display,
+    /// but both can still be looked up by name
+    pub fn insert(&mut self, bank: usize, addr: u16, label: &str) {
+        self.by_location
+            .entry((bank, addr))
+            .or_insert_with(|| label.to_owned());
+        self.by_name.insert(label.to_owned(), (bank, addr));
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// The label at `addr` in `bank`.
+    ///
+    /// Outside of the switchable ROM region, where the bank an address refers to is rarely
+    /// ambiguous, a label in any bank is returned if there's none in `bank`
+    pub fn lookup(&self, bank: usize, addr: u16) -> Option<&str> {
+        let bank = if addr < 0x4000 { 0 } else { bank };
+        if let Some(label) = self.by_location.get(&(bank, addr)) {
+            return Some(label);
+        }
+
+        if (0x4000..=0x7FFF).contains(&addr) {
+            return None;
+        }
+
+        self.by_location
+            .iter()
+            .find(|((_, a), _)| *a == addr)
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// The closest label at or before `addr` in the same bank, and how far past it `addr` is
+    pub fn nearest(&self, bank: usize, addr: u16) -> Option<(&str, u16)> {
+        let bank = if addr < 0x4000 { 0 } else { bank };
+        self.by_location
+            .range((bank, 0)..=(bank, addr))
+            .next_back()
+            .map(|((_, label_addr), label)| (label.as_str(), addr - label_addr))
+    }
+
+    /// The `(bank, addr)` of a label
+    pub fn find(&self, label: &str) -> Option<(usize, u16)> {
+        self.by_name.get(label).copied()
+    }
+
+    /// Iterates over all labels in address order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u16, &str)> {
+        self.by_location
+            .iter()
+            .map(|((bank, addr), label)| (*bank, *addr, label.as_str()))
+    }
+
+    /// Formats an instruction with any address operands replaced by labels.
+    /// Targets in the switchable ROM region are assumed to be in the same bank as the
+    /// instruction, which holds for everything except far calls through bank 0
+    pub fn format_instruction(&self, instruction: &DecodedInstruction) -> String {
+        instruction.format_with_symbols(|addr| self.lookup(instruction.bank, addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYM_FILE: &str = "; File generated by rgblink
+[labels]
+00:0000 RST_00
+00:0150 Main
+00:0158 Main.loop
+01:4000 LoadLevel
+02:4000 PlayMusic
+00:c000 wBuffer
+00:ff80 hTemp ; in HRAM
+";
+
+    #[test]
+    fn parses_rgbds_sym_file() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+
+        assert_eq!(symbols.len(), 7);
+        assert_eq!(symbols.lookup(0, 0x0150), Some("Main"));
+        assert_eq!(symbols.lookup(1, 0x4000), Some("LoadLevel"));
+        assert_eq!(symbols.lookup(2, 0x4000), Some("PlayMusic"));
+        assert_eq!(symbols.lookup(3, 0x4000), None);
+        assert_eq!(symbols.find("Main.loop"), Some((0, 0x0158)));
+        assert_eq!(symbols.find("hTemp"), Some((0, 0xFF80)));
+    }
+
+    #[test]
+    fn bank_0_and_ram_labels_ignore_bank() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+
+        // code running from bank 5 calling into bank 0
+        assert_eq!(symbols.lookup(5, 0x0150), Some("Main"));
+        assert_eq!(symbols.lookup(1, 0xC000), Some("wBuffer"));
+    }
+
+    #[test]
+    fn finds_nearest_label() {
+        let symbols = SymbolTable::parse(SYM_FILE).unwrap();
+
+        assert_eq!(symbols.nearest(0, 0x0155), Some(("Main", 5)));
+        assert_eq!(symbols.nearest(0, 0x0158), Some(("Main.loop", 0)));
+        assert_eq!(symbols.nearest(2, 0x4010), Some(("PlayMusic", 0x10)));
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let err = SymbolTable::parse("00:0150 Main\nnot a symbol\n").unwrap_err();
+
+        assert!(matches!(err, SymbolFileError::InvalidLine { line: 2, .. }));
+    }
+}
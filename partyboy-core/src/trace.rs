@@ -0,0 +1,273 @@
+//! Instruction trace logging.
+//!
+//! One line is written per executed instruction, with the CPU state as it was right
+//! before the instruction was fetched. The default format matches
+//! [gameboy-doctor](https://github.com/robert/gameboy-doctor), so traces can be diffed
+//! against other emulators:
+//!
+//! ```text
+//! A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//! ```
+//!
+//! Note that gameboy-doctor's reference logs expect a DMG boot state and `LY` to always
+//! read `$90`, so they only line up with a run made after
+//! [`GameBoy::enable_gameboy_doctor_mode`](crate::GameBoy::enable_gameboy_doctor_mode).
+//! [`TraceFormat::Detailed`] adds the cycle count, `LY`, the mapped bank and the
+//! disassembled instruction, which is more useful when comparing against this emulator's
+//! own known-good traces.
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    bus::Bus,
+    cpu::InstructionCache,
+    disasm::{self, DecodedInstruction},
+    symbols::SymbolTable,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TraceFormat {
+    #[default]
+    GameboyDoctor,
+    Detailed,
+}
+
+/// CPU registers at the start of an instruction
+pub(crate) struct TraceRegisters {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+}
+
+pub struct Tracer {
+    format: TraceFormat,
+    writer: Box<dyn Write + Send>,
+    symbols: Option<SymbolTable>,
+    cycles: u64,
+}
+
+impl Tracer {
+    pub fn new(format: TraceFormat, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            format,
+            writer: Box::new(writer),
+            symbols: None,
+            cycles: 0,
+        }
+    }
+
+    /// Labels to annotate [`TraceFormat::Detailed`] lines with
+    pub fn with_symbols(self, symbols: SymbolTable) -> Self {
+        Self {
+            symbols: Some(symbols),
+            ..self
+        }
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    /// Number of cycles since the tracer was attached
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.cycles += 1;
+    }
+
+    pub(crate) fn trace(
+        &mut self,
+        regs: &TraceRegisters,
+        pc: u16,
+        bus: &Bus,
+        instruction_cache: &InstructionCache,
+    ) -> std::io::Result<()> {
+        let [a, f] = regs.af.to_be_bytes();
+        let [b, c] = regs.bc.to_be_bytes();
+        let [d, e] = regs.de.to_be_bytes();
+        let [h, l] = regs.hl.to_be_bytes();
+        let pcmem = [0, 1, 2, 3].map(|i| bus.peek_u8(pc.wrapping_add(i)));
+
+        write!(
+            self.writer,
+            "A:{a:02X} F:{f:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} SP:{:04X} PC:{pc:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.sp, pcmem[0], pcmem[1], pcmem[2], pcmem[3]
+        )?;
+
+        if self.format == TraceFormat::Detailed {
+            let bank = bus.mapped_bank(pc);
+            let instruction =
+                disasm::decode(instruction_cache, bank, pc, &|addr| bus.peek_u8(addr));
+
+            write!(
+                self.writer,
+                " CY:{} LY:{:02X} BANK:{bank:02X} | {}",
+                self.cycles,
+                bus.ppu.ly,
+                self.describe(&instruction)
+            )?;
+        }
+
+        writeln!(self.writer)
+    }
+
+    fn describe(&self, instruction: &DecodedInstruction) -> String {
+        let Some(symbols) = &self.symbols else {
+            return instruction.to_string();
+        };
+
+        let text = symbols.format_instruction(instruction);
+        match symbols.nearest(instruction.bank, instruction.addr) {
+            Some((label, 0)) => format!("{label}: {text}"),
+            Some((label, offset)) => format!("{label}+${offset:X}: {text}"),
+            None => text,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct TraceMismatch {
+    /// 1-based line number of the first differing line
+    pub line: usize,
+    /// `None` if the trace ended early
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// Compares two traces line by line and returns the first difference, if any.
+/// Lines are only compared up to the length of the shorter line, so a detailed trace
+/// can be checked against a gameboy-doctor one
+pub fn compare(expected: &str, actual: &str) -> Option<TraceMismatch> {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return None,
+            (Some(e), Some(a)) if e.starts_with(a) || a.starts_with(e) => {}
+            (expected, actual) => {
+                return Some(TraceMismatch {
+                    line,
+                    expected: expected.map(str::to_owned),
+                    actual: actual.map(str::to_owned),
+                });
+            }
+        }
+    }
+}
+
+/// A trace sink that can be read back while the tracer still owns a clone of it
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Takes everything written so far
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameBoy, cartridge::rom::create_test_rom};
+
+    fn trace_program(program: &[u8], format: TraceFormat, ticks: usize) -> Vec<String> {
+        let mut bios = [0; 0x900];
+        bios[..program.len()].copy_from_slice(program);
+
+        let mut gb = GameBoy::new(None, bios, Bus::get_handle_blargg_output());
+        gb.bus.cartridge = Some(create_test_rom());
+
+        let buffer = SharedBuffer::default();
+        gb.set_tracer(Some(Tracer::new(format, buffer.clone())));
+        for _ in 0..ticks {
+            gb.tick();
+        }
+
+        String::from_utf8(buffer.take())
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn writes_gameboy_doctor_lines() {
+        // LD A, $42; LD BC, $1234; NOP
+        let program = [0x3E, 0x42, 0x01, 0x34, 0x12, 0x00];
+        let lines = trace_program(&program, TraceFormat::GameboyDoctor, 8 + 12 + 4);
+
+        assert_eq!(
+            lines,
+            [
+                "A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0000 PCMEM:3E,42,01,34",
+                "A:42 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0002 PCMEM:01,34,12,00",
+                "A:42 F:00 B:12 C:34 D:00 E:00 H:00 L:00 SP:0000 PC:0005 PCMEM:00,00,00,00",
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_detailed_lines() {
+        let lines = trace_program(&[0xC3, 0x50, 0x01], TraceFormat::Detailed, 16 + 4);
+
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines[0].ends_with("CY:1 LY:00 BANK:00 | JP $0150"),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].contains("PC:0150"), "{}", lines[1]);
+        assert!(lines[1].contains("CY:17 "), "{}", lines[1]);
+    }
+
+    #[test]
+    fn compare_reports_first_difference() {
+        let expected = "A:01 PC:0100\nA:01 PC:0101\nA:02 PC:0102\n";
+
+        assert_eq!(compare(expected, expected), None);
+        assert_eq!(
+            compare(
+                expected,
+                "A:01 PC:0100 CY:0\nA:01 PC:0101 CY:4\nA:03 PC:0102 CY:8\n"
+            ),
+            Some(TraceMismatch {
+                line: 3,
+                expected: Some("A:02 PC:0102".to_owned()),
+                actual: Some("A:03 PC:0102 CY:8".to_owned()),
+            })
+        );
+        assert_eq!(
+            compare(expected, "A:01 PC:0100\n"),
+            Some(TraceMismatch {
+                line: 2,
+                expected: Some("A:01 PC:0101".to_owned()),
+                actual: None,
+            })
+        );
+    }
+}
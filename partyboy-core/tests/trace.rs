@@ -0,0 +1,84 @@
+use partyboy_core::{
+    CYCLES_PER_FRAME, GameBoy,
+    trace::{self, SharedBuffer, TraceFormat, Tracer},
+};
+use std::path::PathBuf;
+
+/// Way more than any of the roms need to finish
+const MAX_FRAMES: usize = 60 * 60;
+
+fn get_root_path() -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.pop();
+    path.push("test");
+    path
+}
+
+/// Runs a rom in gameboy-doctor mode and diffs its trace against the reference one as it's
+/// written, a frame at a time. Reference traces are downloaded by
+/// `scripts/download_test_roms.py` to `test/test_expected/traces/<name>.log`
+macro_rules! define_trace_test {
+    ($($name:ident, $rom:expr, $trace:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let root = get_root_path();
+                let rom = std::fs::read(root.join("test_roms").join($rom)).unwrap();
+                let expected =
+                    std::fs::read_to_string(root.join("test_expected/traces").join($trace))
+                        .unwrap();
+                let mut expected = expected.lines().enumerate().peekable();
+
+                let mut gb = GameBoy::builder().rom(rom).build().unwrap();
+                gb.enable_gameboy_doctor_mode();
+                let buffer = SharedBuffer::default();
+                gb.set_tracer(Some(Tracer::new(TraceFormat::GameboyDoctor, buffer.clone())));
+
+                // the end of the last frame's output, if it stopped halfway through a line
+                let mut partial = String::new();
+                for _ in 0..MAX_FRAMES {
+                    if expected.peek().is_none() {
+                        return;
+                    }
+
+                    for _ in 0..CYCLES_PER_FRAME {
+                        gb.tick();
+                    }
+
+                    partial.push_str(&String::from_utf8(buffer.take()).unwrap());
+                    let complete = partial.rfind('\n').map_or(0, |i| i + 1);
+                    for actual in partial[..complete].lines() {
+                        let Some((i, expected)) = expected.next() else {
+                            return;
+                        };
+                        if let Some(mismatch) = trace::compare(expected, actual) {
+                            panic!(
+                                "Trace diverged on line {}\nexpected: {:?}\n  actual: {:?}",
+                                i + 1,
+                                mismatch.expected,
+                                mismatch.actual
+                            );
+                        }
+                    }
+                    partial.drain(..complete);
+                }
+
+                panic!("Trace ended after {} lines", expected.next().unwrap().0);
+            }
+        )*
+    };
+}
+
+define_trace_test! {
+    cpu_instrs_01, "blargg/cpu_instrs/individual/01-special.gb", "cpu_instrs/1.log",
+    cpu_instrs_02, "blargg/cpu_instrs/individual/02-interrupts.gb", "cpu_instrs/2.log",
+    cpu_instrs_03, "blargg/cpu_instrs/individual/03-op sp,hl.gb", "cpu_instrs/3.log",
+    cpu_instrs_04, "blargg/cpu_instrs/individual/04-op r,imm.gb", "cpu_instrs/4.log",
+    cpu_instrs_05, "blargg/cpu_instrs/individual/05-op rp.gb", "cpu_instrs/5.log",
+    cpu_instrs_06, "blargg/cpu_instrs/individual/06-ld r,r.gb", "cpu_instrs/6.log",
+    cpu_instrs_07, "blargg/cpu_instrs/individual/07-jr,jp,call,ret,rst.gb", "cpu_instrs/7.log",
+    cpu_instrs_08, "blargg/cpu_instrs/individual/08-misc instrs.gb", "cpu_instrs/8.log",
+    cpu_instrs_09, "blargg/cpu_instrs/individual/09-op r,r.gb", "cpu_instrs/9.log",
+    cpu_instrs_10, "blargg/cpu_instrs/individual/10-bit ops.gb", "cpu_instrs/10.log",
+    cpu_instrs_11, "blargg/cpu_instrs/individual/11-op a,(hl).gb", "cpu_instrs/11.log",
+}
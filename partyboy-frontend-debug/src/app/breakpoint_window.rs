@@ -0,0 +1,121 @@
+use eframe::egui::{self, Ui};
+use partyboy_core::debug::breakpoint::{BreakReason, Breakpoint, Opcode, WatchAccess};
+
+use crate::MessageToGB;
+
+use super::DebuggerApp;
+
+fn parse_addr(input: &str) -> Option<u16> {
+    let input = input.trim();
+    let input = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix('$'))
+        .unwrap_or(input);
+
+    u16::from_str_radix(input, 16).ok()
+}
+
+impl DebuggerApp {
+    /// Resolves the input as a hex address or a symbol name. Symbols in the switchable
+    /// rom region also return their bank, so the breakpoint only fires in that bank
+    fn parse_breakpoint_target(&self, input: &str) -> Option<(u16, Option<usize>)> {
+        if let Some(addr) = parse_addr(input) {
+            return Some((addr, None));
+        }
+
+        let (bank, addr) = self.symbols.find(input.trim())?;
+        let bank = (0x4000..=0x7FFF).contains(&addr).then_some(bank);
+        Some((addr, bank))
+    }
+
+    fn breakpoint_label(&self, breakpoint: &Breakpoint) -> Option<&str> {
+        match breakpoint {
+            Breakpoint::Execution { addr, bank, .. } => {
+                self.symbols.lookup(bank.unwrap_or(0), *addr)
+            }
+            Breakpoint::Watch { range, .. } => self.label_at(*range.start()),
+            _ => None,
+        }
+    }
+
+    pub(super) fn show_breakpoint_window(&mut self, ctx: &egui::Context) {
+        if !self.toggle_state.breakpoints {
+            return;
+        }
+
+        egui::Window::new("Breakpoints")
+            .default_width(250.0)
+            .resizable(false)
+            .show(ctx, |ui| {
+                self.render_breakpoint_window_display(ui);
+            });
+    }
+
+    fn render_breakpoint_window_display(&mut self, ui: &mut Ui) {
+        if let Some(reason) = &self.break_reason {
+            let label = match reason {
+                BreakReason::Execution { addr, .. }
+                | BreakReason::Watch { addr, .. }
+                | BreakReason::Opcode { addr, .. } => self.label_at(*addr),
+                _ => None,
+            };
+
+            match label {
+                Some(label) => ui.label(format!("Stopped: {} ({})", reason, label)),
+                None => ui.label(format!("Stopped: {}", reason)),
+            };
+            ui.separator();
+        }
+
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.breakpoint_input);
+
+            let target = self.parse_breakpoint_target(&self.breakpoint_input);
+            if ui.button("Break").clicked() {
+                if let Some((addr, bank)) = target {
+                    self.add_breakpoint(Breakpoint::Execution {
+                        addr,
+                        bank,
+                        condition: None,
+                    });
+                }
+            }
+
+            if ui.button("Watch").clicked() {
+                if let Some((addr, _)) = target {
+                    self.add_breakpoint(Breakpoint::Watch {
+                        range: addr..=addr,
+                        access: WatchAccess::ReadWrite,
+                    });
+                }
+            }
+        });
+
+        if ui.button("Break on LD B,B").clicked() {
+            self.add_breakpoint(Breakpoint::Opcode(Opcode::SOFTWARE_BREAKPOINT));
+        }
+
+        ui.separator();
+
+        let mut to_remove = None;
+        for (id, breakpoint) in &self.breakpoints {
+            ui.horizontal(|ui| {
+                if ui.small_button("x").clicked() {
+                    to_remove = Some(*id);
+                }
+                match self.breakpoint_label(breakpoint) {
+                    Some(label) => ui.label(format!("{} {} ({})", id, breakpoint, label)),
+                    None => ui.label(format!("{} {}", id, breakpoint)),
+                };
+            });
+        }
+
+        if let Some(id) = to_remove {
+            let _ = self.to_gb_tx.send(MessageToGB::RemoveBreakpoint(id));
+        }
+    }
+
+    fn add_breakpoint(&self, breakpoint: Breakpoint) {
+        let _ = self.to_gb_tx.send(MessageToGB::AddBreakpoint(breakpoint));
+    }
+}
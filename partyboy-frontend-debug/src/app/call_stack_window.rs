@@ -0,0 +1,42 @@
+use eframe::egui::{self, RichText, Ui};
+
+use super::DebuggerApp;
+
+impl DebuggerApp {
+    pub(super) fn show_call_stack_window(&mut self, ctx: &egui::Context) {
+        if !self.toggle_state.call_stack {
+            return;
+        }
+
+        egui::Window::new("Call Stack")
+            .default_width(250.0)
+            .show(ctx, |ui| {
+                self.render_call_stack_window_display(ui);
+            });
+    }
+
+    fn render_call_stack_window_display(&self, ui: &mut Ui) {
+        let frames = &self.gb_debug_info.call_stack;
+        if frames.is_empty() {
+            ui.label("No calls");
+            return;
+        }
+
+        // innermost call first, like most debuggers
+        for frame in frames.iter().rev() {
+            let target = match self.symbols.nearest(frame.bank, frame.target) {
+                Some((label, 0)) => label.to_owned(),
+                Some((label, offset)) => format!("{}+${:X}", label, offset),
+                None => format!("{:02X}:{:04X}", frame.bank, frame.target),
+            };
+
+            ui.label(
+                RichText::new(format!(
+                    "{:<24} {:?}, returns to {:04X}",
+                    target, frame.kind, frame.return_addr
+                ))
+                .monospace(),
+            );
+        }
+    }
+}
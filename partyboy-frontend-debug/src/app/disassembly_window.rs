@@ -0,0 +1,65 @@
+use eframe::egui::{self, Align, RichText, Ui};
+use partyboy_core::{disasm::DecodedInstruction, symbols::SymbolTable};
+
+use super::DebuggerApp;
+
+fn format_line(instruction: &DecodedInstruction, symbols: &SymbolTable) -> String {
+    let bytes = instruction
+        .bytes()
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "{:02X}:{:04X}  {:<8}  {}",
+        instruction.bank,
+        instruction.addr,
+        bytes,
+        symbols.format_instruction(instruction)
+    )
+}
+
+impl DebuggerApp {
+    pub(super) fn show_disassembly_window(&mut self, ctx: &egui::Context) {
+        if !self.toggle_state.disassembly {
+            return;
+        }
+
+        egui::Window::new("Disassembly")
+            .default_width(320.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                self.render_disassembly_window_display(ui);
+            });
+    }
+
+    fn render_disassembly_window_display(&mut self, ui: &mut Ui) {
+        let pc = self.gb_debug_info.registers.pc;
+        let scroll_to_pc = self.disassembly_scrolled_to != Some(pc);
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                for instruction in &self.gb_debug_info.disassembly {
+                    if let Some(label) = self.symbols.lookup(instruction.bank, instruction.addr) {
+                        ui.label(RichText::new(format!("{}:", label)).monospace().weak());
+                    }
+
+                    let line = RichText::new(format_line(instruction, &self.symbols)).monospace();
+                    if instruction.addr != pc {
+                        ui.label(line);
+                        continue;
+                    }
+
+                    let response = ui.label(line.strong().color(egui::Color32::YELLOW));
+                    if scroll_to_pc {
+                        response.scroll_to_me(Some(Align::Center));
+                    }
+                }
+            });
+
+        // only follow the PC when it moves, so the view can be scrolled while stopped
+        self.disassembly_scrolled_to = Some(pc);
+    }
+}
@@ -12,11 +12,16 @@ impl DebuggerApp {
                 if ui.button("Open").clicked() {
                     self.handle_open();
                 }
+
+                if ui.button("Load symbols").clicked() {
+                    self.handle_load_symbols();
+                }
             });
 
             ui.separator();
 
             if ui.button("Start").clicked() {
+                self.break_reason = None;
                 _ = self.to_gb_tx.send(MessageToGB::Start);
             }
 
@@ -30,6 +35,9 @@ impl DebuggerApp {
             ui.toggle_value(&mut self.toggle_state.palletes, "Palletes");
             ui.toggle_value(&mut self.toggle_state.tile, "Tiles");
             ui.toggle_value(&mut self.toggle_state.maps, "BG Map");
+            ui.toggle_value(&mut self.toggle_state.breakpoints, "Breakpoints");
+            ui.toggle_value(&mut self.toggle_state.disassembly, "Disassembly");
+            ui.toggle_value(&mut self.toggle_state.call_stack, "Call Stack");
         });
     }
 
@@ -40,4 +48,12 @@ impl DebuggerApp {
                 .send(MessageToGB::New(rom_path.to_str().unwrap().to_owned()));
         }
     }
+
+    fn handle_load_symbols(&mut self) {
+        if let Some(sym_path) = FileDialog::new().add_filter("sym", &["sym"]).pick_file() {
+            let _ = self.to_gb_tx.send(MessageToGB::LoadSymbols(
+                sym_path.to_str().unwrap().to_owned(),
+            ));
+        }
+    }
 }
@@ -1,12 +1,17 @@
 use crossbeam::channel::{Receiver, Sender};
 use eframe::egui;
+use partyboy_core::debug::breakpoint::{BreakReason, Breakpoint, BreakpointId};
 use partyboy_core::ppu::rgb::Rgb;
+use partyboy_core::symbols::SymbolTable;
 use partyboy_core::{debug::GBDebugInfo, input::Keycode};
 
 use crate::{channel_log::Log, MessageFromGb, MessageToGB};
 
 use self::tile_window::TileBankState;
 
+mod breakpoint_window;
+mod call_stack_window;
+mod disassembly_window;
 mod gb_display;
 mod log_window;
 mod map_window;
@@ -38,6 +43,9 @@ pub struct ToggleState {
     tile_bank: TileBankState,
     tile: bool,
     maps: bool,
+    breakpoints: bool,
+    disassembly: bool,
+    call_stack: bool,
 }
 
 impl Default for ToggleState {
@@ -48,6 +56,9 @@ impl Default for ToggleState {
             tile: true,
             tile_bank: TileBankState::Bank0,
             maps: true,
+            breakpoints: true,
+            disassembly: true,
+            call_stack: true,
         }
     }
 }
@@ -58,6 +69,13 @@ pub struct DebuggerApp {
     gb_debug_info: Box<GBDebugInfo>,
     fps: f64,
 
+    breakpoint_input: String,
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+    break_reason: Option<BreakReason>,
+
+    disassembly_scrolled_to: Option<u16>,
+    symbols: SymbolTable,
+
     toggle_state: ToggleState,
 
     log_rx: Receiver<Log>,
@@ -77,6 +95,11 @@ impl DebuggerApp {
             logs: Vec::new(),
             gb_debug_info: Box::<GBDebugInfo>::default(),
             fps: 0.0,
+            breakpoint_input: String::new(),
+            breakpoints: Vec::new(),
+            break_reason: None,
+            disassembly_scrolled_to: None,
+            symbols: SymbolTable::default(),
             toggle_state: ToggleState::default(),
             log_rx,
             to_gb_tx,
@@ -101,6 +124,20 @@ impl DebuggerApp {
         let _ = self.to_gb_tx.send(MessageToGB::KeyUp(key_ups));
     }
 
+    /// Label at `addr`. Only an address in the current disassembly knows which rom bank
+    /// it's in, anything else in the switchable rom region won't resolve
+    fn label_at(&self, addr: u16) -> Option<&str> {
+        let bank = self
+            .gb_debug_info
+            .disassembly
+            .iter()
+            .find(|instruction| instruction.addr == addr)
+            .map(|instruction| instruction.bank)
+            .unwrap_or(0);
+
+        self.symbols.lookup(bank, addr)
+    }
+
     fn into_input(key: &egui::Key) -> Option<InputType> {
         match key {
             egui::Key::W => Some(InputType::GBInput(Keycode::Up)),
@@ -123,6 +160,9 @@ impl eframe::App for DebuggerApp {
             match msg {
                 MessageFromGb::Draw(fb) => self.gb_frame_buffer = Some(fb),
                 MessageFromGb::DebugInfo(debug_info) => self.gb_debug_info = debug_info,
+                MessageFromGb::BreakpointHit(reason) => self.break_reason = Some(reason),
+                MessageFromGb::Breakpoints(breakpoints) => self.breakpoints = breakpoints,
+                MessageFromGb::Symbols(symbols) => self.symbols = symbols,
             }
         }
 
@@ -137,11 +177,13 @@ impl eframe::App for DebuggerApp {
         self.show_palette_window(ctx);
         self.show_tile_window(ctx);
         self.show_map_window(ctx);
+        self.show_breakpoint_window(ctx);
+        self.show_disassembly_window(ctx);
+        self.show_call_stack_window(ctx);
 
         // TODO:
         // - Tile/Map/Sprite viewer
         // - Memory Viewer
-        // - Dissassembly
         // - Rom selector?
         // - ppu event viewer
         // - SideBar of general info? (speed mode, are we in hdma, fps, etc)
@@ -1,11 +1,20 @@
-use std::{env, time::Duration};
+use std::{env, net::TcpListener, path::Path, time::Duration};
 
 use app::DebuggerApp;
 use channel_log::ChannelLog;
+use clap::Parser;
 use crossbeam::channel::{Receiver, Sender};
 use eframe::{egui::Context, emath::Vec2, NativeOptions};
 use messages::{MessageFromGb, MessageToGB};
-use partyboy_core::{builder::GameBoyBuilder, GameBoy};
+use partyboy_core::{
+    builder::GameBoyBuilder,
+    debug::{
+        breakpoint::Breakpoint,
+        gdb::{GdbEvent, GdbStub},
+    },
+    symbols::SymbolTable,
+    GameBoy,
+};
 use spin_sleep_util::{MissedTickBehavior, RateReporter};
 
 mod app;
@@ -14,8 +23,22 @@ mod messages;
 
 pub static mut CYCLE_COUNT: u64 = 0;
 
-fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, ctx: Context) -> ! {
+#[derive(Parser, Debug)]
+#[clap(about = "Partyboy debugger")]
+struct Args {
+    /// Listens for gdb on the given port, connect with `target remote localhost:<port>`
+    #[clap(long)]
+    gdb: Option<u16>,
+}
+
+fn gb_loop(
+    to_gb_rx: Receiver<MessageToGB>,
+    from_gb_tx: Sender<MessageFromGb>,
+    ctx: Context,
+    gdb_listener: Option<TcpListener>,
+) -> ! {
     let mut gb: Option<GameBoy> = None;
+    let mut gdb: Option<GdbStub> = None;
 
     let mut interval =
         spin_sleep_util::interval(Duration::from_millis((1000.0f64 / 59.73f64) as u64))
@@ -30,7 +53,21 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
         for msg in inbound_messages {
             match msg {
                 MessageToGB::New(rom_path) => {
-                    drop(gb);
+                    // keep breakpoints across rom loads
+                    let breakpoints: Vec<Breakpoint> = gb
+                        .take()
+                        .map(|gb| gb.breakpoints().iter().map(|(_, bp)| bp.clone()).collect())
+                        .unwrap_or_default();
+
+                    // pick up symbols sitting next to the rom, e.g. game.gb + game.sym
+                    let sym_path = Path::new(&rom_path).with_extension("sym");
+                    let symbols = if sym_path.exists() {
+                        load_symbols(&sym_path).unwrap_or_default()
+                    } else {
+                        SymbolTable::default()
+                    };
+                    let _ = from_gb_tx.send(MessageFromGb::Symbols(symbols));
+
                     // TODO: handle saving
                     let rom = std::fs::read(rom_path).expect("Unable to read rom path");
                     let bios = include_bytes!("../../bin/_cgb_boot.bin");
@@ -41,6 +78,13 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
                         .map_err(|e| log::error!("{}", e))
                         .ok();
 
+                    if let Some(gb) = &mut gb {
+                        for breakpoint in breakpoints {
+                            gb.breakpoints_mut().add(breakpoint);
+                        }
+                        send_breakpoints(gb, &from_gb_tx);
+                    }
+
                     unsafe { CYCLE_COUNT = 0 }
                 }
                 MessageToGB::Start => {
@@ -49,6 +93,13 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
                 }
                 MessageToGB::Stop => {
                     run = false;
+
+                    if let Some(stub) = gdb.as_mut().filter(|stub| stub.is_running()) {
+                        if let Err(e) = stub.notify_stopped(None) {
+                            log::error!("Lost connection to gdb: {}", e);
+                            gdb = None;
+                        }
+                    }
                 }
                 MessageToGB::KeyDown(keys) => {
                     use eframe::egui::Key;
@@ -63,6 +114,24 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
                         });
                     }
                 }
+                MessageToGB::AddBreakpoint(breakpoint) => match &mut gb {
+                    Some(gb) => {
+                        gb.breakpoints_mut().add(breakpoint);
+                        send_breakpoints(gb, &from_gb_tx);
+                    }
+                    None => log::warn!("Load a rom before adding breakpoints"),
+                },
+                MessageToGB::RemoveBreakpoint(id) => {
+                    if let Some(gb) = &mut gb {
+                        gb.breakpoints_mut().remove(id);
+                        send_breakpoints(gb, &from_gb_tx);
+                    }
+                }
+                MessageToGB::LoadSymbols(path) => {
+                    if let Some(symbols) = load_symbols(Path::new(&path)) {
+                        let _ = from_gb_tx.send(MessageFromGb::Symbols(symbols));
+                    }
+                }
                 MessageToGB::KeyUp(keys) => {
                     use eframe::egui::Key;
                     if let Some(gb) = &mut gb {
@@ -80,11 +149,59 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
             }
         }
 
+        if let (Some(listener), None) = (&gdb_listener, &gdb) {
+            gdb = accept_gdb(listener);
+            if gdb.is_some() {
+                // gdb expects the target to be stopped when it attaches
+                run = false;
+            }
+        }
+
+        if let (Some(stub), Some(gb)) = (&mut gdb, &mut gb) {
+            match stub.poll(gb) {
+                Ok(GdbEvent::None) => {}
+                Ok(GdbEvent::Continue) => {
+                    interval.reset();
+                    run = true;
+                }
+                Ok(GdbEvent::Stopped) => {
+                    run = false;
+                    send_stopped_state(gb, &from_gb_tx, &ctx);
+                }
+                Ok(GdbEvent::Detached) => {
+                    log::info!("gdb detached");
+                    gdb = None;
+                    send_breakpoints(gb, &from_gb_tx);
+                }
+                Err(e) => {
+                    log::error!("Lost connection to gdb: {}", e);
+                    gdb = None;
+                }
+            }
+        }
+
         if run {
             if let Some(gb) = &mut gb {
                 loop {
                     unsafe { CYCLE_COUNT += 1 }
                     gb.tick();
+
+                    if let Some(reason) = gb.take_break_reason() {
+                        log::info!("{}", reason);
+                        run = false;
+
+                        if let Some(stub) = &mut gdb {
+                            if let Err(e) = stub.notify_stopped(Some(reason)) {
+                                log::error!("Lost connection to gdb: {}", e);
+                                gdb = None;
+                            }
+                        }
+
+                        let _ = from_gb_tx.send(MessageFromGb::BreakpointHit(reason));
+                        send_stopped_state(gb, &from_gb_tx, &ctx);
+                        break;
+                    }
+
                     if gb.consume_draw_flag() {
                         let _ =
                             from_gb_tx.send(MessageFromGb::Draw(gb.get_frame_buffer().to_vec()));
@@ -106,13 +223,60 @@ fn gb_loop(to_gb_rx: Receiver<MessageToGB>, from_gb_tx: Sender<MessageFromGb>, c
                     interval.tick();
                 }
             }
+        } else if gdb.is_some() {
+            // stay responsive to gdb while stopped
+            std::thread::sleep(Duration::from_millis(1));
         } else {
             std::thread::sleep(Duration::from_millis(100));
         }
     }
 }
 
+fn send_stopped_state(gb: &GameBoy, from_gb_tx: &Sender<MessageFromGb>, ctx: &Context) {
+    let _ = from_gb_tx.send(MessageFromGb::Draw(gb.get_frame_buffer().to_vec()));
+    let _ = from_gb_tx.send(MessageFromGb::DebugInfo(Box::new(gb.debug_info())));
+    // gdb may have added or removed breakpoints behind the ui's back
+    send_breakpoints(gb, from_gb_tx);
+    ctx.request_repaint();
+}
+
+fn accept_gdb(listener: &TcpListener) -> Option<GdbStub> {
+    match listener.accept() {
+        Ok((stream, addr)) => {
+            log::info!("gdb connected from {}", addr);
+            GdbStub::new(stream)
+                .map_err(|e| log::error!("Unable to set up gdb connection: {}", e))
+                .ok()
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => None,
+        Err(e) => {
+            log::error!("Unable to accept gdb connection: {}", e);
+            None
+        }
+    }
+}
+
+fn send_breakpoints(gb: &GameBoy, from_gb_tx: &Sender<MessageFromGb>) {
+    let breakpoints = gb.breakpoints().iter().cloned().collect();
+    let _ = from_gb_tx.send(MessageFromGb::Breakpoints(breakpoints));
+}
+
+fn load_symbols(path: &Path) -> Option<SymbolTable> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| log::error!("Unable to read {}: {}", path.display(), e))
+        .ok()?;
+
+    let symbols = SymbolTable::parse(&contents)
+        .map_err(|e| log::error!("Unable to parse {}: {}", path.display(), e))
+        .ok()?;
+
+    log::info!("Loaded {} symbols from {}", symbols.len(), path.display());
+    Some(symbols)
+}
+
 fn main() {
+    let args = Args::parse();
+
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "info")
     }
@@ -132,6 +296,16 @@ fn main() {
         ..Default::default()
     };
 
+    let gdb_listener = args.gdb.map(|port| {
+        let listener =
+            TcpListener::bind(("127.0.0.1", port)).expect("Unable to listen for gdb connections");
+        listener
+            .set_nonblocking(true)
+            .expect("Unable to listen for gdb connections");
+        log::info!("Waiting for gdb on port {}", port);
+        listener
+    });
+
     let (to_gb_tx, to_gb_rx) = crossbeam::channel::unbounded::<MessageToGB>();
     let (from_gb_tx, from_gb_rx) = crossbeam::channel::unbounded::<MessageFromGb>();
 
@@ -141,7 +315,7 @@ fn main() {
         Box::new(|cc| {
             let ctx = cc.egui_ctx.clone();
             std::thread::spawn(|| {
-                gb_loop(to_gb_rx, from_gb_tx, ctx);
+                gb_loop(to_gb_rx, from_gb_tx, ctx, gdb_listener);
             });
             Box::new(DebuggerApp::new(cc, log_rx, to_gb_tx, from_gb_rx))
         }),
@@ -1,5 +1,7 @@
+use partyboy_core::debug::breakpoint::{BreakReason, Breakpoint, BreakpointId};
 use partyboy_core::debug::GBDebugInfo;
 use partyboy_core::ppu::rgb::Rgb;
+use partyboy_core::symbols::SymbolTable;
 
 use crate::app::InputType;
 
@@ -10,10 +12,20 @@ pub enum MessageToGB {
     Stop,
     KeyDown(Vec<InputType>),
     KeyUp(Vec<InputType>),
+    AddBreakpoint(Breakpoint),
+    RemoveBreakpoint(BreakpointId),
+    /// load a `.sym` file from the given path
+    LoadSymbols(String),
 }
 
 pub enum MessageFromGb {
     /// GB wants to draw frame with given frame buffer
     Draw(Vec<Rgb>),
     DebugInfo(Box<GBDebugInfo>),
+    /// GB has stopped because a breakpoint was hit
+    BreakpointHit(BreakReason),
+    /// The current list of breakpoints, sent whenever it changes
+    Breakpoints(Vec<(BreakpointId, Breakpoint)>),
+    /// Symbols for the loaded rom, sent whenever they're (re)loaded
+    Symbols(SymbolTable),
 }
@@ -0,0 +1,188 @@
+//! Records every emulated frame and audio sample, as a Y4M video and a WAV file next to it.
+//! Both are uncompressed and can be written as they come in, so recordings can be hours
+//! long. A frame is written every [`CYCLES_PER_FRAME`] of emulated time so the video stays
+//! in sync with the audio, repeating the last one while the LCD is off. Frames are converted
+//! to full range 4:4:4 YCbCr, which is what Y4M supports that comes closest to RGB.
+//!
+//! The emulator makes 4194304 / 87 samples a second, a fraction more than the whole
+//! [`SAMPLE_RATE`] a WAV header can hold. The odd sample, one every couple of seconds, is
+//! dropped so the audio doesn't fall behind the video in long recordings.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use partyboy_core::{CYCLES_PER_FRAME, SAMPLE_RATE, SPEED};
+
+const WAV_HEADER_LEN: u32 = 44;
+const CHANNELS: u16 = 2;
+const BYTES_PER_SAMPLE: u16 = 2;
+
+pub struct AvRecording {
+    video: BufWriter<File>,
+    audio: BufWriter<File>,
+    /// The planes of the last frame, written again until the next one is drawn
+    planes: Vec<u8>,
+    /// Ticks since the recording started
+    ticks: u64,
+    audio_bytes: u32,
+}
+
+impl AvRecording {
+    /// Creates `path` with the `y4m` and `wav` extensions for frames of the given size
+    pub fn create(path: &Path, width: u32, height: u32) -> io::Result<Self> {
+        let mut video = BufWriter::new(File::create(path.with_extension("y4m"))?);
+        writeln!(
+            video,
+            "YUV4MPEG2 W{width} H{height} F{SPEED}:{CYCLES_PER_FRAME} Ip A1:1 C444 XCOLORRANGE=FULL"
+        )?;
+
+        let mut audio = BufWriter::new(File::create(path.with_extension("wav"))?);
+        write_wav_header(&mut audio, 0)?;
+
+        // black until the first frame is drawn
+        let pixels = (width * height) as usize;
+        let mut planes = vec![128; pixels * 3];
+        planes[..pixels].fill(0);
+
+        log::info!("Recording to {}...", path.display());
+        Ok(Self {
+            video,
+            audio,
+            planes,
+            ticks: 0,
+            audio_bytes: 0,
+        })
+    }
+
+    /// Makes `rgba` the frame that is written from now on
+    pub fn set_frame(&mut self, rgba: &[u8]) {
+        let pixels = rgba.len() / 4;
+        let (y, chroma) = self.planes.split_at_mut(pixels);
+        let (cb, cr) = chroma.split_at_mut(pixels);
+        for (i, px) in rgba.chunks_exact(4).enumerate() {
+            let (r, g, b) = (px[0] as f32, px[1] as f32, px[2] as f32);
+            let to_u8 = |val: f32| val.round().clamp(0.0, 255.0) as u8;
+            y[i] = to_u8(0.299 * r + 0.587 * g + 0.114 * b);
+            cb[i] = to_u8(128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b);
+            cr[i] = to_u8(128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b);
+        }
+    }
+
+    /// Records one tick of the emulator, along with the sample it returned
+    pub fn tick(&mut self, sample: Option<(f32, f32)>) -> io::Result<()> {
+        self.ticks += 1;
+
+        // only as many samples as fit in the time so far at the rate in the header
+        let samples = (self.audio_bytes / (CHANNELS * BYTES_PER_SAMPLE) as u32) as u64;
+        if let Some(sample) = sample
+            && samples * SPEED < self.ticks * SAMPLE_RATE
+        {
+            self.push_sample(sample)?;
+        }
+
+        if self.ticks.is_multiple_of(CYCLES_PER_FRAME) {
+            self.video.write_all(b"FRAME\n")?;
+            self.video.write_all(&self.planes)?;
+        }
+        Ok(())
+    }
+
+    fn push_sample(&mut self, (left, right): (f32, f32)) -> io::Result<()> {
+        let sample_len = (CHANNELS * BYTES_PER_SAMPLE) as u32;
+        if self.audio_bytes + sample_len > u32::MAX - WAV_HEADER_LEN {
+            return Err(io::Error::other("WAV files can't be larger than 4 GiB"));
+        }
+        self.audio_bytes += sample_len;
+
+        for sample in [left, right] {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.audio.write_all(&sample.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Fills in the length of the audio, which isn't known until the end
+    pub fn finish(mut self) -> io::Result<()> {
+        self.video.flush()?;
+        self.audio.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.audio, self.audio_bytes)?;
+        self.audio.flush()?;
+        log::info!("Finished recording");
+        Ok(())
+    }
+}
+
+/// A 16 bit stereo PCM header, for `data_len` bytes of samples
+fn write_wav_header(w: &mut impl Write, data_len: u32) -> io::Result<()> {
+    let block_align = CHANNELS * BYTES_PER_SAMPLE;
+    w.write_all(b"RIFF")?;
+    w.write_all(&(WAV_HEADER_LEN - 8 + data_len).to_le_bytes())?;
+    w.write_all(b"WAVEfmt ")?;
+    w.write_all(&16u32.to_le_bytes())?;
+    // PCM
+    w.write_all(&1u16.to_le_bytes())?;
+    w.write_all(&CHANNELS.to_le_bytes())?;
+    w.write_all(&(SAMPLE_RATE as u32).to_le_bytes())?;
+    w.write_all(&(SAMPLE_RATE as u32 * block_align as u32).to_le_bytes())?;
+    w.write_all(&block_align.to_le_bytes())?;
+    w.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+    w.write_all(b"data")?;
+    w.write_all(&data_len.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_frames_on_emulated_time() {
+        let path = std::env::temp_dir().join(format!("partyboy_test_{}", std::process::id()));
+        let mut recording = AvRecording::create(&path, 1, 1).unwrap();
+
+        // the lcd is off for the first frame
+        for _ in 0..CYCLES_PER_FRAME {
+            recording.tick(None).unwrap();
+        }
+        recording.set_frame(&[0xFF; 4]);
+        for _ in 0..CYCLES_PER_FRAME * 2 - 1 {
+            recording.tick(None).unwrap();
+        }
+        recording.finish().unwrap();
+
+        let video = std::fs::read(path.with_extension("y4m")).unwrap();
+        let audio = std::fs::read(path.with_extension("wav")).unwrap();
+        std::fs::remove_file(path.with_extension("y4m")).unwrap();
+        std::fs::remove_file(path.with_extension("wav")).unwrap();
+
+        let header_len = video.iter().position(|&b| b == b'\n').unwrap() + 1;
+        assert_eq!(
+            video[header_len..],
+            *b"FRAME\n\x00\x80\x80FRAME\n\xFF\x80\x80"
+        );
+        assert_eq!(audio.len(), 44);
+    }
+
+    #[test]
+    fn drops_samples_over_the_header_rate() {
+        let path = std::env::temp_dir().join(format!("partyboy_test_drop_{}", std::process::id()));
+        let mut recording = AvRecording::create(&path, 1, 1).unwrap();
+
+        let ticks_per_sample = SPEED / SAMPLE_RATE;
+        for tick in 1..=SPEED * 3 {
+            let sample = tick.is_multiple_of(ticks_per_sample).then_some((0.0, 0.0));
+            recording.tick(sample).unwrap();
+        }
+        recording.finish().unwrap();
+
+        let audio = std::fs::read(path.with_extension("wav")).unwrap();
+        std::fs::remove_file(path.with_extension("y4m")).unwrap();
+        std::fs::remove_file(path.with_extension("wav")).unwrap();
+
+        // 3 seconds at the header rate, the emulator made a sample more than that
+        assert_eq!(SPEED * 3 / ticks_per_sample, SAMPLE_RATE * 3 + 1);
+        assert_eq!((audio.len() - 44) / 4, SAMPLE_RATE as usize * 3);
+    }
+}
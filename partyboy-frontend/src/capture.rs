@@ -0,0 +1,308 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use crossbeam::channel::Sender;
+use image::{
+    Delay, Frame, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+};
+use partyboy_core::{CYCLES_PER_FRAME, SPEED};
+
+const FRAMES_PER_SECOND: f64 = SPEED as f64 / CYCLES_PER_FRAME as f64;
+/// How many frames can wait for the encoder before the emulator has to wait for it
+const QUEUED_FRAMES: usize = 60;
+/// The closest fraction of seconds to 70224 / 4194304 that fits the u16s of an APNG frame
+const APNG_FRAME_DELAY: (u16, u16) = (400, 23891);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+        }
+    }
+}
+
+/// A new file next to the rom, named after it and the current time. Roms that weren't
+/// loaded from a file get theirs in the working directory
+fn capture_path(rom_path: Option<&Path>, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+
+    let dir_and_stem = rom_path.and_then(|path| Some((path.parent()?, path.file_stem()?)));
+    match dir_and_stem {
+        Some((dir, stem)) => dir.join(format!(
+            "{}_{timestamp}.{extension}",
+            stem.to_string_lossy()
+        )),
+        None => PathBuf::from(format!("partyboy_{timestamp}.{extension}")),
+    }
+}
+
+/// Saves an RGBA frame as a PNG next to the rom. Returns whether that worked
+pub fn save_screenshot(rom_path: Option<&Path>, frame: Vec<u8>, width: u32, height: u32) -> bool {
+    let path = capture_path(rom_path, "png");
+    let Some(image) = RgbaImage::from_raw(width, height, frame) else {
+        log::error!("Screenshot has an unexpected size");
+        return false;
+    };
+
+    match image.save(&path) {
+        Ok(()) => {
+            log::info!("Saved screenshot to {}", path.display());
+            true
+        }
+        Err(e) => {
+            log::error!("Unable to save screenshot: {}", e);
+            false
+        }
+    }
+}
+
+/// Scales an RGBA frame up by repeating every pixel `scale` times in both directions
+fn scale_frame(frame: &[u8], width: u32, scale: u32) -> Vec<u8> {
+    if scale == 1 {
+        return frame.to_vec();
+    }
+
+    let scale = scale as usize;
+    frame
+        .chunks_exact(width as usize * 4)
+        .flat_map(|line| {
+            let scaled_line = line
+                .chunks_exact(4)
+                .flat_map(|px| px.repeat(scale))
+                .collect::<Vec<_>>();
+            scaled_line.repeat(scale)
+        })
+        .collect()
+}
+
+/// An animated GIF or APNG being recorded. Frames are encoded on their own thread so the
+/// emulator doesn't stall while the encoder keeps up, the file is finished when the
+/// recording is stopped.
+///
+/// Like the av recording, a frame is written every [`CYCLES_PER_FRAME`] of emulated time,
+/// repeating the last one while the LCD is off
+pub struct Recording {
+    frames: Sender<Vec<u8>>,
+    writer: JoinHandle<()>,
+    /// The last frame drawn, written again until the next one is
+    frame: Vec<u8>,
+    /// Ticks since the last frame was written
+    cycles: u64,
+}
+
+impl Recording {
+    /// Starts with `frame`, what is on the screen at the moment
+    pub fn start(
+        format: RecordingFormat,
+        scale: u32,
+        rom_path: Option<&Path>,
+        frame: Vec<u8>,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let path = capture_path(rom_path, format.extension());
+        let (frames, rx) = crossbeam::channel::bounded::<Vec<u8>>(QUEUED_FRAMES);
+        log::info!("Recording to {}...", path.display());
+
+        let writer = std::thread::spawn(move || {
+            let frames = rx.iter().map(|frame| scale_frame(&frame, width, scale));
+            let (width, height) = (width * scale, height * scale);
+            let written = match format {
+                RecordingFormat::Gif => write_gif(&path, width, height, frames),
+                RecordingFormat::Apng => write_apng(&path, width, height, frames),
+            };
+
+            match written {
+                Ok(count) => log::info!("Saved {count} frames to {}", path.display()),
+                Err(e) => log::error!("Unable to save recording: {}", e),
+            }
+        });
+
+        Self {
+            frames,
+            writer,
+            frame,
+            cycles: 0,
+        }
+    }
+
+    /// Makes `frame` the one that is written from now on
+    pub fn set_frame(&mut self, frame: &[u8]) {
+        self.frame.clear();
+        self.frame.extend_from_slice(frame);
+    }
+
+    /// Call once for every tick of the emulator
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        if self.cycles == CYCLES_PER_FRAME {
+            self.cycles = 0;
+            let _ = self.frames.send(self.frame.clone());
+        }
+    }
+
+    /// Waits for the file to be written
+    pub fn stop(self) {
+        drop(self.frames);
+        let _ = self.writer.join();
+    }
+}
+
+/// GIF delays are in hundredths of a second, so frames take 1 or 2 of them to stay in sync
+fn write_gif(
+    path: &Path,
+    width: u32,
+    height: u32,
+    frames: impl Iterator<Item = Vec<u8>>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(File::create(path)?), 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+
+    let centis_at = |frame: usize| (frame as f64 * 100.0 / FRAMES_PER_SECOND).round() as u32;
+    let mut count = 0;
+    for frame in frames {
+        let image = RgbaImage::from_raw(width, height, frame).ok_or("unexpected frame size")?;
+        let centis = centis_at(count + 1) - centis_at(count);
+        let delay = Delay::from_numer_denom_ms(centis * 10, 1);
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// APNG needs the number of frames up front, so the header claims as many as it can hold
+/// and is patched once the last frame is written
+fn write_apng(
+    path: &Path,
+    width: u32,
+    height: u32,
+    frames: impl Iterator<Item = Vec<u8>>,
+) -> Result<usize, Box<dyn Error>> {
+    // read back when the frame count is patched
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file.try_clone()?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(u32::MAX, 0)?;
+    encoder.set_frame_delay(APNG_FRAME_DELAY.0, APNG_FRAME_DELAY.1)?;
+
+    let mut writer = encoder.write_header()?;
+    let mut count = 0;
+    for frame in frames {
+        writer.write_image_data(&frame)?;
+        count += 1;
+    }
+
+    if count == 0 {
+        drop(writer);
+        let _ = fs::remove_file(path);
+        return Err("no frames were recorded".into());
+    }
+
+    writer.finish()?;
+    set_frame_count(&mut file, count)?;
+
+    Ok(count as usize)
+}
+
+/// Overwrites the number of frames in the `acTL` chunk of an APNG, and its checksum
+fn set_frame_count(file: &mut File, count: u32) -> io::Result<()> {
+    // the chunks start after the 8 byte signature
+    let mut offset = 8;
+    loop {
+        let mut header = [0; 8];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+
+        if &header[4..] == b"acTL" {
+            // the frame count, then the number of plays
+            let mut data = [0; 8];
+            file.read_exact(&mut data)?;
+            data[..4].copy_from_slice(&count.to_be_bytes());
+
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(b"acTL");
+            crc.update(&data);
+
+            file.seek(SeekFrom::Start(offset + 8))?;
+            file.write_all(&data)?;
+            file.write_all(&crc.finalize().to_be_bytes())?;
+            return Ok(());
+        }
+
+        // length, type, data and checksum
+        offset += 12 + len as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_repeats_frames_on_emulated_time() {
+        let (frames, rx) = crossbeam::channel::unbounded();
+        let mut recording = Recording {
+            frames,
+            writer: std::thread::spawn(|| {}),
+            frame: vec![0; 4],
+            cycles: 0,
+        };
+
+        // nothing drawn for two frames, then a new frame halfway through the third
+        for cycle in 0..CYCLES_PER_FRAME * 3 {
+            if cycle == CYCLES_PER_FRAME * 5 / 2 {
+                recording.set_frame(&[1; 4]);
+            }
+            recording.tick();
+        }
+        recording.stop();
+
+        let frames = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(frames, [vec![0; 4], vec![0; 4], vec![1; 4]]);
+    }
+
+    #[test]
+    fn apng_has_recorded_frame_count() {
+        let path = std::env::temp_dir().join(format!("partyboy_test_{}.png", std::process::id()));
+        let frames = (0..3).map(|i| vec![i * 0x40; 2 * 2 * 4]);
+        let count = write_apng(&path, 2, 2, frames).unwrap();
+
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(&path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let frames = reader.info().animation_control().unwrap().num_frames;
+        let mut buf = vec![0; reader.output_buffer_size().unwrap()];
+        let mut decoded = 0;
+        while reader.next_frame(&mut buf).is_ok() {
+            decoded += 1;
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((count, frames, decoded), (3, 3, 3));
+    }
+}
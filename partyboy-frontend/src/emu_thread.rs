@@ -1,4 +1,10 @@
-use std::{collections::VecDeque, thread::JoinHandle, time::Duration};
+use std::{
+    collections::VecDeque,
+    io,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use cpal::{
     Stream, StreamConfig,
@@ -7,17 +13,70 @@ use cpal::{
 use crossbeam::channel::{Receiver, Sender};
 use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use partyboy_common::loop_helper::LoopHelper as ReportHelper;
-use partyboy_core::{GameBoy, SPEED};
+use partyboy_core::{
+    CYCLES_PER_FRAME, GameBoy, SPEED,
+    builder::GameBoyBuilderError,
+    camera::StillImage,
+    ppu::{
+        cgb_palette::BootPaletteCombo,
+        frame_blend::FrameBlendMode,
+        pixel_format::PixelFormat,
+        rgb::{ColorCorrection, Rgb},
+    },
+    serial::printer::{PrintedImage, Printer},
+    sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH},
+    trace::Tracer,
+};
 use ringbuffer::{ConstGenericRingBuffer, RingBuffer};
 
-use crate::msgs::{MsgFromGb, MsgToGb};
+use crate::{
+    av_recording::AvRecording,
+    capture::{Recording, RecordingFormat, save_screenshot},
+    msgs::{MsgFromGb, MsgToGb},
+    roms::Game,
+    saves::write_save_file,
+    speed::{SPEEDS, SpeedAdjuster},
+};
 
 const FPS_REPORT_RATE_MS: u64 = 500;
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
 
 pub struct EmuThreadHandle {
     pub tx: Sender<MsgToGb>,
     pub rx: Receiver<MsgFromGb>,
-    pub handle: JoinHandle<Option<Box<[u8]>>>,
+    pub handle: JoinHandle<()>,
+}
+
+pub struct EmuOptions {
+    pub trace: Option<Tracer>,
+    /// Run carts that support it in Super Game Boy mode
+    pub sgb: bool,
+    /// Send 256x224 frames with the SGB border instead of the bare 160x144 screen
+    pub sgb_border: bool,
+    /// Attach a Game Boy Printer that saves printed strips as PNGs in this directory
+    pub printer: Option<PathBuf>,
+    /// The picture the Game Boy Camera sees, instead of its test pattern
+    pub camera_image: Option<StillImage>,
+    /// Also save the ram of carts that have no battery to keep it on real hardware
+    pub save_without_battery: bool,
+    /// Mute the audio when not running at normal speed, instead of keeping its pitch
+    pub mute_speed_changes: bool,
+    pub color_correction: ColorCorrection,
+    /// Show blended frames to emulate the ghosting of the LCD
+    pub frame_blending: Option<FrameBlendMode>,
+    /// Color DMG games with the palette of this CGB bios combo instead of the title hash
+    pub palette_combo: Option<BootPaletteCombo>,
+    /// Color DMG games with these 12 RGB32 colors, takes precedence over the combo
+    pub custom_palette: Option<Vec<u32>>,
+    /// Record every emulated frame and audio sample to this path, as a Y4M and a WAV file
+    pub record: Option<PathBuf>,
+    pub recording_format: RecordingFormat,
+    /// From 0.0 to 1.0, only applied to what is played
+    pub volume: f32,
+    /// How far the emulator runs ahead of the audio that is playing
+    pub audio_latency_ms: u32,
+    /// How many times larger recordings are than the screen
+    pub recording_scale: u32,
 }
 
 fn take_snapshot(gb: &GameBoy) -> Vec<u8> {
@@ -32,7 +91,260 @@ fn apply_snapshot(gb: &mut GameBoy, snapshot: &[u8]) {
     gb.release_all_keys();
 }
 
-fn set_up_audio() -> (Option<Stream>, Sender<(f32, f32)>) {
+/// The width and height of the frames [`current_frame`] returns
+fn frame_size(sgb_border: bool) -> (u32, u32) {
+    if sgb_border {
+        (SGB_SCREEN_WIDTH as u32, SGB_SCREEN_HEIGHT as u32)
+    } else {
+        (160, 144)
+    }
+}
+
+fn to_rgba(frame: &[Rgb]) -> Vec<u8> {
+    frame
+        .iter()
+        .flat_map(|px| [px.r, px.g, px.b, 0xFF])
+        .collect()
+}
+
+/// The frame to show, as RGBA. With the border enabled, games that aren't running in SGB
+/// mode are centered in a black 256x224 frame so the window size stays the same
+fn current_frame(gb: &GameBoy, sgb_border: bool) -> Vec<u8> {
+    let blended = gb.get_blended_frame_buffer();
+    if !sgb_border {
+        return match blended {
+            Some(frame) => to_rgba(frame),
+            None => {
+                let mut frame = vec![0; 160 * 144 * PixelFormat::Rgba8888.bytes_per_pixel()];
+                gb.render_frame_buffer(PixelFormat::Rgba8888, &mut frame);
+                frame
+            }
+        };
+    }
+
+    if let Some(frame) = gb.get_sgb_frame_buffer() {
+        return to_rgba(frame);
+    }
+
+    let screen = blended.unwrap_or_else(|| gb.get_frame_buffer());
+    let (x_offset, y_offset) = ((SGB_SCREEN_WIDTH - 160) / 2, (SGB_SCREEN_HEIGHT - 144) / 2);
+    let mut frame = vec![Rgb::const_mono(0); SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT];
+    for (y, line) in screen.chunks_exact(160).enumerate() {
+        let start = (y + y_offset) * SGB_SCREEN_WIDTH + x_offset;
+        frame[start..start + 160].copy_from_slice(line);
+    }
+
+    to_rgba(&frame)
+}
+
+/// Sends the current frame to the window
+fn show_frame(gb: &GameBoy, options: &EmuOptions, s: &Sender<MsgFromGb>) {
+    let _ = s.try_send(MsgFromGb::Frame(current_frame(gb, options.sgb_border)));
+}
+
+/// Writes to the av recording, which is stopped when that fails
+fn write_av(
+    av_recording: &mut Option<AvRecording>,
+    write: impl FnOnce(&mut AvRecording) -> io::Result<()>,
+) {
+    if let Some(recording) = av_recording
+        && let Err(e) = write(recording)
+    {
+        log::error!("Unable to write recording, stopping it: {}", e);
+        *av_recording = None;
+    }
+}
+
+/// Makes the frame that was just drawn the one the recordings write from now on
+fn record_frame(
+    av_recording: &mut Option<AvRecording>,
+    recording: &mut Option<Recording>,
+    gb: &GameBoy,
+    options: &EmuOptions,
+) {
+    if av_recording.is_none() && recording.is_none() {
+        return;
+    }
+
+    let frame = current_frame(gb, options.sgb_border);
+    if let Some(av_recording) = av_recording {
+        av_recording.set_frame(&frame);
+    }
+    if let Some(recording) = recording {
+        recording.set_frame(&frame);
+    }
+}
+
+/// Moves the recordings along by one tick of the emulator, which made `sample`
+fn tick_recordings(
+    av_recording: &mut Option<AvRecording>,
+    recording: &mut Option<Recording>,
+    sample: Option<(f32, f32)>,
+) {
+    write_av(av_recording, |av| av.tick(sample));
+    if let Some(recording) = recording {
+        recording.tick();
+    }
+}
+
+/// Runs the emulator until it draws a frame, or for as long as a frame takes while the LCD
+/// is off and nothing gets drawn. Its frame buffer is blank then. Returns whether a frame
+/// was drawn
+fn run_frame(
+    gb: &mut GameBoy,
+    av_recording: &mut Option<AvRecording>,
+    recording: &mut Option<Recording>,
+) -> bool {
+    for _ in 0..CYCLES_PER_FRAME {
+        let sample = gb.tick();
+        tick_recordings(av_recording, recording, sample);
+        if gb.consume_draw_flag() {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn show_message(s: &Sender<MsgFromGb>, text: &str) {
+    let _ = s.try_send(MsgFromGb::Message(text.to_owned()));
+}
+
+/// Measures how long the rumble motor was on during each frame, as games pulse it to
+/// rumble less than full strength
+#[derive(Default)]
+struct RumbleMeter {
+    /// Ticks since the strength was last measured
+    ticks: u64,
+    ticks_on: u64,
+    /// The strength the window was last sent
+    strength: f32,
+}
+
+impl RumbleMeter {
+    /// Measures the strength every [`CYCLES_PER_FRAME`] of emulated time, so it keeps
+    /// working while the LCD is off
+    fn tick(&mut self, gb: &GameBoy, s: &Sender<MsgFromGb>) {
+        if gb.rumble() {
+            self.ticks_on += 1;
+        }
+
+        self.ticks += 1;
+        if self.ticks == CYCLES_PER_FRAME {
+            // in steps of 1/8 so not every frame sends a new strength
+            let strength = (self.ticks_on as f32 / CYCLES_PER_FRAME as f32 * 8.0).round() / 8.0;
+            self.ticks = 0;
+            self.ticks_on = 0;
+            self.send(strength, s);
+        }
+    }
+
+    fn stop(&mut self, s: &Sender<MsgFromGb>) {
+        self.ticks = 0;
+        self.ticks_on = 0;
+        self.send(0.0, s);
+    }
+
+    fn send(&mut self, strength: f32, s: &Sender<MsgFromGb>) {
+        // when the channel is full it's tried again on the next frame
+        if strength != self.strength && s.try_send(MsgFromGb::Rumble(strength)).is_ok() {
+            self.strength = strength;
+        }
+    }
+}
+
+/// Builds the emulator for `game` with the options applied. Also returns the path of the
+/// rom it was loaded from
+fn build_gameboy(
+    game: Option<Game>,
+    bios: Option<Vec<u8>>,
+    options: &EmuOptions,
+) -> Result<(GameBoy, Option<PathBuf>), GameBoyBuilderError> {
+    let mut builder = GameBoy::builder()
+        .sgb(options.sgb)
+        .color_correction(options.color_correction);
+    if let Some(combo) = options.palette_combo {
+        builder = builder.palette_combo(combo);
+    }
+    if let Some(palette) = options.custom_palette.clone() {
+        builder = builder.custom_palette(palette);
+    }
+    let mut rom_path = None;
+    if let Some(game) = game {
+        builder = builder.rom(game.rom);
+        if let Some(ram) = game.ram {
+            builder = builder.ram(ram);
+        }
+        if let Some(patch) = game.patch {
+            builder = builder.patch(patch);
+        }
+        rom_path = Some(game.rom_path);
+    }
+    if let Some(bios) = bios {
+        builder = builder.bios(bios);
+    }
+    let mut gb = builder.build()?;
+    gb.set_frame_blending(options.frame_blending);
+
+    if let Some(dir) = options.printer.clone() {
+        let printer = Printer::new(Box::new(move |image| save_printed_image(&dir, image)));
+        gb.set_serial_device(Some(Box::new(printer)));
+    }
+
+    if let Some(image) = options.camera_image.clone()
+        && !gb.set_camera_source(Some(Box::new(image)))
+    {
+        log::warn!("A camera image was given, but the game isn't a Game Boy Camera");
+    }
+
+    Ok((gb, rom_path))
+}
+
+/// Writes the cart ram to the save file of the rom, if it should be saved at all
+fn save(gb: &GameBoy, rom_path: Option<&Path>, options: &EmuOptions) {
+    if !options.save_without_battery && !gb.has_battery() {
+        return;
+    }
+
+    if let (Some(rom_path), Some(ram)) = (rom_path, gb.try_read_cartridge_ram()) {
+        write_save_file(rom_path, &ram);
+    }
+}
+
+/// Writes the cart ram to the save file, if it changed since the last save
+fn autosave(gb: &mut GameBoy, rom_path: Option<&Path>, options: &EmuOptions) {
+    if gb.consume_cartridge_ram_dirty_flag() {
+        save(gb, rom_path, options);
+    }
+}
+
+fn save_printed_image(dir: &Path, image: PrintedImage) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    let path = dir.join(format!("print_{timestamp}.png"));
+
+    let bytes = image
+        .pixels
+        .iter()
+        .flat_map(|px| [px.r, px.g, px.b])
+        .collect::<Vec<_>>();
+    let Some(buffer) = image::RgbImage::from_raw(image.width as u32, image.height as u32, bytes)
+    else {
+        log::error!("Printed image has an unexpected size");
+        return;
+    };
+
+    match buffer.save(&path) {
+        Ok(()) => log::info!("Printed to {}", path.display()),
+        Err(e) => log::error!("Unable to save printed image: {}", e),
+    }
+}
+
+/// `buffered_samples` is how many samples the emulator stays ahead, the channel has room
+/// for a few times that
+fn set_up_audio(buffered_samples: usize) -> (Option<Stream>, Sender<(f32, f32)>) {
     let hosts = cpal::available_hosts().len();
     log::debug!("{}", hosts);
 
@@ -45,7 +357,7 @@ fn set_up_audio() -> (Option<Stream>, Sender<(f32, f32)>) {
 
     log::info!("Audio devices found: {audio_devices_found}");
 
-    let (audio_s, audio_r) = crossbeam::channel::bounded::<(f32, f32)>(512 * 16);
+    let (audio_s, audio_r) = crossbeam::channel::bounded::<(f32, f32)>(buffered_samples * 4);
 
     let audio_stream = if audio_devices_found > 0 {
         let device = host
@@ -106,32 +418,28 @@ fn set_up_audio() -> (Option<Stream>, Sender<(f32, f32)>) {
     (audio_stream, audio_s)
 }
 
-pub fn new(rom: Option<Vec<u8>>, bios: Option<Vec<u8>>, ram: Option<Vec<u8>>) -> EmuThreadHandle {
+pub fn new(game: Option<Game>, bios: Option<Vec<u8>>, options: EmuOptions) -> EmuThreadHandle {
     let (s_to_gb, r_from_ui) = crossbeam::channel::bounded::<MsgToGb>(32);
     let (s_to_ui, r_from_gb) = crossbeam::channel::bounded::<MsgFromGb>(128);
 
     let handle = std::thread::spawn(move || {
-        let (_stream, audio_s) = set_up_audio();
+        // the audio plays at 48 kHz
+        let buffered_samples = (options.audio_latency_ms as usize * 48).max(512);
+        let (_stream, audio_s) = set_up_audio(buffered_samples);
 
         let (s, r) = (s_to_ui, r_from_ui);
 
-        // TODO: make this an option and be able to set rom via msg
-        let mut builder = GameBoy::builder();
-        // TODO: make builder take optionals?
-        if let Some(rom) = rom {
-            builder = builder.rom(rom);
-        }
-        if let Some(ram) = ram {
-            builder = builder.ram(ram);
-        }
-        if let Some(bios) = bios {
-            builder = builder.bios(bios);
-        }
-        let mut gb = builder
-            .build()
+        let mut options = options;
+        let (mut gb, mut rom_path) = build_gameboy(game, bios.clone(), &options)
             .expect("Unable to construct emulator instance");
 
+        if let Some(tracer) = options.trace.take() {
+            gb.set_tracer(Some(tracer));
+        }
+
         let mut turbo = false;
+        let mut paused = false;
+        let mut speed = SpeedAdjuster::new(options.mute_speed_changes);
         let mut snapshot: Option<Vec<u8>> = None;
 
         let mut history = VecDeque::new();
@@ -141,6 +449,20 @@ pub fn new(rom: Option<Vec<u8>>, bios: Option<Vec<u8>>, ram: Option<Vec<u8>>) ->
 
         let mut last_8_frames = ConstGenericRingBuffer::<_, 8>::new();
 
+        let mut last_autosave = Instant::now();
+
+        let mut rumble = RumbleMeter::default();
+
+        let mut recording: Option<Recording> = None;
+        let mut av_recording = options.record.as_ref().and_then(|path| {
+            let (width, height) = frame_size(options.sgb_border);
+            AvRecording::create(path, width, height)
+                .inspect_err(|e| log::error!("Unable to start recording: {}", e))
+                .ok()
+        });
+        // whether the window was last told that something is being recorded
+        let mut recording_shown = false;
+
         loop {
             // calculate how many ticks have elapsed
             let now = partyboy_common::time::now();
@@ -148,7 +470,33 @@ pub fn new(rom: Option<Vec<u8>>, bios: Option<Vec<u8>>, ram: Option<Vec<u8>>) ->
             let msgs: Vec<MsgToGb> = r.try_iter().collect();
             for msg in msgs {
                 match msg {
-                    MsgToGb::Load => todo!(),
+                    MsgToGb::Load(path) => {
+                        let loaded =
+                            Game::load(&path)
+                                .map_err(|e| e.to_string())
+                                .and_then(|game| {
+                                    build_gameboy(Some(game), bios.clone(), &options)
+                                        .map_err(|e| e.to_string())
+                                });
+
+                        match loaded {
+                            Ok((mut new_gb, new_rom_path)) => {
+                                save(&gb, rom_path.as_deref(), &options);
+                                new_gb.set_tracer(gb.take_tracer());
+                                (gb, rom_path) = (new_gb, new_rom_path);
+
+                                snapshot = None;
+                                history.clear();
+                                last_8_frames.clear();
+                                log::info!("Loaded {}", path.display());
+                                show_message(&s, "Game loaded");
+                            }
+                            Err(e) => {
+                                log::error!("Unable to load {}: {}", path.display(), e);
+                                show_message(&s, "Unable to load game");
+                            }
+                        }
+                    }
                     MsgToGb::KeyDown(key) => {
                         log::debug!("{:?}", key);
                         gb.key_down(key);
@@ -165,37 +513,105 @@ pub fn new(rom: Option<Vec<u8>>, bios: Option<Vec<u8>>, ram: Option<Vec<u8>>) ->
                     MsgToGb::Rewind(state) => {
                         rewind = state;
                     }
+                    MsgToGb::Pause(state) => {
+                        paused = state;
+                        if paused {
+                            autosave(&mut gb, rom_path.as_deref(), &options);
+                            last_autosave = Instant::now();
+                        }
+                    }
+                    MsgToGb::FrameAdvance => {
+                        if paused {
+                            // the audio of a single frame would only be a click
+                            if run_frame(&mut gb, &mut av_recording, &mut recording) {
+                                record_frame(&mut av_recording, &mut recording, &gb, &options);
+                            }
+                            show_frame(&gb, &options, &s);
+                            report_helper.record_frame_draw();
+                        }
+                    }
+                    MsgToGb::ColorCorrection(color_correction) => {
+                        options.color_correction = color_correction;
+                        gb.set_color_correction(color_correction);
+                    }
+                    MsgToGb::Speed(index) => {
+                        speed.set_speed(SPEEDS[index]);
+                        last_8_frames.clear();
+                    }
                     MsgToGb::SaveSnapshot => {
                         let state = take_snapshot(&gb);
                         snapshot = Some(state);
+                        show_message(&s, "State saved");
                     }
-                    MsgToGb::LoadSnapshot => {
-                        if let Some(state) = &snapshot {
+                    MsgToGb::LoadSnapshot => match &snapshot {
+                        Some(state) => {
                             apply_snapshot(&mut gb, state);
-                            log::info!("Loaded snapshot")
+                            log::info!("Loaded snapshot");
+                            show_message(&s, "State loaded");
+                        }
+                        None => show_message(&s, "No state saved"),
+                    },
+                    MsgToGb::Autosave => {
+                        autosave(&mut gb, rom_path.as_deref(), &options);
+                        last_autosave = Instant::now();
+                    }
+                    MsgToGb::Screenshot => {
+                        let (width, height) = frame_size(options.sgb_border);
+                        let frame = current_frame(&gb, options.sgb_border);
+                        if save_screenshot(rom_path.as_deref(), frame, width, height) {
+                            show_message(&s, "Screenshot saved");
+                        } else {
+                            show_message(&s, "Unable to save screenshot");
                         }
                     }
+                    MsgToGb::ToggleRecording => match recording.take() {
+                        Some(recording) => {
+                            recording.stop();
+                            show_message(&s, "Recording stopped");
+                        }
+                        None => {
+                            let (width, height) = frame_size(options.sgb_border);
+                            recording = Some(Recording::start(
+                                options.recording_format,
+                                options.recording_scale,
+                                rom_path.as_deref(),
+                                current_frame(&gb, options.sgb_border),
+                                width,
+                                height,
+                            ));
+                        }
+                    },
                     MsgToGb::Shutdown => {
-                        let ram = gb.try_read_cartridge_ram();
-                        return ram;
+                        save(&gb, rom_path.as_deref(), &options);
+                        if let Some(recording) = recording.take() {
+                            recording.stop();
+                        }
+                        if let Some(Err(e)) = av_recording.take().map(AvRecording::finish) {
+                            log::error!("Unable to finish recording: {}", e);
+                        }
+                        return;
                     }
                 }
             }
 
             'tick_emulator: {
-                if rewind || turbo {
+                if rewind || turbo || paused {
+                    rumble.stop(&s);
                     break 'tick_emulator;
                 }
 
-                while audio_s.len() < 512 * 4 {
+                while audio_s.len() < buffered_samples {
                     let sample = gb.tick();
+                    rumble.tick(&gb, &s);
+                    tick_recordings(&mut av_recording, &mut recording, sample);
                     if let Some(sample) = sample {
-                        audio_s.try_send(sample).unwrap();
+                        let (left, right) = sample;
+                        speed.push((left * options.volume, right * options.volume), &audio_s);
                     }
 
                     if gb.consume_draw_flag() {
-                        let frame_msg = MsgFromGb::Frame(gb.get_frame_buffer().into());
-                        let _ = s.try_send(frame_msg);
+                        record_frame(&mut av_recording, &mut recording, &gb, &options);
+                        show_frame(&gb, &options, &s);
                         report_helper.record_frame_draw();
 
                         // record state
@@ -209,31 +625,41 @@ pub fn new(rom: Option<Vec<u8>>, bios: Option<Vec<u8>>, ram: Option<Vec<u8>>) ->
                 }
             }
 
-            if turbo && !rewind {
-                loop {
-                    gb.tick();
-                    if gb.consume_draw_flag() {
-                        let frame_msg = MsgFromGb::Frame(gb.get_frame_buffer().into());
-                        let _ = s.try_send(frame_msg);
-                        report_helper.record_frame_draw();
-                        break;
-                    }
+            if turbo && !rewind && !paused {
+                if run_frame(&mut gb, &mut av_recording, &mut recording) {
+                    record_frame(&mut av_recording, &mut recording, &gb, &options);
                 }
+                show_frame(&gb, &options, &s);
+                report_helper.record_frame_draw();
             }
 
             if rewind {
                 if let Some(state) = history.pop_front() {
                     apply_snapshot(&mut gb, &state);
-                    let frame_msg = MsgFromGb::Frame(gb.get_frame_buffer().into());
-                    let _ = s.try_send(frame_msg);
+                    // recordings only have what was played forwards
+                    show_frame(&gb, &options, &s);
                 }
             }
 
+            if last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+                autosave(&mut gb, rom_path.as_deref(), &options);
+                last_autosave = Instant::now();
+            }
+
             // check if we should report fps
             if let Some(fps) = report_helper.report_fps(now) {
                 last_8_frames.enqueue(fps);
                 let fps = last_8_frames.iter().sum::<f64>() / last_8_frames.len() as f64;
                 let _ = s.try_send(MsgFromGb::Fps(fps));
+                let fill = audio_s.len() as f32 / buffered_samples as f32;
+                let _ = s.try_send(MsgFromGb::AudioBuffer(fill));
+            }
+
+            // the av recording also stops on its own when it can't be written
+            let is_recording = recording.is_some() || av_recording.is_some();
+            if is_recording != recording_shown {
+                recording_shown = is_recording;
+                let _ = s.send(MsgFromGb::Recording(is_recording));
             }
 
             if !turbo {
@@ -0,0 +1,218 @@
+use clap::ValueEnum;
+
+type Px = [u8; 4];
+
+/// Upscalers that run on the RGBA frame before it is handed to `pixels`, which then scales
+/// the result by a whole number to fit the window
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    /// The same as EPX, which is accepted as another name for it
+    #[value(alias = "epx")]
+    Scale2x,
+    Scale3x,
+    /// Draws the gaps between the pixels of the LCD
+    LcdGrid,
+}
+
+impl Filter {
+    /// The order `G` cycles through them in
+    pub fn next(self) -> Self {
+        match self {
+            Self::Nearest => Self::Scale2x,
+            Self::Scale2x => Self::Scale3x,
+            Self::Scale3x => Self::LcdGrid,
+            Self::LcdGrid => Self::Nearest,
+        }
+    }
+
+    /// How many times wider and higher the output is than the frame
+    pub fn factor(self) -> u32 {
+        match self {
+            Self::Nearest => 1,
+            Self::Scale2x => 2,
+            Self::Scale3x | Self::LcdGrid => 3,
+        }
+    }
+
+    /// Upscales the RGBA `frame` into `out`, which must be [`Filter::factor`] times as wide
+    /// and high
+    pub fn apply(self, frame: &[u8], width: usize, height: usize, out: &mut [u8]) {
+        if self == Self::Nearest {
+            out.copy_from_slice(frame);
+            return;
+        }
+
+        let factor = self.factor() as usize;
+        // neighbours past the edges repeat the edge
+        let get = |x: usize, y: usize, dx: isize, dy: isize| -> Px {
+            let x = x.saturating_add_signed(dx).min(width - 1);
+            let y = y.saturating_add_signed(dy).min(height - 1);
+            let index = (y * width + x) * 4;
+            [
+                frame[index],
+                frame[index + 1],
+                frame[index + 2],
+                frame[index + 3],
+            ]
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut block = [[0; 4]; 9];
+                let block = &mut block[..factor * factor];
+                let neighbours = [
+                    get(x, y, -1, -1),
+                    get(x, y, 0, -1),
+                    get(x, y, 1, -1),
+                    get(x, y, -1, 0),
+                    get(x, y, 0, 0),
+                    get(x, y, 1, 0),
+                    get(x, y, -1, 1),
+                    get(x, y, 0, 1),
+                    get(x, y, 1, 1),
+                ];
+
+                match self {
+                    Self::Nearest => unreachable!(),
+                    Self::Scale2x => scale2x(neighbours, block),
+                    Self::Scale3x => scale3x(neighbours, block),
+                    Self::LcdGrid => lcd_grid(neighbours[4], block),
+                }
+
+                for (i, px) in block.iter().enumerate() {
+                    let (out_x, out_y) = (x * factor + i % factor, y * factor + i / factor);
+                    let index = (out_y * width * factor + out_x) * 4;
+                    out[index..index + 4].copy_from_slice(px);
+                }
+            }
+        }
+    }
+}
+
+/// `n` is the 3x3 neighbourhood around the pixel, `block` the 2x2 pixels it becomes
+fn scale2x(n: [Px; 9], block: &mut [Px]) {
+    let [_, b, _, d, e, f, _, h, _] = n;
+    block.fill(e);
+    if b != h && d != f {
+        if d == b {
+            block[0] = d;
+        }
+        if b == f {
+            block[1] = f;
+        }
+        if d == h {
+            block[2] = d;
+        }
+        if h == f {
+            block[3] = f;
+        }
+    }
+}
+
+/// `n` is the 3x3 neighbourhood around the pixel, `block` the 3x3 pixels it becomes
+fn scale3x(n: [Px; 9], block: &mut [Px]) {
+    let [a, b, c, d, e, f, g, h, i] = n;
+    block.fill(e);
+    if b != h && d != f {
+        if d == b {
+            block[0] = d;
+        }
+        if (d == b && e != c) || (b == f && e != a) {
+            block[1] = b;
+        }
+        if b == f {
+            block[2] = f;
+        }
+        if (d == b && e != g) || (d == h && e != a) {
+            block[3] = d;
+        }
+        if (b == f && e != i) || (h == f && e != c) {
+            block[5] = f;
+        }
+        if d == h {
+            block[6] = d;
+        }
+        if (d == h && e != i) || (h == f && e != g) {
+            block[7] = h;
+        }
+        if h == f {
+            block[8] = f;
+        }
+    }
+}
+
+/// Darkens the right column and bottom row of the 3x3 block, like the gaps on the LCD
+fn lcd_grid(p: Px, block: &mut [Px]) {
+    let [r, g, b, a] = p;
+    let gap = [r / 4 * 3, g / 4 * 3, b / 4 * 3, a];
+    for (i, px) in block.iter_mut().enumerate() {
+        *px = if i % 3 == 2 || i / 3 == 2 { gap } else { p };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: Px = [0xFF, 0xFF, 0xFF, 0xFF];
+    const K: Px = [0x00, 0x00, 0x00, 0xFF];
+
+    #[test]
+    fn scale2x_rounds_corners() {
+        #[rustfmt::skip]
+        let n = [
+            W, K, K,
+            K, W, W,
+            W, W, W,
+        ];
+        let mut block = [[0; 4]; 4];
+        scale2x(n, &mut block);
+        assert_eq!(block, [K, W, W, W]);
+    }
+
+    #[test]
+    fn scale2x_keeps_lines() {
+        #[rustfmt::skip]
+        let n = [
+            W, K, W,
+            K, W, W,
+            W, K, W,
+        ];
+        let mut block = [[0; 4]; 4];
+        scale2x(n, &mut block);
+        assert_eq!(block, [W; 4]);
+    }
+
+    #[test]
+    fn scale3x_rounds_corners() {
+        #[rustfmt::skip]
+        let n = [
+            W, K, K,
+            K, W, W,
+            W, W, W,
+        ];
+        let mut block = [[0; 4]; 9];
+        scale3x(n, &mut block);
+        #[rustfmt::skip]
+        assert_eq!(block, [
+            K, K, W,
+            W, W, W,
+            W, W, W,
+        ]);
+    }
+
+    #[test]
+    fn lcd_grid_darkens_gaps() {
+        let p = [0x80, 0x40, 0x20, 0xFF];
+        let gap = [0x60, 0x30, 0x18, 0xFF];
+        let mut block = [[0; 4]; 9];
+        lcd_grid(p, &mut block);
+        #[rustfmt::skip]
+        assert_eq!(block, [
+            p, p, gap,
+            p, p, gap,
+            gap, gap, gap,
+        ]);
+    }
+}
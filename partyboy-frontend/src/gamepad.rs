@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use partyboy_core::input::Keycode;
+use serde::{Deserialize, Serialize};
+
+/// The buttons of a standard gamepad, named by where they are rather than how they are
+/// labelled, so `South` is A on an Xbox pad and B on a Nintendo one
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Tells the connected gamepads apart
+pub type PadId = usize;
+
+/// What a [`GamepadBackend`] reports, for the gamepad with the given id. All connected
+/// gamepads act as one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadEvent {
+    Button(PadId, PadButton, bool),
+    /// The position of the left stick, from -1.0 to 1.0 on both axes with up being positive
+    LeftStick(PadId, f32, f32),
+    Disconnected(PadId),
+}
+
+/// Where gamepad events come from and rumble goes to, so [`Gamepad`] can be driven by
+/// something other than a real gamepad
+pub trait GamepadBackend {
+    fn next_event(&mut self) -> Option<PadEvent>;
+    /// From 0.0 for off to 1.0 for full strength
+    fn set_rumble(&mut self, strength: f32);
+}
+
+/// The gamepad buttons for the Game Boy buttons. The left stick always works as the d-pad
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadSettings {
+    pub up: PadButton,
+    pub down: PadButton,
+    pub left: PadButton,
+    pub right: PadButton,
+    pub a: PadButton,
+    pub b: PadButton,
+    pub start: PadButton,
+    pub select: PadButton,
+    /// How far the left stick has to be pushed to press a direction, from 0.0 to 1.0
+    pub deadzone: f32,
+    /// Whether rumble carts make the gamepad rumble
+    pub rumble: bool,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            up: PadButton::DPadUp,
+            down: PadButton::DPadDown,
+            left: PadButton::DPadLeft,
+            right: PadButton::DPadRight,
+            a: PadButton::East,
+            b: PadButton::South,
+            start: PadButton::Start,
+            select: PadButton::Select,
+            deadzone: 0.5,
+            rumble: true,
+        }
+    }
+}
+
+const KEYCODES: [Keycode; 8] = [
+    Keycode::Up,
+    Keycode::Down,
+    Keycode::Left,
+    Keycode::Right,
+    Keycode::A,
+    Keycode::B,
+    Keycode::Start,
+    Keycode::Select,
+];
+
+impl GamepadSettings {
+    /// The buttons in the order of [`KEYCODES`]
+    fn buttons(&self) -> [PadButton; 8] {
+        [
+            self.up,
+            self.down,
+            self.left,
+            self.right,
+            self.a,
+            self.b,
+            self.start,
+            self.select,
+        ]
+    }
+}
+
+/// What one gamepad holds down, per entry of [`KEYCODES`]
+#[derive(Default)]
+struct Held {
+    buttons: [bool; 8],
+    stick: [bool; 8],
+}
+
+/// Turns the events of a [`GamepadBackend`] into Game Boy key presses
+pub struct Gamepad {
+    backend: Box<dyn GamepadBackend>,
+    settings: GamepadSettings,
+    pads: HashMap<PadId, Held>,
+    /// What was last reported, so a key held by both the stick and a button, or by several
+    /// gamepads, is only released once all of them let go
+    pressed: [bool; 8],
+}
+
+impl Gamepad {
+    pub fn new(backend: Box<dyn GamepadBackend>, settings: GamepadSettings) -> Self {
+        Self {
+            backend,
+            settings,
+            pads: HashMap::new(),
+            pressed: [false; 8],
+        }
+    }
+
+    /// Handles the pending events. Returns the keys that changed, and whether they are
+    /// pressed now
+    pub fn poll(&mut self) -> Vec<(Keycode, bool)> {
+        let mut changes = Vec::new();
+
+        while let Some(event) = self.backend.next_event() {
+            self.handle(event);
+
+            for (i, keycode) in KEYCODES.into_iter().enumerate() {
+                let pressed = self
+                    .pads
+                    .values()
+                    .any(|held| held.buttons[i] || held.stick[i]);
+                if pressed != self.pressed[i] {
+                    self.pressed[i] = pressed;
+                    changes.push((keycode, pressed));
+                }
+            }
+        }
+
+        changes
+    }
+
+    fn handle(&mut self, event: PadEvent) {
+        match event {
+            PadEvent::Button(id, button, pressed) => {
+                let held = self.pads.entry(id).or_default();
+                for (held, mapped) in held.buttons.iter_mut().zip(self.settings.buttons()) {
+                    if mapped == button {
+                        *held = pressed;
+                    }
+                }
+            }
+            PadEvent::LeftStick(id, x, y) => {
+                let deadzone = self.settings.deadzone;
+                let held = self.pads.entry(id).or_default();
+                held.stick[..4].copy_from_slice(&[
+                    y > deadzone,
+                    y < -deadzone,
+                    x < -deadzone,
+                    x > deadzone,
+                ]);
+            }
+            // a gamepad that is gone can't release what it was holding
+            PadEvent::Disconnected(id) => {
+                self.pads.remove(&id);
+            }
+        }
+    }
+
+    /// Does nothing when rumble is turned off in the settings
+    pub fn set_rumble(&mut self, strength: f32) {
+        if self.settings.rumble {
+            self.backend.set_rumble(strength);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockState {
+        events: VecDeque<PadEvent>,
+        rumble: f32,
+    }
+
+    /// A virtual gamepad, whose events are queued by the test
+    struct MockBackend(Rc<RefCell<MockState>>);
+
+    impl GamepadBackend for MockBackend {
+        fn next_event(&mut self) -> Option<PadEvent> {
+            self.0.borrow_mut().events.pop_front()
+        }
+
+        fn set_rumble(&mut self, strength: f32) {
+            self.0.borrow_mut().rumble = strength;
+        }
+    }
+
+    fn mock_gamepad(settings: GamepadSettings) -> (Gamepad, Rc<RefCell<MockState>>) {
+        let state = Rc::new(RefCell::new(MockState::default()));
+        let gamepad = Gamepad::new(Box::new(MockBackend(state.clone())), settings);
+        (gamepad, state)
+    }
+
+    fn send(gamepad: &mut Gamepad, state: &RefCell<MockState>, events: &[PadEvent]) -> Vec<String> {
+        state.borrow_mut().events.extend(events);
+        gamepad
+            .poll()
+            .into_iter()
+            .map(|(keycode, pressed)| format!("{keycode:?} {pressed}"))
+            .collect()
+    }
+
+    #[test]
+    fn maps_buttons() {
+        let settings = GamepadSettings {
+            a: PadButton::South,
+            b: PadButton::East,
+            ..Default::default()
+        };
+        let (mut gamepad, state) = mock_gamepad(settings);
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[
+                PadEvent::Button(0, PadButton::South, true),
+                PadEvent::Button(0, PadButton::North, true),
+                PadEvent::Button(0, PadButton::Start, true),
+            ],
+        );
+        assert_eq!(changes, ["A true", "Start true"]);
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[PadEvent::Button(0, PadButton::South, false)],
+        );
+        assert_eq!(changes, ["A false"]);
+    }
+
+    #[test]
+    fn stick_has_deadzone() {
+        let (mut gamepad, state) = mock_gamepad(GamepadSettings::default());
+
+        let changes = send(&mut gamepad, &state, &[PadEvent::LeftStick(0, 0.3, -0.2)]);
+        assert!(changes.is_empty());
+
+        let changes = send(&mut gamepad, &state, &[PadEvent::LeftStick(0, 0.8, -0.9)]);
+        assert_eq!(changes, ["Down true", "Right true"]);
+
+        let changes = send(&mut gamepad, &state, &[PadEvent::LeftStick(0, 0.0, 0.0)]);
+        assert_eq!(changes, ["Down false", "Right false"]);
+    }
+
+    #[test]
+    fn stick_and_dpad_share_directions() {
+        let (mut gamepad, state) = mock_gamepad(GamepadSettings::default());
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[
+                PadEvent::Button(0, PadButton::DPadUp, true),
+                PadEvent::LeftStick(0, 0.0, 1.0),
+                PadEvent::Button(0, PadButton::DPadUp, false),
+            ],
+        );
+        assert_eq!(changes, ["Up true"]);
+
+        let changes = send(&mut gamepad, &state, &[PadEvent::LeftStick(0, 0.0, 0.0)]);
+        assert_eq!(changes, ["Up false"]);
+    }
+
+    #[test]
+    fn disconnect_releases_keys() {
+        let (mut gamepad, state) = mock_gamepad(GamepadSettings::default());
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[
+                PadEvent::Button(0, PadButton::East, true),
+                PadEvent::LeftStick(0, -1.0, 0.0),
+                PadEvent::Disconnected(0),
+            ],
+        );
+        assert_eq!(changes, ["A true", "Left true", "Left false", "A false"]);
+    }
+
+    #[test]
+    fn disconnect_keeps_other_pads() {
+        let (mut gamepad, state) = mock_gamepad(GamepadSettings::default());
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[
+                PadEvent::Button(0, PadButton::East, true),
+                PadEvent::Button(1, PadButton::East, true),
+                PadEvent::Button(1, PadButton::Start, true),
+                PadEvent::Disconnected(1),
+            ],
+        );
+        assert_eq!(changes, ["A true", "Start true", "Start false"]);
+
+        let changes = send(
+            &mut gamepad,
+            &state,
+            &[PadEvent::Button(0, PadButton::East, false)],
+        );
+        assert_eq!(changes, ["A false"]);
+    }
+
+    #[test]
+    fn rumble_can_be_turned_off() {
+        let (mut gamepad, state) = mock_gamepad(GamepadSettings::default());
+        gamepad.set_rumble(0.5);
+        assert_eq!(state.borrow().rumble, 0.5);
+
+        let settings = GamepadSettings {
+            rumble: false,
+            ..Default::default()
+        };
+        let (mut gamepad, state) = mock_gamepad(settings);
+        gamepad.set_rumble(0.5);
+        assert_eq!(state.borrow().rumble, 0.0);
+    }
+}
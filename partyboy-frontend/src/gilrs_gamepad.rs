@@ -0,0 +1,150 @@
+use gilrs::{
+    Axis, Button, Event, EventType, GamepadId, Gilrs,
+    ff::{self, BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks},
+};
+
+use crate::gamepad::{GamepadBackend, PadButton, PadEvent};
+
+/// Reads the gamepads of the host through gilrs
+pub struct GilrsBackend {
+    gilrs: Gilrs,
+    /// Plays on every gamepad that supports it while the strength is above 0
+    rumble: Option<Effect>,
+    strength: f32,
+}
+
+impl GilrsBackend {
+    pub fn new() -> Option<Self> {
+        let gilrs = Gilrs::new()
+            .inspect_err(|e| log::error!("Unable to read gamepads: {}", e))
+            .ok()?;
+        for (_, gamepad) in gilrs.gamepads() {
+            log::info!("Gamepad connected: {}", gamepad.name());
+        }
+
+        Some(Self {
+            gilrs,
+            rumble: None,
+            strength: 0.0,
+        })
+    }
+
+    /// Starts rumbling on the connected gamepads, if any of them can
+    fn start_rumble(&mut self) -> Result<Option<Effect>, ff::Error> {
+        let gamepads: Vec<GamepadId> = self
+            .gilrs
+            .gamepads()
+            .filter(|(_, gamepad)| gamepad.is_ff_supported())
+            .map(|(id, _)| id)
+            .collect();
+        if gamepads.is_empty() {
+            return Ok(None);
+        }
+
+        // repeated until it's dropped
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong {
+                    magnitude: u16::MAX,
+                },
+                scheduling: Replay {
+                    play_for: Ticks::from_ms(100),
+                    ..Default::default()
+                },
+                envelope: Default::default(),
+            })
+            .gain(self.strength)
+            .gamepads(&gamepads)
+            .finish(&mut self.gilrs)?;
+        effect.play()?;
+
+        Ok(Some(effect))
+    }
+
+    /// Restarts the rumble so it plays on the gamepads that are connected now
+    fn on_hotplug(&mut self) {
+        self.rumble = None;
+        self.set_rumble(self.strength);
+    }
+}
+
+fn pad_button(button: Button) -> Option<PadButton> {
+    let button = match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::North => PadButton::North,
+        Button::West => PadButton::West,
+        Button::LeftTrigger => PadButton::LeftTrigger,
+        Button::LeftTrigger2 => PadButton::LeftTrigger2,
+        Button::RightTrigger => PadButton::RightTrigger,
+        Button::RightTrigger2 => PadButton::RightTrigger2,
+        Button::Select => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::LeftThumb => PadButton::LeftThumb,
+        Button::RightThumb => PadButton::RightThumb,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
+
+impl GamepadBackend for GilrsBackend {
+    fn next_event(&mut self) -> Option<PadEvent> {
+        while let Some(Event { id, event, .. }) = self.gilrs.next_event() {
+            let event = match event {
+                EventType::ButtonPressed(button, _) => {
+                    pad_button(button).map(|button| PadEvent::Button(id.into(), button, true))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    pad_button(button).map(|button| PadEvent::Button(id.into(), button, false))
+                }
+                EventType::AxisChanged(Axis::LeftStickX | Axis::LeftStickY, _, _) => {
+                    let gamepad = self.gilrs.gamepad(id);
+                    Some(PadEvent::LeftStick(
+                        id.into(),
+                        gamepad.value(Axis::LeftStickX),
+                        gamepad.value(Axis::LeftStickY),
+                    ))
+                }
+                EventType::Connected => {
+                    log::info!("Gamepad connected: {}", self.gilrs.gamepad(id).name());
+                    self.on_hotplug();
+                    None
+                }
+                EventType::Disconnected => {
+                    log::info!("Gamepad disconnected: {}", self.gilrs.gamepad(id).name());
+                    self.on_hotplug();
+                    Some(PadEvent::Disconnected(id.into()))
+                }
+                _ => None,
+            };
+
+            if event.is_some() {
+                return event;
+            }
+        }
+
+        None
+    }
+
+    fn set_rumble(&mut self, strength: f32) {
+        self.strength = strength;
+
+        if strength <= 0.0 {
+            // dropping the effect stops it
+            self.rumble = None;
+            return;
+        }
+
+        let result = match &self.rumble {
+            Some(effect) => effect.set_gain(strength),
+            None => self.start_rumble().map(|effect| self.rumble = effect),
+        };
+        if let Err(e) = result {
+            log::error!("Unable to rumble: {}", e);
+        }
+    }
+}
@@ -1,17 +1,29 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 
-use emu_thread::EmuThreadHandle;
-use input::try_into_gameboy_input;
+use capture::RecordingFormat;
+use emu_thread::{EmuOptions, EmuThreadHandle};
+use filters::Filter;
+use gamepad::Gamepad;
+use gilrs_gamepad::GilrsBackend;
 use logging::init_logger;
 use msgs::MsgFromGb;
-use partyboy_core::ppu::rgb::Rgb;
+use osd::{Osd, RED, WHITE};
+use partyboy_core::{
+    camera::StillImage,
+    ppu::{cgb_palette::BootPaletteCombo, frame_blend::FrameBlendMode, rgb::ColorCorrection},
+    sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH},
+    symbols::SymbolTable,
+    trace::{TraceFormat, Tracer},
+};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use crossbeam::channel::{Receiver, Sender};
 use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
-use saves::{read_save_file, write_save_file};
+use roms::Game;
+use settings::{Hotkey, Settings};
+use speed::{NORMAL_SPEED, SPEEDS};
 use winit::{
     application::ApplicationHandler,
     dpi::LogicalSize,
@@ -19,21 +31,113 @@ use winit::{
     event_loop::{ActiveEventLoop, EventLoop},
     keyboard::{Key, NamedKey},
     platform::modifier_supplement::KeyEventExtModifierSupplement,
-    window::{Window, WindowId},
+    window::{Fullscreen, Window, WindowId},
 };
 
 use crate::msgs::MsgToGb;
 
+mod av_recording;
+mod capture;
 mod emu_thread;
-mod input;
+mod filters;
+mod gamepad;
+mod gilrs_gamepad;
 mod logging;
 mod msgs;
+mod osd;
+mod palettes;
+mod patches;
+mod roms;
 mod saves;
+mod settings;
+mod speed;
 
-pub const SCALE: u32 = 2;
 pub const WIDTH: u32 = 160;
 pub const HEIGHT: u32 = 144;
 
+/// Mirrors [`ColorCorrection`], in the order `L` cycles through them
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ColorCorrectionArg {
+    None,
+    Byuu,
+    ModernBalanced,
+    GbaBacklit,
+}
+
+impl ColorCorrectionArg {
+    fn next(self) -> Self {
+        match self {
+            Self::None => Self::Byuu,
+            Self::Byuu => Self::ModernBalanced,
+            Self::ModernBalanced => Self::GbaBacklit,
+            Self::GbaBacklit => Self::None,
+        }
+    }
+}
+
+impl From<ColorCorrectionArg> for ColorCorrection {
+    fn from(arg: ColorCorrectionArg) -> Self {
+        match arg {
+            ColorCorrectionArg::None => ColorCorrection::None,
+            ColorCorrectionArg::Byuu => ColorCorrection::Byuu,
+            ColorCorrectionArg::ModernBalanced => ColorCorrection::ModernBalanced,
+            ColorCorrectionArg::GbaBacklit => ColorCorrection::GbaBacklit,
+        }
+    }
+}
+
+/// Mirrors [`FrameBlendMode`]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FrameBlendingArg {
+    Mix,
+    LcdResponse,
+}
+
+impl From<FrameBlendingArg> for FrameBlendMode {
+    fn from(arg: FrameBlendingArg) -> Self {
+        match arg {
+            FrameBlendingArg::Mix => FrameBlendMode::Mix,
+            FrameBlendingArg::LcdResponse => FrameBlendMode::LcdResponse,
+        }
+    }
+}
+
+/// Mirrors [`BootPaletteCombo`]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PaletteComboArg {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+impl From<PaletteComboArg> for BootPaletteCombo {
+    fn from(arg: PaletteComboArg) -> Self {
+        match arg {
+            PaletteComboArg::Up => BootPaletteCombo::Up,
+            PaletteComboArg::UpA => BootPaletteCombo::UpA,
+            PaletteComboArg::UpB => BootPaletteCombo::UpB,
+            PaletteComboArg::Left => BootPaletteCombo::Left,
+            PaletteComboArg::LeftA => BootPaletteCombo::LeftA,
+            PaletteComboArg::LeftB => BootPaletteCombo::LeftB,
+            PaletteComboArg::Down => BootPaletteCombo::Down,
+            PaletteComboArg::DownA => BootPaletteCombo::DownA,
+            PaletteComboArg::DownB => BootPaletteCombo::DownB,
+            PaletteComboArg::Right => BootPaletteCombo::Right,
+            PaletteComboArg::RightA => BootPaletteCombo::RightA,
+            PaletteComboArg::RightB => BootPaletteCombo::RightB,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(version = "1.0", about = "A Gameboy color emulator")]
 struct Args {
@@ -41,23 +145,173 @@ struct Args {
     #[arg(short, long)]
     rom: Option<String>,
 
-    /// The path to the bios to use.
+    /// The path to the bios to use, instead of the one in the settings file.
     #[arg(short, long)]
     bios: Option<String>,
 
     /// Enables file logging.
     #[arg(short, long)]
     log: bool,
+
+    /// Writes a gameboy-doctor style trace of every executed instruction to the given file.
+    #[arg(long)]
+    trace: Option<String>,
+
+    /// Adds cycle counts, LY, banks and disassembly to each trace line.
+    #[arg(long, requires = "trace")]
+    trace_detailed: bool,
+
+    /// A .sym file whose labels annotate detailed trace lines.
+    #[arg(long, requires = "trace_detailed")]
+    symbols: Option<String>,
+
+    /// Runs games that support it in Super Game Boy mode, with SGB palettes.
+    #[arg(long)]
+    sgb: bool,
+
+    /// Shows the 256x224 Super Game Boy screen, including the border.
+    #[arg(long, requires = "sgb")]
+    sgb_border: bool,
+
+    /// Attaches a Game Boy Printer, printed images are saved as PNGs in the given directory.
+    #[arg(long)]
+    printer: Option<String>,
+
+    /// An image file for the Game Boy Camera to see, instead of its test pattern.
+    #[arg(long)]
+    camera_image: Option<String>,
+
+    /// Writes a save file even for carts without a battery.
+    #[arg(long)]
+    save_without_battery: bool,
+
+    /// Mutes the audio in slow motion and fast-forward, instead of keeping its pitch.
+    #[arg(long)]
+    mute_speed_changes: bool,
+
+    /// How Game Boy Color colors are adjusted to look like they did on the real screen.
+    #[arg(long, value_enum, default_value_t = ColorCorrectionArg::None)]
+    color_correction: ColorCorrectionArg,
+
+    /// Blends frames together like the slow LCD did, some games flicker without it.
+    #[arg(long, value_enum)]
+    frame_blending: Option<FrameBlendingArg>,
+
+    /// Colors Game Boy games with the palette the CGB bios gives this button combo, without a bios.
+    #[arg(long, value_enum)]
+    palette_combo: Option<PaletteComboArg>,
+
+    /// A file with 12 hex colors to color Game Boy games with, without a bios.
+    #[arg(long, conflicts_with = "palette_combo")]
+    palette: Option<String>,
+
+    /// Reads the settings from this file instead of the one in the config directory.
+    #[arg(long)]
+    config: Option<String>,
+
+    /// The size of the window, in multiples of the screen.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=8))]
+    scale: Option<u32>,
+
+    /// The audio volume, from 0.0 to 1.0.
+    #[arg(long)]
+    volume: Option<f32>,
+
+    /// How far ahead of the audio that is playing the emulator runs, in milliseconds.
+    #[arg(long)]
+    audio_latency: Option<u32>,
+
+    /// Upscales the screen before it's drawn, G cycles through them.
+    #[arg(long, value_enum, default_value_t = Filter::Nearest)]
+    filter: Filter,
+
+    /// Records every emulated frame and audio sample to <RECORD>.y4m and <RECORD>.wav, unaffected by turbo and speed changes.
+    #[arg(long)]
+    record: Option<String>,
+
+    /// The format of recordings started with F9.
+    #[arg(long, value_enum, default_value_t = RecordingFormat::Gif)]
+    recording_format: RecordingFormat,
+
+    /// How many times larger recordings are than the screen.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=8))]
+    recording_scale: u32,
+}
+
+impl Args {
+    fn screen_size(&self) -> (u32, u32) {
+        if self.sgb_border {
+            (SGB_SCREEN_WIDTH as u32, SGB_SCREEN_HEIGHT as u32)
+        } else {
+            (WIDTH, HEIGHT)
+        }
+    }
 }
 
 struct App {
     args: Args,
+    settings: Settings,
     tx: Sender<MsgToGb>,
     rx: Receiver<MsgFromGb>,
-    handle: Option<JoinHandle<Option<Box<[u8]>>>>,
-    frame_to_draw: Option<Vec<Rgb>>,
+    handle: Option<JoinHandle<()>>,
+    frame_to_draw: Option<Vec<u8>>,
     window: Option<Arc<Window>>,
     pixels: Option<Pixels<'static>>,
+    gamepad: Option<Gamepad>,
+    osd: Osd,
+    paused: bool,
+    turbo: bool,
+    rewinding: bool,
+    recording: bool,
+    /// Index into [`SPEEDS`]
+    speed: usize,
+}
+
+impl App {
+    fn set_speed(&mut self, speed: usize) {
+        self.speed = speed;
+        self.tx.send(MsgToGb::Speed(speed)).unwrap();
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        self.args.filter = filter;
+        log::info!("Filter: {:?}", filter);
+
+        let (width, height) = self.args.screen_size();
+        let pixels = self.pixels.as_mut().expect("pixels not initialized");
+        if let Err(e) = pixels.resize_buffer(width * filter.factor(), height * filter.factor()) {
+            log::error!("Unable to resize pixel buffer: {}", e);
+        }
+        self.osd.message(format!("Filter: {:?}", filter));
+    }
+
+    /// What the OSD shows in the top right corner
+    fn indicators(&self) -> Vec<(String, [u8; 4])> {
+        let mut indicators = Vec::new();
+        if self.recording {
+            indicators.push(("REC".to_owned(), RED));
+        }
+        if self.paused {
+            indicators.push(("PAUSED".to_owned(), WHITE));
+        }
+        if self.rewinding {
+            indicators.push(("<< REWIND".to_owned(), WHITE));
+        } else if self.turbo {
+            indicators.push(("TURBO >>".to_owned(), WHITE));
+        }
+        if self.speed != NORMAL_SPEED {
+            indicators.push((format!("{}X", SPEEDS[self.speed]), WHITE));
+        }
+        indicators
+    }
+
+    fn title(&self, fps: f64) -> String {
+        match (self.paused, self.speed) {
+            (true, _) => "Paused".to_owned(),
+            (false, NORMAL_SPEED) => format!("{:.2}", fps),
+            (false, speed) => format!("{:.2} ({}x)", fps, SPEEDS[speed]),
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -66,14 +320,17 @@ impl ApplicationHandler for App {
             return;
         }
 
-        let size = LogicalSize::new((WIDTH * SCALE) as f64, (HEIGHT * SCALE) as f64);
+        let (width, height) = self.args.screen_size();
+        let scale = self.settings.window.scale.max(1);
+        let size = LogicalSize::new((width * scale) as f64, (height * scale) as f64);
         let window = Arc::new(
             event_loop
                 .create_window(
                     Window::default_attributes()
                         .with_title("Partyboy 🎉")
                         .with_inner_size(size)
-                        .with_resizable(false),
+                        .with_min_inner_size(LogicalSize::new(width as f64, height as f64))
+                        .with_resizable(true),
                 )
                 .expect("Unable to create window"),
         );
@@ -81,7 +338,8 @@ impl ApplicationHandler for App {
         let window_size = window.inner_size();
         let surface_texture =
             SurfaceTexture::new(window_size.width, window_size.height, window.clone());
-        let pixels = PixelsBuilder::new(WIDTH, HEIGHT, surface_texture)
+        let factor = self.args.filter.factor();
+        let pixels = PixelsBuilder::new(width * factor, height * factor, surface_texture)
             .enable_vsync(false)
             .build()
             .expect("Unable to create pixel buffer");
@@ -105,14 +363,32 @@ impl ApplicationHandler for App {
 
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Focused(false) => self.tx.send(MsgToGb::Autosave).unwrap(),
+            WindowEvent::DroppedFile(path) => self.tx.send(MsgToGb::Load(path)).unwrap(),
+            WindowEvent::Resized(size) => {
+                let pixels = self.pixels.as_mut().expect("pixels not initialized");
+                if let Err(e) = pixels.resize_surface(size.width, size.height) {
+                    log::error!("Unable to resize surface: {}", e);
+                }
+            }
             WindowEvent::RedrawRequested => {
+                let (width, height) = self.args.screen_size();
+                let indicators = self.indicators();
                 let pixels = self.pixels.as_mut().expect("pixels not initialized");
                 if let Some(frame) = &self.frame_to_draw {
-                    let flat_frame = frame
-                        .iter()
-                        .flat_map(|px| [px.r, px.g, px.b, 0xFF])
-                        .collect::<Vec<_>>();
-                    pixels.frame_mut().copy_from_slice(flat_frame.as_slice());
+                    self.args.filter.apply(
+                        frame,
+                        width as usize,
+                        height as usize,
+                        pixels.frame_mut(),
+                    );
+                    self.osd.draw(
+                        pixels.frame_mut(),
+                        width as usize,
+                        height as usize,
+                        self.args.filter.factor() as usize,
+                        &indicators,
+                    );
                 }
 
                 if let Err(e) = pixels.render() {
@@ -122,29 +398,65 @@ impl ApplicationHandler for App {
             }
             WindowEvent::KeyboardInput { event, .. } if !event.repeat => {
                 let key = event.key_without_modifiers();
-                if let Some(gb_input) = try_into_gameboy_input(key.as_ref()) {
+                if let Some(gb_input) = self.settings.keys.keycode(key.as_ref()) {
                     match event.state {
                         ElementState::Pressed => self.tx.send(MsgToGb::KeyDown(gb_input)).unwrap(),
                         ElementState::Released => self.tx.send(MsgToGb::KeyUp(gb_input)).unwrap(),
                     }
                 }
 
-                match key.as_ref() {
-                    Key::Named(NamedKey::Escape) => {
-                        event_loop.exit();
+                if key == Key::Named(NamedKey::Escape) {
+                    event_loop.exit();
+                    return;
+                }
+
+                let Some(hotkey) = self.settings.hotkeys.hotkey(key.as_ref()) else {
+                    return;
+                };
+                let pressed = event.state.is_pressed();
+                match hotkey {
+                    Hotkey::Turbo => {
+                        self.turbo = pressed;
+                        self.tx.send(MsgToGb::Turbo(pressed)).unwrap();
                     }
-                    Key::Named(NamedKey::Space) => {
-                        self.tx
-                            .send(MsgToGb::Turbo(event.state.is_pressed()))
-                            .unwrap();
+                    Hotkey::Rewind => {
+                        self.rewinding = pressed;
+                        self.tx.send(MsgToGb::Rewind(pressed)).unwrap();
+                    }
+                    Hotkey::Pause if pressed => {
+                        self.paused = !self.paused;
+                        self.tx.send(MsgToGb::Pause(self.paused)).unwrap();
+                        window.set_title(&self.title(0.0));
                     }
-                    Key::Character("q") => {
+                    Hotkey::FrameAdvance if pressed => self.tx.send(MsgToGb::FrameAdvance).unwrap(),
+                    Hotkey::Slower if pressed && self.speed > 0 => self.set_speed(self.speed - 1),
+                    Hotkey::Faster if pressed && self.speed + 1 < SPEEDS.len() => {
+                        self.set_speed(self.speed + 1)
+                    }
+                    Hotkey::ColorCorrection if pressed => {
+                        self.args.color_correction = self.args.color_correction.next();
+                        log::info!("Color correction: {:?}", self.args.color_correction);
+                        self.osd.message(format!(
+                            "Color correction: {:?}",
+                            self.args.color_correction
+                        ));
                         self.tx
-                            .send(MsgToGb::Rewind(event.state.is_pressed()))
+                            .send(MsgToGb::ColorCorrection(self.args.color_correction.into()))
                             .unwrap();
                     }
-                    Key::Character("c") => self.tx.send(MsgToGb::SaveSnapshot).unwrap(),
-                    Key::Character("v") => self.tx.send(MsgToGb::LoadSnapshot).unwrap(),
+                    Hotkey::Fullscreen if pressed => {
+                        let fullscreen = match window.fullscreen() {
+                            Some(_) => None,
+                            None => Some(Fullscreen::Borderless(None)),
+                        };
+                        window.set_fullscreen(fullscreen);
+                    }
+                    Hotkey::Filter if pressed => self.set_filter(self.args.filter.next()),
+                    Hotkey::Screenshot if pressed => self.tx.send(MsgToGb::Screenshot).unwrap(),
+                    Hotkey::Record if pressed => self.tx.send(MsgToGb::ToggleRecording).unwrap(),
+                    Hotkey::SaveState if pressed => self.tx.send(MsgToGb::SaveSnapshot).unwrap(),
+                    Hotkey::LoadState if pressed => self.tx.send(MsgToGb::LoadSnapshot).unwrap(),
+                    Hotkey::Osd if pressed => self.osd.enabled = !self.osd.enabled,
                     _ => {}
                 }
             }
@@ -157,10 +469,32 @@ impl ApplicationHandler for App {
             return;
         };
 
+        if let Some(gamepad) = &mut self.gamepad {
+            for (gb_input, pressed) in gamepad.poll() {
+                let msg = if pressed {
+                    MsgToGb::KeyDown(gb_input)
+                } else {
+                    MsgToGb::KeyUp(gb_input)
+                };
+                self.tx.send(msg).unwrap();
+            }
+        }
+
         for msg in self.rx.try_iter() {
             match msg {
                 MsgFromGb::Frame(fb) => self.frame_to_draw = Some(fb),
-                MsgFromGb::Fps(fps) => window.set_title(format!("{:.2}", fps).as_str()),
+                MsgFromGb::Fps(fps) => {
+                    self.osd.set_fps(fps);
+                    window.set_title(&self.title(fps));
+                }
+                MsgFromGb::AudioBuffer(fill) => self.osd.set_audio_buffer(fill),
+                MsgFromGb::Recording(recording) => self.recording = recording,
+                MsgFromGb::Message(text) => self.osd.message(text),
+                MsgFromGb::Rumble(strength) => {
+                    if let Some(gamepad) = &mut self.gamepad {
+                        gamepad.set_rumble(strength);
+                    }
+                }
             }
         }
 
@@ -172,20 +506,12 @@ impl ApplicationHandler for App {
             .send(MsgToGb::Shutdown)
             .expect("Unable to signal emu thread to stop");
 
-        let ram = self
-            .handle
+        // the emu thread writes the save file before it stops
+        self.handle
             .take()
             .expect("Emulator JoinHandle should not be empty")
             .join()
             .expect("Unable to join emu thread");
-
-        if let Some(ram) = ram {
-            if let Some(path) = self.args.rom.as_ref() {
-                write_save_file(&PathBuf::from(path), &ram);
-            }
-        } else {
-            log::warn!("Emulator was unable to read cart ram, no save file was (over)written");
-        }
     }
 }
 
@@ -195,32 +521,125 @@ fn main() {
     #[cfg(debug_assertions)]
     init_logger(args.log);
 
-    let rom = args
+    let game = args
         .rom
         .as_ref()
-        .map(|path| std::fs::read(path).expect("Unable to read game file"));
+        .map(|path| Game::load(&PathBuf::from(path)).expect("Unable to read game file"));
 
-    let ram = args
-        .rom
+    let settings_path = args
+        .config
         .as_ref()
-        .and_then(|path| read_save_file(&PathBuf::from(path)));
+        .map(PathBuf::from)
+        .or_else(Settings::default_path);
+    let mut settings = settings_path
+        .map(|path| {
+            Settings::load(&path).unwrap_or_else(|e| {
+                log::error!(
+                    "Unable to read {}, using the defaults: {}",
+                    path.display(),
+                    e
+                );
+                Settings::default()
+            })
+        })
+        .unwrap_or_default();
+    if let Some(bios) = &args.bios {
+        settings.bios = Some(PathBuf::from(bios));
+    }
+    if let Some(scale) = args.scale {
+        settings.window.scale = scale;
+    }
+    if let Some(volume) = args.volume {
+        settings.audio.volume = volume;
+    }
+    if let Some(latency) = args.audio_latency {
+        settings.audio.latency_ms = latency;
+    }
 
-    let bios = args
+    let bios = settings
         .bios
         .as_ref()
         .map(|path| std::fs::read(path).expect("Unable to read bios file"));
 
-    let EmuThreadHandle { tx, rx, handle } = emu_thread::new(rom, bios, ram);
+    let trace = args.trace.as_ref().map(|path| {
+        let file = std::fs::File::create(path).expect("Unable to create trace file");
+        let format = if args.trace_detailed {
+            TraceFormat::Detailed
+        } else {
+            TraceFormat::GameboyDoctor
+        };
+        let tracer = Tracer::new(format, std::io::BufWriter::new(file));
+
+        match &args.symbols {
+            Some(path) => {
+                let symbols = std::fs::read_to_string(path).expect("Unable to read symbol file");
+                let symbols = SymbolTable::parse(&symbols).expect("Unable to parse symbol file");
+                tracer.with_symbols(symbols)
+            }
+            None => tracer,
+        }
+    });
+
+    let camera_image = args.camera_image.as_ref().map(|path| {
+        let image = image::open(path)
+            .expect("Unable to read camera image")
+            .into_luma8();
+        StillImage::from_luma(
+            image.width() as usize,
+            image.height() as usize,
+            image.as_raw(),
+        )
+    });
+
+    let custom_palette = args.palette.as_ref().map(|path| {
+        palettes::read_palette_file(Path::new(path)).expect("Unable to read palette file")
+    });
+
+    let options = EmuOptions {
+        trace,
+        sgb: args.sgb,
+        sgb_border: args.sgb_border,
+        printer: args.printer.as_ref().map(|dir| {
+            std::fs::create_dir_all(dir).expect("Unable to create printer output directory");
+            PathBuf::from(dir)
+        }),
+        camera_image,
+        save_without_battery: args.save_without_battery,
+        mute_speed_changes: args.mute_speed_changes,
+        color_correction: args.color_correction.into(),
+        frame_blending: args.frame_blending.map(Into::into),
+        palette_combo: args.palette_combo.map(Into::into),
+        custom_palette,
+        record: args.record.as_ref().map(PathBuf::from),
+        recording_format: args.recording_format,
+        volume: settings.audio.volume.clamp(0.0, 1.0),
+        audio_latency_ms: settings.audio.latency_ms,
+        recording_scale: args.recording_scale,
+    };
+    let EmuThreadHandle { tx, rx, handle } = emu_thread::new(game, bios, options);
+
+    let gamepad = GilrsBackend::new()
+        .map(|backend| Gamepad::new(Box::new(backend), settings.gamepad.clone()));
+
+    let osd = Osd::new(settings.window.osd);
 
     let event_loop = EventLoop::new().expect("Unable to create event loop");
     let mut app = App {
         args,
+        settings,
         tx,
         rx,
         handle: Some(handle),
         frame_to_draw: None,
         window: None,
         pixels: None,
+        gamepad,
+        osd,
+        paused: false,
+        turbo: false,
+        rewinding: false,
+        recording: false,
+        speed: NORMAL_SPEED,
     };
 
     event_loop
@@ -1,20 +1,43 @@
-use partyboy_core::{input::Keycode, ppu::rgb::Rgb};
+use std::path::PathBuf;
+
+use partyboy_core::{input::Keycode, ppu::rgb::ColorCorrection};
 
 pub enum MsgFromGb {
-    Frame(Vec<Rgb>),
+    /// RGBA pixels, ready to be copied into the window
+    Frame(Vec<u8>),
     Fps(f64),
+    /// How full the audio buffer is, 1.0 being the configured latency
+    AudioBuffer(f32),
+    /// Whether a recording started with [`MsgToGb::ToggleRecording`] or `--record` is running
+    Recording(bool),
+    /// Shown on the OSD for a moment
+    Message(String),
+    /// How strong the gamepad should rumble, from 0.0 to 1.0
+    Rumble(f32),
 }
 
 pub enum MsgToGb {
-    #[allow(dead_code)]
-    Load,
+    /// Switch to the rom at this path, which can also be a zip file
+    Load(PathBuf),
     KeyDown(Keycode),
     KeyUp(Keycode),
     Turbo(bool),
     Rewind(bool),
+    Pause(bool),
+    /// Run a single frame while paused
+    FrameAdvance,
+    /// Index into [`crate::speed::SPEEDS`]
+    Speed(usize),
+    ColorCorrection(ColorCorrection),
 
     SaveSnapshot,
     LoadSnapshot,
+    /// Save the current frame as a PNG next to the rom
+    Screenshot,
+    /// Start recording a GIF or APNG next to the rom, or finish the current one
+    ToggleRecording,
+    /// Write the save file now if the cartridge ram changed
+    Autosave,
 
     Shutdown,
 }
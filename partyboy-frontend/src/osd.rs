@@ -0,0 +1,252 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+type Px = [u8; 4];
+
+pub const WHITE: Px = [0xFF, 0xFF, 0xFF, 0xFF];
+pub const RED: Px = [0xFF, 0x40, 0x40, 0xFF];
+const SHADOW: Px = [0x00, 0x00, 0x00, 0xFF];
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// Between characters and lines, in screen pixels
+const SPACING: usize = 1;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + SPACING + 1;
+/// Between the text and the edges of the screen
+const MARGIN: usize = 2;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const MAX_MESSAGES: usize = 3;
+
+/// Text drawn over the frame. Stats go in the top left corner, indicators like the speed
+/// in the top right one and messages in the bottom left one, until they expire
+pub struct Osd {
+    pub enabled: bool,
+    fps: f64,
+    /// How full the audio buffer is, 1.0 being the configured latency
+    audio_buffer: f32,
+    messages: VecDeque<(String, Instant)>,
+}
+
+impl Osd {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            fps: 0.0,
+            audio_buffer: 0.0,
+            messages: VecDeque::new(),
+        }
+    }
+
+    pub fn set_fps(&mut self, fps: f64) {
+        self.fps = fps;
+    }
+
+    pub fn set_audio_buffer(&mut self, fill: f32) {
+        self.audio_buffer = fill;
+    }
+
+    /// Shows `text` for a couple of seconds
+    pub fn message(&mut self, text: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((text.into(), Instant::now()));
+    }
+
+    /// Draws onto the RGBA `frame`, which is `scale` times as wide and high as the
+    /// `width` x `height` screen. Indicators are stacked from the top
+    pub fn draw(
+        &mut self,
+        frame: &mut [u8],
+        width: usize,
+        height: usize,
+        scale: usize,
+        indicators: &[(String, Px)],
+    ) {
+        let now = Instant::now();
+        self.messages
+            .retain(|(_, shown)| now.duration_since(*shown) < MESSAGE_DURATION);
+
+        if !self.enabled {
+            return;
+        }
+
+        let mut canvas = Canvas {
+            frame,
+            width,
+            height,
+            scale,
+        };
+
+        canvas.text(MARGIN, MARGIN, &format!("{:.1} FPS", self.fps), WHITE);
+        let audio = format!("AUDIO {:.0}%", self.audio_buffer * 100.0);
+        canvas.text(MARGIN, MARGIN + LINE_HEIGHT, &audio, WHITE);
+
+        for (i, (text, color)) in indicators.iter().enumerate() {
+            let x = width.saturating_sub(MARGIN + text_width(text));
+            canvas.text(x, MARGIN + i * LINE_HEIGHT, text, *color);
+        }
+
+        // the newest message is at the bottom
+        for (i, (text, _)) in self.messages.iter().rev().enumerate() {
+            let y = height.saturating_sub(MARGIN + GLYPH_HEIGHT + i * LINE_HEIGHT);
+            canvas.text(MARGIN, y, text, WHITE);
+        }
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    (text.chars().count() * (GLYPH_WIDTH + SPACING)).saturating_sub(SPACING)
+}
+
+/// An RGBA buffer that is `scale` times the size of the screen, drawn on in screen pixels
+struct Canvas<'a> {
+    frame: &'a mut [u8],
+    width: usize,
+    height: usize,
+    scale: usize,
+}
+
+impl Canvas<'_> {
+    /// Draws `text` with its top left corner at `x`, `y`. It gets a shadow to stay readable
+    /// on light backgrounds, and is cut off at the edges of the screen
+    fn text(&mut self, x: usize, y: usize, text: &str, color: Px) {
+        for (offset, color) in [(1, SHADOW), (0, color)] {
+            for (i, c) in text.chars().enumerate() {
+                let glyph_x = x + i * (GLYPH_WIDTH + SPACING) + offset;
+                for (row_y, row) in glyph(c).into_iter().enumerate() {
+                    for col in 0..GLYPH_WIDTH {
+                        if row & (0b100 >> col) != 0 {
+                            self.pixel(glyph_x + col, y + row_y + offset, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn pixel(&mut self, x: usize, y: usize, color: Px) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let row_len = self.width * self.scale;
+        for dy in 0..self.scale {
+            let start = ((y * self.scale + dy) * row_len + x * self.scale) * 4;
+            for px in self.frame[start..start + self.scale * 4].chunks_exact_mut(4) {
+                px.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// The rows of a 3x5 character, top to bottom with the leftmost pixel in bit 2. Letters
+/// are all uppercase, and anything without a glyph is drawn as `?`
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Px = [0, 0, 0, 0];
+
+    #[test]
+    fn text_width_leaves_out_trailing_spacing() {
+        assert_eq!(text_width(""), 0);
+        assert_eq!(text_width("A"), 3);
+        assert_eq!(text_width("REC"), 11);
+    }
+
+    #[test]
+    fn unknown_characters_are_question_marks() {
+        let question_mark = [0b111, 0b001, 0b010, 0b000, 0b010];
+        assert_eq!(glyph('~'), question_mark);
+        assert_eq!(glyph('é'), question_mark);
+        assert_eq!(glyph('a'), glyph('A'));
+    }
+
+    #[test]
+    fn pixels_are_scaled_and_clipped() {
+        let (width, height, scale) = (3, 2, 2);
+        let mut frame = vec![0; width * height * scale * scale * 4];
+        let mut canvas = Canvas {
+            frame: &mut frame,
+            width,
+            height,
+            scale,
+        };
+
+        canvas.pixel(2, 1, WHITE);
+        canvas.pixel(3, 0, RED);
+        canvas.pixel(0, 2, RED);
+
+        let pixels = frame.chunks_exact(4).collect::<Vec<_>>();
+        #[rustfmt::skip]
+        let expected: [&[u8]; 24] = [
+            &BLACK, &BLACK, &BLACK, &BLACK, &BLACK, &BLACK,
+            &BLACK, &BLACK, &BLACK, &BLACK, &BLACK, &BLACK,
+            &BLACK, &BLACK, &BLACK, &BLACK, &WHITE, &WHITE,
+            &BLACK, &BLACK, &BLACK, &BLACK, &WHITE, &WHITE,
+        ];
+        assert_eq!(pixels, expected);
+    }
+}
@@ -0,0 +1,23 @@
+use std::{fs, path::Path};
+
+/// Reads a custom DMG palette: 12 hex RGB colors like `FFFFFF` or `#FFAD63`, separated by
+/// whitespace or commas. The first 4 are the background colors, then both object palettes
+pub fn read_palette_file(path: &Path) -> Result<Vec<u32>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let colors = contents
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|color| !color.is_empty())
+        .map(|color| {
+            let hex = color.trim_start_matches('#').trim_start_matches("0x");
+            u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|&color| color <= 0xFFFFFF)
+                .ok_or_else(|| format!("{color} is not a hex RGB color"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match colors.len() {
+        12 => Ok(colors),
+        n => Err(format!("expected 12 colors, found {n}")),
+    }
+}
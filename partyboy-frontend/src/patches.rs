@@ -0,0 +1,14 @@
+use std::{fs, path::Path};
+
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+/// Finds a patch next to the rom with the same name, i.e. `game.ips` for `game.gb`
+pub fn read_patch_file(rom_path: &Path) -> Option<Vec<u8>> {
+    let patch_path = PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.is_file())?;
+
+    log::info!("Loading patch {}...", patch_path.display());
+    fs::read(patch_path).ok()
+}
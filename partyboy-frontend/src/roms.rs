@@ -0,0 +1,115 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use zip::ZipArchive;
+
+use crate::{patches::read_patch_file, saves::read_save_file};
+
+const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "sgb"];
+
+/// A rom, with the save file and patch found next to it
+pub struct Game {
+    pub rom_path: PathBuf,
+    pub rom: Vec<u8>,
+    pub ram: Option<Vec<u8>>,
+    pub patch: Option<Vec<u8>>,
+}
+
+impl Game {
+    pub fn load(rom_path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            rom_path: rom_path.to_owned(),
+            rom: read_rom_file(rom_path)?,
+            ram: read_save_file(rom_path),
+            patch: read_patch_file(rom_path),
+        })
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|ext| extension.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Reads a rom, or the first rom inside a zip file
+fn read_rom_file(path: &Path) -> io::Result<Vec<u8>> {
+    if !has_extension(path, &["zip"]) {
+        return fs::read(path);
+    }
+
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(io::Error::other)?;
+    let index = (0..archive.len())
+        .find(|&index| {
+            archive
+                .name_for_index(index)
+                .is_some_and(|name| has_extension(Path::new(name), &ROM_EXTENSIONS))
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No rom in the zip file"))?;
+
+    let mut rom = Vec::new();
+    archive
+        .by_index(index)
+        .map_err(io::Error::other)?
+        .read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    /// Writes a zip with the given files to the temp dir
+    fn write_zip(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("partyboy_test_{}_{name}.zip", std::process::id()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for (file_name, contents) in files {
+            zip.start_file(*file_name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_first_rom_in_zip() {
+        let path = write_zip(
+            "roms",
+            &[
+                ("readme.txt", "readme"),
+                ("game.GBC", "rom"),
+                ("other.gb", "other rom"),
+            ],
+        );
+        let rom = read_rom_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(rom.unwrap(), b"rom");
+    }
+
+    #[test]
+    fn zip_without_rom() {
+        let path = write_zip(
+            "no_rom",
+            &[("readme.txt", "readme"), ("game.gb.txt", "not a rom")],
+        );
+        let err = read_rom_file(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No rom in the zip file");
+    }
+}
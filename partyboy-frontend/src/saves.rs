@@ -25,6 +25,10 @@ pub fn write_save_file(rom_path: &Path, bytes: &[u8]) {
         return;
     };
 
-    let _ = fs::write(save_file_path, bytes);
-    log::info!("Written save file...");
+    // write next to the old save and swap it in, so a crash mid-write can't corrupt it
+    let tmp_path = save_file_path.with_extension("sav.tmp");
+    match fs::write(&tmp_path, bytes).and_then(|_| fs::rename(&tmp_path, &save_file_path)) {
+        Ok(()) => log::info!("Written save file..."),
+        Err(e) => log::error!("Unable to write save file: {}", e),
+    }
 }
@@ -0,0 +1,404 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use partyboy_core::input::Keycode;
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, Visitor},
+};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::gamepad::GamepadSettings;
+
+/// Read from `settings.toml` in the `partyboy` config directory. Entries that are left out
+/// keep their defaults, and flags given on the command line take precedence. Misspelt
+/// entries are an error rather than silently left out
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// The bios to use when no `--bios` is given
+    pub bios: Option<PathBuf>,
+    pub window: WindowSettings,
+    pub audio: AudioSettings,
+    pub keys: KeyBindings,
+    pub hotkeys: Hotkeys,
+    pub gamepad: GamepadSettings,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSettings {
+    /// The size of the window, in multiples of the screen
+    pub scale: u32,
+    /// Whether the on-screen display is shown on start
+    pub osd: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            scale: 2,
+            osd: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioSettings {
+    /// From 0.0 to 1.0
+    pub volume: f32,
+    /// How far the emulator runs ahead of the audio that is playing
+    pub latency_ms: u32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            latency_ms: 40,
+        }
+    }
+}
+
+/// The keys for the Game Boy buttons. Keys are named by the character they type, or by
+/// their winit `NamedKey` like `Space`, `F11` or `ArrowUp`, ignoring case
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub up: String,
+    pub down: String,
+    pub left: String,
+    pub right: String,
+    pub a: String,
+    pub b: String,
+    pub start: String,
+    pub select: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: "w".to_owned(),
+            down: "s".to_owned(),
+            left: "a".to_owned(),
+            right: "d".to_owned(),
+            a: "o".to_owned(),
+            b: "k".to_owned(),
+            start: "m".to_owned(),
+            select: "n".to_owned(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keycode(&self, key: Key<&str>) -> Option<Keycode> {
+        self.bindings()
+            .into_iter()
+            .find(|(binding, _)| is_key(binding, &key))
+            .map(|(_, keycode)| keycode)
+    }
+
+    fn bindings(&self) -> [(&String, Keycode); 8] {
+        [
+            (&self.up, Keycode::Up),
+            (&self.down, Keycode::Down),
+            (&self.left, Keycode::Left),
+            (&self.right, Keycode::Right),
+            (&self.a, Keycode::A),
+            (&self.b, Keycode::B),
+            (&self.start, Keycode::Start),
+            (&self.select, Keycode::Select),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    Turbo,
+    Rewind,
+    Pause,
+    FrameAdvance,
+    Slower,
+    Faster,
+    ColorCorrection,
+    Filter,
+    Fullscreen,
+    Screenshot,
+    Record,
+    SaveState,
+    LoadState,
+    Osd,
+}
+
+/// The keys for everything besides the Game Boy buttons, named like in [`KeyBindings`]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hotkeys {
+    pub turbo: String,
+    pub rewind: String,
+    pub pause: String,
+    pub frame_advance: String,
+    pub slower: String,
+    pub faster: String,
+    pub color_correction: String,
+    pub filter: String,
+    pub fullscreen: String,
+    pub screenshot: String,
+    pub record: String,
+    pub save_state: String,
+    pub load_state: String,
+    pub osd: String,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            turbo: "Space".to_owned(),
+            rewind: "q".to_owned(),
+            pause: "p".to_owned(),
+            frame_advance: "f".to_owned(),
+            slower: "-".to_owned(),
+            faster: "=".to_owned(),
+            color_correction: "l".to_owned(),
+            filter: "g".to_owned(),
+            fullscreen: "F11".to_owned(),
+            screenshot: "F12".to_owned(),
+            record: "F9".to_owned(),
+            save_state: "c".to_owned(),
+            load_state: "v".to_owned(),
+            osd: "F3".to_owned(),
+        }
+    }
+}
+
+impl Hotkeys {
+    pub fn hotkey(&self, key: Key<&str>) -> Option<Hotkey> {
+        self.bindings()
+            .into_iter()
+            .find(|(binding, _)| is_key(binding, &key))
+            .map(|(_, hotkey)| hotkey)
+    }
+
+    fn bindings(&self) -> [(&String, Hotkey); 14] {
+        [
+            (&self.turbo, Hotkey::Turbo),
+            (&self.rewind, Hotkey::Rewind),
+            (&self.pause, Hotkey::Pause),
+            (&self.frame_advance, Hotkey::FrameAdvance),
+            (&self.slower, Hotkey::Slower),
+            (&self.faster, Hotkey::Faster),
+            (&self.color_correction, Hotkey::ColorCorrection),
+            (&self.filter, Hotkey::Filter),
+            (&self.fullscreen, Hotkey::Fullscreen),
+            (&self.screenshot, Hotkey::Screenshot),
+            (&self.record, Hotkey::Record),
+            (&self.save_state, Hotkey::SaveState),
+            (&self.load_state, Hotkey::LoadState),
+            (&self.osd, Hotkey::Osd),
+        ]
+    }
+}
+
+/// Whether `key` is the one `binding` names
+fn is_key(binding: &str, key: &Key<&str>) -> bool {
+    match key {
+        Key::Character(character) => binding.eq_ignore_ascii_case(character),
+        Key::Named(named) => binding.eq_ignore_ascii_case(&format!("{named:?}")),
+        _ => false,
+    }
+}
+
+/// Whether `binding` is a character or the name of a key, so it can ever match
+fn is_key_name(binding: &str) -> bool {
+    binding.chars().count() == 1
+        || named_keys()
+            .iter()
+            .any(|name| binding.eq_ignore_ascii_case(name))
+}
+
+/// The names of all winit `NamedKey`s. Its `Deserialize` hands them over when asked for an
+/// enum, which is all the deserializer here does
+fn named_keys() -> &'static [&'static str] {
+    struct VariantNames<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> Deserializer<'de> for VariantNames<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("only enums have variant names"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            variants: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = variants;
+            Err(de::Error::custom("only the variant names are needed"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map struct
+            identifier ignored_any
+        }
+    }
+
+    let mut names: &'static [&'static str] = &[];
+    let _ = NamedKey::deserialize(VariantNames(&mut names));
+    names
+}
+
+impl Settings {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("partyboy").join("settings.toml"))
+    }
+
+    /// Reads the settings file. When there is none yet, one with the defaults is written
+    /// so there is something to edit
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                let settings: Self = toml::from_str(&contents).map_err(|e| e.to_string())?;
+                settings.warn_unknown_keys();
+                Ok(settings)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let settings = Self::default();
+                settings.write(path);
+                Ok(settings)
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Points out bindings that don't name a key. They are kept, but never match
+    fn warn_unknown_keys(&self) {
+        let keys = self
+            .keys
+            .bindings()
+            .map(|(binding, keycode)| (binding, format!("{keycode:?}")));
+        let hotkeys = self
+            .hotkeys
+            .bindings()
+            .map(|(binding, hotkey)| (binding, format!("{hotkey:?}")));
+        for (binding, action) in keys.into_iter().chain(hotkeys) {
+            if !is_key_name(binding) {
+                log::warn!(
+                    "{binding:?} for {action} isn't the name of a key, it will never be pressed"
+                );
+            }
+        }
+    }
+
+    fn write(&self, path: &Path) {
+        let written = toml::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                fs::write(path, contents).map_err(|e| e.to_string())
+            });
+
+        match written {
+            Ok(()) => log::info!("Written default settings to {}", path.display()),
+            Err(e) => log::error!("Unable to write default settings: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::NamedKey;
+
+    use super::*;
+
+    #[test]
+    fn matches_characters_ignoring_case() {
+        assert!(is_key("w", &Key::Character("w")));
+        assert!(is_key("w", &Key::Character("W")));
+        assert!(is_key("-", &Key::Character("-")));
+        assert!(!is_key("w", &Key::Character("s")));
+    }
+
+    #[test]
+    fn matches_named_keys_ignoring_case() {
+        assert!(is_key("Space", &Key::Named(NamedKey::Space)));
+        assert!(is_key("f11", &Key::Named(NamedKey::F11)));
+        assert!(is_key("ARROWUP", &Key::Named(NamedKey::ArrowUp)));
+        assert!(!is_key("Space", &Key::Character(" ")));
+        assert!(!is_key("F1", &Key::Named(NamedKey::F11)));
+    }
+
+    #[test]
+    fn knows_key_names() {
+        assert!(is_key_name("w"));
+        assert!(is_key_name("-"));
+        assert!(is_key_name("Space"));
+        assert!(is_key_name("arrowup"));
+        assert!(is_key_name("F11"));
+        assert!(!is_key_name("Spcae"));
+        assert!(!is_key_name("Arrow Up"));
+        assert!(!is_key_name(""));
+    }
+
+    #[test]
+    fn default_bindings_are_key_names() {
+        let settings = Settings::default();
+        assert!(
+            settings
+                .keys
+                .bindings()
+                .iter()
+                .all(|(binding, _)| is_key_name(binding))
+        );
+        assert!(
+            settings
+                .hotkeys
+                .bindings()
+                .iter()
+                .all(|(binding, _)| is_key_name(binding))
+        );
+    }
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let settings: Settings = toml::from_str(
+            r#"
+            [window]
+            scale = 4
+
+            [keys]
+            a = "j"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.bios, None);
+        assert_eq!(settings.window.scale, 4);
+        assert!(settings.window.osd);
+        assert_eq!(settings.audio.latency_ms, 40);
+        assert_eq!(settings.keys.a, "j");
+        assert_eq!(settings.keys.b, "k");
+        assert_eq!(settings.hotkeys.turbo, "Space");
+        assert_eq!(settings.gamepad.deadzone, 0.5);
+    }
+
+    #[test]
+    fn rejects_typos_in_values() {
+        assert!(toml::from_str::<Settings>("[window]\nscale = \"big\"").is_err());
+    }
+
+    #[test]
+    fn rejects_misspelt_names() {
+        assert!(toml::from_str::<Settings>("bois = \"cgb_boot.bin\"").is_err());
+        assert!(toml::from_str::<Settings>("[window]\nsclae = 3").is_err());
+        assert!(toml::from_str::<Settings>("[hotkeys]\ntubro = \"t\"").is_err());
+        assert!(toml::from_str::<Settings>("[gamepad]\ndeadzoen = 0.2").is_err());
+    }
+}
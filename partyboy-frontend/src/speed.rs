@@ -0,0 +1,112 @@
+use crossbeam::channel::Sender;
+
+/// The speeds that can be picked, as a multiple of the normal speed
+pub const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+pub const NORMAL_SPEED: usize = 2;
+
+/// 10ms at 48KHz
+const BLOCK_SAMPLES: usize = 480;
+
+/// Sits between the emulator and the audio device when running at a different speed.
+///
+/// The emulator is still paced by the audio buffer, so running faster or slower only
+/// works if fewer or more samples reach the device. The audio is cut into short blocks
+/// which get dropped or played multiple times, keeping the pitch the same
+pub struct SpeedAdjuster {
+    speed: f64,
+    mute: bool,
+    block: Vec<(f32, f32)>,
+    /// How many times the next block should be played, the fractional part carries over
+    plays: f64,
+}
+
+impl SpeedAdjuster {
+    pub fn new(mute: bool) -> Self {
+        Self {
+            speed: SPEEDS[NORMAL_SPEED],
+            mute,
+            block: Vec::with_capacity(BLOCK_SAMPLES),
+            plays: 0.0,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        self.block.clear();
+        self.plays = 0.0;
+    }
+
+    pub fn push(&mut self, sample: (f32, f32), audio_s: &Sender<(f32, f32)>) {
+        if self.speed == SPEEDS[NORMAL_SPEED] {
+            let _ = audio_s.try_send(sample);
+            return;
+        }
+
+        self.block.push(if self.mute { (0.0, 0.0) } else { sample });
+        if self.block.len() < BLOCK_SAMPLES {
+            return;
+        }
+
+        self.plays += 1.0 / self.speed;
+        while self.plays >= 1.0 {
+            for &sample in &self.block {
+                let _ = audio_s.try_send(sample);
+            }
+            self.plays -= 1.0;
+        }
+        self.block.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes `blocks` blocks of samples numbered from 0 at `speed`, and returns the
+    /// numbers that reach the audio device
+    fn play(speed: f64, mute: bool, blocks: usize) -> Vec<f32> {
+        let (audio_s, audio_r) = crossbeam::channel::unbounded();
+        let mut adjuster = SpeedAdjuster::new(mute);
+        adjuster.set_speed(speed);
+        for i in 0..blocks * BLOCK_SAMPLES {
+            adjuster.push((i as f32, i as f32), &audio_s);
+        }
+        audio_r.try_iter().map(|(left, _)| left).collect()
+    }
+
+    /// The numbers of the samples in `block`
+    fn block(block: usize) -> impl Iterator<Item = f32> {
+        (block * BLOCK_SAMPLES..(block + 1) * BLOCK_SAMPLES).map(|i| i as f32)
+    }
+
+    #[test]
+    fn normal_speed_passes_samples_through() {
+        let expected: Vec<f32> = (0..2).flat_map(block).collect();
+        assert_eq!(play(1.0, false, 2), expected);
+    }
+
+    #[test]
+    fn slow_motion_repeats_blocks() {
+        let expected: Vec<f32> = (0..2)
+            .flat_map(|i| std::iter::repeat_n(i, 4))
+            .flat_map(block)
+            .collect();
+        assert_eq!(play(0.25, false, 2), expected);
+    }
+
+    #[test]
+    fn fast_forward_drops_blocks() {
+        let expected: Vec<f32> = [1, 3, 5, 7].into_iter().flat_map(block).collect();
+        assert_eq!(play(2.0, false, 8), expected);
+
+        let expected: Vec<f32> = [7, 15].into_iter().flat_map(block).collect();
+        assert_eq!(play(8.0, false, 16), expected);
+    }
+
+    #[test]
+    fn muted_blocks_are_silent() {
+        let played = play(2.0, true, 4);
+        assert_eq!(played.len(), 2 * BLOCK_SAMPLES);
+        assert!(played.iter().all(|&sample| sample == 0.0));
+    }
+}
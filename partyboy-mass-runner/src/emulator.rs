@@ -1,21 +1,23 @@
 use std::time::{Duration, Instant};
 
-use image::{ImageBuffer, RgbImage};
+use image::RgbImage;
 use partyboy_core::builder::GameBoyBuilder;
 use partyboy_core::input::Keycode;
-use partyboy_core::ppu::rgb::Rgb;
+use partyboy_core::ppu::pixel_format::PixelFormat;
 use partyboy_core::GameBoy;
 
 use crate::rom::{rom_display_name, Rom};
 use crate::types::{RunResult, WorkerStatus};
 
-pub fn into_img(fb: Vec<Rgb>) -> RgbImage {
-    let mut img: RgbImage = ImageBuffer::new(160, 144);
-    fb.into_iter()
-        .flat_map(|rgb| [rgb.r, rgb.g, rgb.b])
-        .zip(img.iter_mut())
-        .for_each(|(px, img_px)| *img_px = px);
-    img
+pub fn into_img(fb: Vec<u8>) -> RgbImage {
+    RgbImage::from_raw(160, 144, fb).expect("frame buffer is rendered as 160x144 RGB888")
+}
+
+/// The current frame in the layout `into_img` expects
+fn render_frame(gb: &GameBoy) -> Vec<u8> {
+    let mut frame = vec![0; 160 * 144 * PixelFormat::Rgb888.bytes_per_pixel()];
+    gb.render_frame_buffer(PixelFormat::Rgb888, &mut frame);
+    frame
 }
 
 /// Run `ticks` emulator ticks, optionally throttling to `speed_factor` * real time.
@@ -96,7 +98,7 @@ pub fn run_one_rom(
             status,
             0.0,
         );
-        let fourty = gb.get_frame_buffer().to_vec();
+        let fourty = render_frame(&gb);
 
         // Phase 2: 40-120s alternating Start / A every half second
         let mut pressed = false;
@@ -119,7 +121,7 @@ pub fn run_one_rom(
             status,
             40.0,
         );
-        let onetwenty = gb.get_frame_buffer().to_vec();
+        let onetwenty = render_frame(&gb);
 
         RunResult::Success {
             fourty_seconds_frame_buffer: fourty,
@@ -3,13 +3,12 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 
-use partyboy_core::ppu::rgb::Rgb;
-
 #[derive(Debug)]
 pub enum RunResult {
     Success {
-        fourty_seconds_frame_buffer: Vec<Rgb>,
-        onetwenty_seconds_frame_buffer: Vec<Rgb>,
+        /// RGB888, see [`crate::emulator::into_img`]
+        fourty_seconds_frame_buffer: Vec<u8>,
+        onetwenty_seconds_frame_buffer: Vec<u8>,
         rom_path: PathBuf,
     },
     Fail {
@@ -41,6 +41,23 @@ def download_gb_file(url, file_name):
     return
 
 
+def download_zipped_log(url, file_name):
+    if os.path.exists(file_name):
+        print("Skipping {}, already found.".format(file_name))
+        return
+
+    print("Fetching {}.".format(file_name))
+    with urllib.request.urlopen(url, context=ctx) as response, open(file_name + ".zip", 'wb') as out_file:
+        shutil.copyfileobj(response, out_file)
+
+    with zipfile.ZipFile(file_name + ".zip", 'r') as zip_ref, open(file_name, 'wb') as out_file:
+        with zip_ref.open(zip_ref.filelist[0]) as log_file:
+            shutil.copyfileobj(log_file, out_file)
+
+    os.remove(file_name + ".zip")
+    return
+
+
 current_working_directory = os.getcwd()
 path = Path(current_working_directory)
 
@@ -50,6 +67,7 @@ while path.name != "partyboy":
         sys.exit();
     path = path.parent
 
+root_path = path
 path = path.joinpath("test/test_roms")
 path.mkdir(exist_ok=True)
 os.chdir(path)
@@ -65,3 +83,10 @@ download_gb_file(dmg_acid_2_url, "dmg-acid2")
 
 cgb_acid_2_url = "https://github.com/mattcurrie/cgb-acid2/releases/download/v1.1/cgb-acid2.gbc"
 download_gb_file(cgb_acid_2_url, "cgb-acid2")
+
+# gameboy-doctor's reference traces, see partyboy-core/tests/trace.rs
+traces_path = root_path.joinpath("test/test_expected/traces/cpu_instrs")
+traces_path.mkdir(parents=True, exist_ok=True)
+for i in range(1, 12):
+    doctor_url = "https://raw.githubusercontent.com/robert/gameboy-doctor/master/truth/zipped/cpu_instrs/{}.zip".format(i)
+    download_zipped_log(doctor_url, str(traces_path.joinpath("{}.log".format(i))))
@@ -28,4 +28,18 @@ ret_code = subprocess.call([
     filter_exp
 ])
 
-sys.exit(ret_code)
+# the debugger (breakpoints, gdb stub, call stack) is only built with debug_info
+debug_ret_code = subprocess.call([
+    'cargo',
+    'nextest',
+    'run',
+    '--profile',
+    'ci',
+    '-p',
+    'partyboy-core',
+    '--lib',
+    '--features',
+    'debug_info'
+])
+
+sys.exit(ret_code or debug_ret_code)